@@ -1,7 +1,7 @@
 use proc_macro::TokenStream;
 use quote::{format_ident, quote};
 use std::str::FromStr;
-use syn::{parse_macro_input, Lit, Meta, MetaNameValue};
+use syn::{parse_macro_input, Lit, Meta};
 
 macro_rules! litstr {
     ($lit: expr) => {
@@ -13,12 +13,69 @@ macro_rules! litstr {
     };
 }
 
+struct SortKey {
+    field: syn::Member,
+    desc: bool,
+}
+
+fn sort_member(s: &str) -> syn::Member {
+    if let Ok(index) = s.parse::<usize>() {
+        syn::Member::Unnamed(index.into())
+    } else {
+        syn::Member::Named(format_ident!("{}", s))
+    }
+}
+
+impl FromStr for SortKey {
+    type Err = ::std::convert::Infallible;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut sp = s.split_whitespace();
+        let field = sort_member(sp.next().expect("empty sorting key"));
+        let desc = match sp.next() {
+            None | Some("asc") => false,
+            Some("desc") => true,
+            Some(v) => panic!("invalid sorting order: {}", v),
+        };
+        assert!(sp.next().is_none(), "invalid sorting key: {}", s);
+        Ok(Self { field, desc })
+    }
+}
+
+fn uses_type_params(tokens: proc_macro2::TokenStream, params: &[&syn::Ident]) -> bool {
+    tokens.into_iter().any(|t| match t {
+        proc_macro2::TokenTree::Ident(ref i) => params.contains(&i),
+        proc_macro2::TokenTree::Group(g) => uses_type_params(g.stream(), params),
+        _ => false,
+    })
+}
+
+fn parse_sort_keys(s: &str) -> Vec<SortKey> {
+    s.split(',').map(|v| v.trim().parse().unwrap()).collect()
+}
+
 /// Sorting for structures
 ///
-/// Automatically implements Eq, PartialEq, Ord and PartialOrd for single-field comparison,
-/// supports structures with no or a single lifetime.
+/// Automatically implements Eq, PartialEq, Ord and PartialOrd for field comparison, supports
+/// structures with lifetimes and generic type parameters (sorting fields, which depend on type
+/// parameters, get Ord bounds).
+///
+/// The default sorting field is "id", can be overriden with sorting(id = "field") attribute.
+///
+/// For tuple structs and newtypes, the default sorting field is 0, can be overriden with
+/// sorting(index = N) attribute. Field indexes can be used in keys as well.
+///
+/// Multiple fields can be compared lexicographically with sorting(keys = "field1, field2"),
+/// each key can be followed by "asc" (default) or "desc" order.
 ///
-/// The default sorting field is "id", can be overriden with sorting(id = "field") attribute:
+/// The whole ordering can be reversed with sorting(order = "desc"). A custom comparator
+/// function `fn(&T, &T) -> std::cmp::Ordering` can be set with sorting(with = "path::to::fn"),
+/// it is used for all sorting fields.
+///
+/// With sorting(hash), Hash is implemented over the same fields, keeping it consistent with Eq
+/// (can not be combined with a custom comparator).
+///
+/// The generated `sort_key()` method returns a reference to the sorting field (or a tuple of
+/// references for multiple keys).
 ///
 /// # Panics
 ///
@@ -33,69 +90,208 @@ macro_rules! litstr {
 ///     name: String,
 ///     value: u32
 /// }
+///
+/// #[derive(Sorting)]
+/// #[sorting(keys = "priority desc, name", hash)]
+/// struct MyTask {
+///     priority: u8,
+///     name: String,
+/// }
+///
+/// fn cmp_ignore_case(a: &String, b: &String) -> std::cmp::Ordering {
+///     a.to_lowercase().cmp(&b.to_lowercase())
+/// }
+///
+/// #[derive(Sorting)]
+/// #[sorting(id = "name", order = "desc", with = "cmp_ignore_case")]
+/// struct MyItem {
+///     name: String,
+/// }
+///
+/// #[derive(Sorting)]
+/// struct MyId(u64);
+///
+/// #[derive(Sorting)]
+/// #[sorting(index = 1)]
+/// struct MyPair(String, u32);
+///
+/// #[derive(Sorting)]
+/// struct MyEntry<'a, K, V> {
+///     id: K,
+///     label: &'a str,
+///     value: V,
+/// }
 /// ```
 #[proc_macro_derive(Sorting, attributes(sorting))]
 pub fn sorting_derive(input: TokenStream) -> TokenStream {
     let sitem = parse_macro_input!(input as syn::ItemStruct);
     let sid = &sitem.ident;
-    let mut owned = true;
-    for param in sitem.generics.params {
-        if let syn::GenericParam::Lifetime(_) = param {
-            owned = false;
-            break;
-        }
-    }
-    let mut id = "id".to_owned();
+    let mut keys = vec![SortKey {
+        field: if let syn::Fields::Unnamed(_) = sitem.fields {
+            syn::Member::Unnamed(0.into())
+        } else {
+            syn::Member::Named(format_ident!("id"))
+        },
+        desc: false,
+    }];
+    let mut reverse = false;
+    let mut with: Option<syn::Path> = None;
+    let mut hash = false;
     for a in &sitem.attrs {
         if a.path.is_ident("sorting") {
-            if let Ok(nameval) = a.parse_args::<MetaNameValue>() {
+            let Ok(Meta::List(list)) = a.parse_meta() else {
+                panic!("invalid attribute")
+            };
+            for nested in list.nested {
+                let nameval = match nested {
+                    syn::NestedMeta::Meta(Meta::NameValue(nameval)) => nameval,
+                    syn::NestedMeta::Meta(Meta::Path(path)) if path.is_ident("hash") => {
+                        hash = true;
+                        continue;
+                    }
+                    _ => panic!("invalid attribute"),
+                };
                 if nameval.path.is_ident("id") {
-                    id = litstr!(nameval.lit);
+                    keys = vec![SortKey {
+                        field: sort_member(&litstr!(nameval.lit)),
+                        desc: false,
+                    }];
+                } else if nameval.path.is_ident("index") {
+                    let Lit::Int(index) = nameval.lit else {
+                        panic!("invalid index value")
+                    };
+                    keys = vec![SortKey {
+                        field: syn::Member::Unnamed(
+                            index.base10_parse::<usize>().expect("invalid index").into(),
+                        ),
+                        desc: false,
+                    }];
+                } else if nameval.path.is_ident("keys") {
+                    keys = parse_sort_keys(&litstr!(nameval.lit));
+                } else if nameval.path.is_ident("order") {
+                    reverse = match litstr!(nameval.lit).as_str() {
+                        "asc" => false,
+                        "desc" => true,
+                        v => panic!("invalid sorting order: {}", v),
+                    };
+                } else if nameval.path.is_ident("with") {
+                    with = Some(syn::parse_str(&litstr!(nameval.lit)).expect("invalid path"));
                 } else {
                     panic!("invalid attribute")
                 }
-            } else {
-                panic!("invalid attribute")
             }
         }
     }
-    let i_id = format_ident!("{}", id);
-    let tr = if owned {
+    let mut cmps = keys.iter().map(|k| {
+        let f = &k.field;
+        let (a, b) = if k.desc ^ reverse {
+            (quote! { other }, quote! { self })
+        } else {
+            (quote! { self }, quote! { other })
+        };
+        if let Some(ref with) = with {
+            quote! { #with(&#a.#f, &#b.#f) }
+        } else {
+            quote! { #a.#f.cmp(&#b.#f) }
+        }
+    });
+    let first = cmps.next().unwrap();
+    let f_cmp = quote! { #first #(.then_with(|| #cmps))* };
+    let fields = keys.iter().map(|k| &k.field);
+    // a custom comparator may treat different values as equal, keep Eq consistent with Ord
+    let f_eq = if with.is_some() {
+        quote! { self.cmp(other) == ::std::cmp::Ordering::Equal }
+    } else {
+        quote! { #(self.#fields == other.#fields)&&* }
+    };
+    assert!(
+        !(hash && with.is_some()),
+        "hash can not be used with a custom comparator"
+    );
+    let key_types: Vec<&syn::Type> = keys
+        .iter()
+        .map(|key| {
+            let (_, field) = sitem
+                .fields
+                .iter()
+                .enumerate()
+                .find(|(i, f)| {
+                    if let Some(ref ident) = f.ident {
+                        key.field == syn::Member::Named(ident.clone())
+                    } else {
+                        key.field == syn::Member::Unnamed((*i).into())
+                    }
+                })
+                .expect("sorting field not found");
+            &field.ty
+        })
+        .collect();
+    let params: Vec<&syn::Ident> = sitem.generics.type_params().map(|p| &p.ident).collect();
+    let mut generics = sitem.generics.clone();
+    let mut hash_generics = sitem.generics.clone();
+    for ty in &key_types {
+        if uses_type_params(quote! { #ty }, &params) {
+            if with.is_none() {
+                generics
+                    .make_where_clause()
+                    .predicates
+                    .push(syn::parse_quote! { #ty: ::std::cmp::Ord });
+            }
+            hash_generics
+                .make_where_clause()
+                .predicates
+                .push(syn::parse_quote! { #ty: ::std::hash::Hash });
+        }
+    }
+    let fields = keys.iter().map(|k| &k.field);
+    let f_sort_key = if keys.len() == 1 {
+        let ty = key_types[0];
         quote! {
-            impl Eq for #sid {}
-            impl Ord for #sid {
-                fn cmp(&self, other: &Self) -> ::std::cmp::Ordering {
-                    self.#i_id.cmp(&other.#i_id)
-                }
+            pub fn sort_key(&self) -> &#ty {
+                #(&self.#fields)*
             }
-            impl PartialOrd for #sid {
-                fn partial_cmp(&self, other: &Self) -> Option<::std::cmp::Ordering> {
-                    Some(self.cmp(other))
-                }
+        }
+    } else {
+        quote! {
+            pub fn sort_key(&self) -> (#(&#key_types),*) {
+                (#(&self.#fields),*)
             }
-            impl PartialEq for #sid {
-                fn eq(&self, other: &Self) -> bool {
-                    self.#i_id == other.#i_id
+        }
+    };
+    let (orig_impl_generics, orig_ty_generics, orig_where_clause) = sitem.generics.split_for_impl();
+    let f_hash = if hash {
+        let fields = keys.iter().map(|k| &k.field);
+        let (impl_generics, ty_generics, where_clause) = hash_generics.split_for_impl();
+        quote! {
+            impl #impl_generics ::std::hash::Hash for #sid #ty_generics #where_clause {
+                fn hash<H: ::std::hash::Hasher>(&self, state: &mut H) {
+                    #(::std::hash::Hash::hash(&self.#fields, state);)*
                 }
             }
         }
     } else {
-        quote! {
-            impl<'srt> Eq for #sid<'srt> {}
-            impl<'srt> Ord for #sid<'srt> {
-                fn cmp(&self, other: &Self) -> ::std::cmp::Ordering {
-                    self.#i_id.cmp(&other.#i_id)
-                }
+        quote! {}
+    };
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let tr = quote! {
+        impl #orig_impl_generics #sid #orig_ty_generics #orig_where_clause {
+            #f_sort_key
+        }
+        #f_hash
+        impl #impl_generics Eq for #sid #ty_generics #where_clause {}
+        impl #impl_generics Ord for #sid #ty_generics #where_clause {
+            fn cmp(&self, other: &Self) -> ::std::cmp::Ordering {
+                #f_cmp
             }
-            impl<'srt> PartialOrd for #sid<'srt> {
-                fn partial_cmp(&self, other: &Self) -> Option<::std::cmp::Ordering> {
-                    Some(self.cmp(other))
-                }
+        }
+        impl #impl_generics PartialOrd for #sid #ty_generics #where_clause {
+            fn partial_cmp(&self, other: &Self) -> Option<::std::cmp::Ordering> {
+                Some(self.cmp(other))
             }
-            impl<'srt> PartialEq for #sid<'srt> {
-                fn eq(&self, other: &Self) -> bool {
-                    self.#i_id == other.#i_id
-                }
+        }
+        impl #impl_generics PartialEq for #sid #ty_generics #where_clause {
+            fn eq(&self, other: &Self) -> bool {
+                #f_eq
             }
         }
     };
@@ -157,24 +353,56 @@ fn format_case(s: &str, case: Case) -> String {
 }
 
 struct EnumVar {
-    id: String,
+    id: syn::Ident,
     name: Option<String>,
     aliases: Vec<String>,
-    skip: bool,
+    skip_from: bool,
+    skip_display: bool,
+    default: bool,
+    prefix: Option<String>,
+    data: bool,
 }
 
 impl EnumVar {
-    fn new(i: &syn::Ident) -> Self {
+    fn new(v: &syn::Variant) -> Self {
+        let data = match v.fields {
+            syn::Fields::Unit => false,
+            syn::Fields::Unnamed(ref f) if f.unnamed.len() == 1 => true,
+            _ => panic!("unsupported variant: {}", v.ident),
+        };
         Self {
-            id: i.to_string(),
+            id: v.ident.clone(),
             name: None,
             aliases: Vec::new(),
-            skip: false,
+            skip_from: false,
+            skip_display: false,
+            default: false,
+            prefix: None,
+            data,
+        }
+    }
+}
+
+fn enumstr_metas(attrs: &[syn::Attribute]) -> Vec<Meta> {
+    let mut result = Vec::new();
+    for a in attrs {
+        if a.path.is_ident("enumstr") {
+            let Ok(Meta::List(list)) = a.parse_meta() else {
+                panic!("invalid attribute")
+            };
+            for nested in list.nested {
+                let syn::NestedMeta::Meta(meta) = nested else {
+                    panic!("invalid attribute")
+                };
+                result.push(meta);
+            }
         }
     }
+    result
 }
 
-/// Implements Display and FromStr for enums with no data attached. The default behavior is to use
+/// Implements Display and FromStr for enums with unit variants (data variants are supported
+/// either as prefixed or as default ones, see below). The default behavior is to use
 /// snake_case. Can be overriden with enumstr(rename_all = "case")
 ///
 /// The possible case values: "lowercase", "UPPERCASE", "snake_case", "SCREAMING_SNAKE_CASE",
@@ -183,9 +411,41 @@ impl EnumVar {
 /// Individual fields can be overriden with enumstr(rename = "name"), altered with enumstr(alias =
 /// "alias")
 ///
-/// Fields, marked with enumstr(skip), are skipted in FromStr implementation.
+/// Fields, marked with enumstr(skip) or enumstr(skip = "from"), are skipted in FromStr
+/// implementation. Fields, marked with enumstr(skip_display) or enumstr(skip = "display"), are
+/// written by Display as "<hidden>", which can be overriden with enum-level enumstr(hidden =
+/// "text"). enumstr(skip = "both") hides the field in both directions.
+///
+/// A variant, marked with enumstr(default), is returned by FromStr for unknown values, so parsing
+/// never fails. If the default variant holds a single String field, the original value is
+/// captured and written back by Display.
+///
+/// The list of parseable variants and their names is available as `ALL` and `NAMES` constants
+/// and via `iter()` method (skipped, hidden and data variants are not listed).
+///
+/// Single-field variants, marked with enumstr(prefix = "prefix:"), are written by Display as
+/// "prefix:{value}" and parsed back with FromStr of the inner type (the prefix is matched
+/// case-sensitively).
+///
+/// Variant names are also available without allocation via `as_str()`, `AsRef<str>` and `From`
+/// for `&'static str` (`as_str()` is const and static unless the enum has a capturing default
+/// variant). These are not generated if the enum has prefixed variants.
+///
+/// For fieldless enums, enumstr(repr = "type") additionally implements `TryFrom<type>` and
+/// `From<Enum> for type`, using variant discriminants.
+///
+/// FromStr can be made case-insensitive with enum-level enumstr(case_insensitive) and can trim
+/// surrounding whitespace with enumstr(trim).
 ///
-/// To avoid additional dependancies, parse() Err type is String.
+/// With enum-level enumstr(serde), Serialize and Deserialize are implemented as well, using the
+/// same string representation as Display and FromStr (requires serde crate).
+///
+/// With enumstr(help), `variants_help()` method is generated, returning the comma-separated list
+/// of accepted values. enumstr(clap) additionally implements `clap::ValueEnum` (requires clap
+/// crate, the enum must implement Clone).
+///
+/// To avoid additional dependancies, parse() Err type is String. With enumstr(error = "bmart"),
+/// `bmart::Error` of kind InvalidData is returned instead, listing the accepted values.
 ///
 /// # Panics
 ///
@@ -201,88 +461,625 @@ impl EnumVar {
 ///     Field2,
 ///     #[enumstr(skip)]
 ///     SecretField,
+///     #[enumstr(skip = "both")]
+///     InternalField,
 ///     VeryLongField,
 ///     #[enumstr(rename = "another")]
 ///     #[enumstr(alias = "a")]
 ///     #[enumstr(alias = "af")]
 ///     AnotherField
 /// }
+///
+/// #[derive(EnumStr)]
+/// enum MyKind {
+///     Builtin,
+///     #[enumstr(default)]
+///     Other(String),
+/// }
+///
+/// #[derive(EnumStr)]
+/// enum MyUnit {
+///     Seconds,
+///     #[enumstr(prefix = "custom:")]
+///     Custom(String),
+/// }
+///
+/// #[derive(EnumStr, Copy, Clone)]
+/// #[enumstr(repr = "u16")]
+/// enum MyStatus {
+///     Ok = 200,
+///     NotFound = 404,
+/// }
+///
+/// #[derive(EnumStr)]
+/// #[enumstr(rename_all = "lowercase", case_insensitive, trim)]
+/// enum MyInput {
+///     Yes,
+///     No,
+/// }
 /// ```
 #[proc_macro_derive(EnumStr, attributes(enumstr))]
 pub fn enumstr_derive(input: TokenStream) -> TokenStream {
     let sitem = parse_macro_input!(input as syn::ItemEnum);
     let mut vars: Vec<EnumVar> = Vec::new();
     for var in &sitem.variants {
-        let mut evar = EnumVar::new(&var.ident);
-        for a in &var.attrs {
-            if a.path.is_ident("enumstr") {
-                if let Ok(nameval) = a.parse_args::<MetaNameValue>() {
-                    if nameval.path.is_ident("rename") {
-                        evar.name = Some(litstr!(nameval.lit));
-                    } else if nameval.path.is_ident("alias") {
-                        evar.aliases.push(litstr!(nameval.lit));
-                    } else {
-                        panic!("invalid attribute")
-                    }
-                } else if let Ok(name) = a.parse_args::<Meta>() {
-                    if name.path().is_ident("skip") {
-                        evar.skip = true;
-                    } else {
-                        panic!("invalid attribute")
+        let mut evar = EnumVar::new(var);
+        for meta in enumstr_metas(&var.attrs) {
+            match meta {
+                Meta::NameValue(nameval) if nameval.path.is_ident("rename") => {
+                    evar.name = Some(litstr!(nameval.lit));
+                }
+                Meta::NameValue(nameval) if nameval.path.is_ident("alias") => {
+                    evar.aliases.push(litstr!(nameval.lit));
+                }
+                Meta::Path(path) if path.is_ident("skip") => evar.skip_from = true,
+                Meta::Path(path) if path.is_ident("skip_display") => evar.skip_display = true,
+                Meta::NameValue(nameval) if nameval.path.is_ident("skip") => {
+                    match litstr!(nameval.lit).as_str() {
+                        "from" => evar.skip_from = true,
+                        "display" => evar.skip_display = true,
+                        "both" => {
+                            evar.skip_from = true;
+                            evar.skip_display = true;
+                        }
+                        v => panic!("invalid skip value: {}", v),
                     }
-                } else {
-                    panic!("invalid attribute")
                 }
+                Meta::Path(path) if path.is_ident("default") => evar.default = true,
+                Meta::NameValue(nameval) if nameval.path.is_ident("prefix") => {
+                    evar.prefix = Some(litstr!(nameval.lit));
+                }
+                _ => panic!("invalid attribute"),
             }
         }
+        assert!(
+            !evar.data || evar.default || evar.prefix.is_some(),
+            "data variants must be marked either as default or with a prefix: {}",
+            evar.id
+        );
+        assert!(
+            evar.data || evar.prefix.is_none(),
+            "prefixed variants must have a single field: {}",
+            evar.id
+        );
+        assert!(
+            !(evar.default && evar.prefix.is_some()),
+            "default variant can not have a prefix: {}",
+            evar.id
+        );
         vars.push(evar);
     }
     let sid = &sitem.ident;
     let mut case = Case::Snake;
-    for a in &sitem.attrs {
-        if a.path.is_ident("enumstr") {
-            if let Ok(nameval) = a.parse_args::<MetaNameValue>() {
-                if nameval.path.is_ident("rename_all") {
-                    case = litstr!(nameval.lit).parse().unwrap();
-                } else {
-                    panic!("invalid attribute")
-                }
-            } else {
-                panic!("invalid attribute")
+    let mut case_insensitive = false;
+    let mut trim = false;
+    let mut repr: Option<syn::Ident> = None;
+    let mut bmart_error = false;
+    let mut serde = false;
+    let mut hidden = "<hidden>".to_owned();
+    let mut help = false;
+    let mut clap = false;
+    for meta in enumstr_metas(&sitem.attrs) {
+        match meta {
+            Meta::NameValue(nameval) if nameval.path.is_ident("rename_all") => {
+                case = litstr!(nameval.lit).parse().unwrap();
+            }
+            Meta::Path(path) if path.is_ident("case_insensitive") => case_insensitive = true,
+            Meta::Path(path) if path.is_ident("trim") => trim = true,
+            Meta::Path(path) if path.is_ident("serde") => serde = true,
+            Meta::Path(path) if path.is_ident("help") => help = true,
+            Meta::Path(path) if path.is_ident("clap") => {
+                help = true;
+                clap = true;
+            }
+            Meta::NameValue(nameval) if nameval.path.is_ident("hidden") => {
+                hidden = litstr!(nameval.lit);
+            }
+            Meta::NameValue(nameval) if nameval.path.is_ident("error") => {
+                bmart_error = match litstr!(nameval.lit).as_str() {
+                    "string" => false,
+                    "bmart" => true,
+                    v => panic!("unsupported error type: {}", v),
+                };
             }
+            Meta::NameValue(nameval) if nameval.path.is_ident("repr") => {
+                repr = Some(format_ident!("{}", litstr!(nameval.lit)));
+            }
+            _ => panic!("invalid attribute"),
         }
     }
-    let mut st_to = "match self {".to_owned();
-    let mut st_from = "match s {".to_owned();
-    for var in vars {
-        let name = if let Some(name) = var.name {
-            name
+    let norm = |s: &str| {
+        if case_insensitive {
+            s.to_lowercase()
         } else {
-            format_case(&var.id, case)
-        };
-        st_to += &format!("{}::{} => \"{}\",", sid, var.id, name);
-        if !var.skip {
-            st_from += &format!("\"{}\"", name);
-            for alias in var.aliases {
-                st_from += &format!(" | \"{}\"", alias);
+            s.to_owned()
+        }
+    };
+    let mut m_to = Vec::new();
+    let mut m_from = Vec::new();
+    let (err_ty, f_err) = if bmart_error {
+        (
+            quote! { ::bmart::Error },
+            quote! {
+                Err(::bmart::Error::invalid_data(format!(
+                    "value unsupported: {} (expected one of: {})",
+                    v,
+                    Self::NAMES.join(", ")
+                )))
+            },
+        )
+    } else {
+        (
+            quote! { String },
+            quote! { Err(format!("value unsupported: {}", v)) },
+        )
+    };
+    let mut m_default = quote! {{ let v = s; #f_err }};
+    let mut all = Vec::new();
+    let mut all_names = Vec::new();
+    let mut has_capture = false;
+    let mut has_prefix = false;
+    let mut m_disp = Vec::new();
+    let mut m_prefix = Vec::new();
+    let mut m_possible = Vec::new();
+    for var in vars {
+        let name = var
+            .name
+            .unwrap_or_else(|| format_case(&var.id.to_string(), case));
+        let id = &var.id;
+        if let Some(ref prefix) = var.prefix {
+            has_prefix = true;
+            if var.skip_display {
+                m_disp.push(quote! { #sid::#id(_) => f.write_str(#hidden) });
+            } else {
+                m_disp.push(quote! { #sid::#id(v) => write!(f, "{}{}", #prefix, v) });
+            }
+            if !var.skip_from {
+                m_prefix.push(quote! {
+                    if let Some(v) = input.strip_prefix(#prefix) {
+                        return match v.parse() {
+                            Ok(v) => Ok(#sid::#id(v)),
+                            Err(_) => {
+                                let v = s;
+                                #f_err
+                            }
+                        };
+                    }
+                });
+            }
+            continue;
+        }
+        if var.default {
+            m_default = if var.data {
+                quote! { Ok(#sid::#id(s.into())) }
+            } else {
+                quote! { Ok(#sid::#id) }
+            };
+        }
+        if var.data {
+            has_capture = true;
+            if var.skip_display {
+                m_to.push(quote! { #sid::#id(_) => #hidden });
+                m_disp.push(quote! { #sid::#id(_) => f.write_str(#hidden) });
+            } else {
+                m_to.push(quote! { #sid::#id(v) => ::std::convert::AsRef::<str>::as_ref(v) });
+                m_disp.push(
+                    quote! { #sid::#id(v) => f.write_str(::std::convert::AsRef::<str>::as_ref(v)) },
+                );
             }
-            st_from += &format!(" => Ok({}::{}),", sid, var.id);
+            continue;
+        }
+        let display_name = if var.skip_display { &hidden } else { &name };
+        m_to.push(quote! { #sid::#id => #display_name });
+        m_disp.push(quote! { #sid::#id => f.write_str(#display_name) });
+        if !var.skip_from {
+            if !var.skip_display {
+                all.push(quote! { #sid::#id });
+                all_names.push(name.clone());
+                let aliases = &var.aliases;
+                m_possible.push(quote! {
+                    #sid::#id => Some(::clap::builder::PossibleValue::new(#name)#(.alias(#aliases))*)
+                });
+            }
+            let names = std::iter::once(norm(&name)).chain(var.aliases.iter().map(|a| norm(a)));
+            m_from.push(quote! { #(#names)|* => Ok(#sid::#id) });
         }
     }
-    st_to += "}";
-    st_from += "_ => Err(\"value unsupported: \".to_owned() + s)}";
-    let m_to: syn::ExprMatch = syn::parse_str(&st_to).unwrap();
-    let m_from: syn::ExprMatch = syn::parse_str(&st_from).unwrap();
+    let f_input = if trim {
+        quote! { s.trim() }
+    } else {
+        quote! { s }
+    };
+    let f_match = if case_insensitive {
+        quote! { input.to_lowercase().as_str() }
+    } else {
+        quote! { input }
+    };
+    // variants with prefixed data have no names, variants with captured values can not provide
+    // static names
+    let (f_as_str, f_static) = if has_prefix {
+        (quote! {}, quote! {})
+    } else if has_capture {
+        (
+            quote! {
+                pub fn as_str(&self) -> &str {
+                    match self { #(#m_to,)* }
+                }
+            },
+            quote! {
+                impl AsRef<str> for #sid {
+                    fn as_ref(&self) -> &str {
+                        self.as_str()
+                    }
+                }
+            },
+        )
+    } else {
+        (
+            quote! {
+                pub const fn as_str(&self) -> &'static str {
+                    match self { #(#m_to,)* }
+                }
+            },
+            quote! {
+                impl AsRef<str> for #sid {
+                    fn as_ref(&self) -> &str {
+                        self.as_str()
+                    }
+                }
+                impl From<#sid> for &'static str {
+                    fn from(v: #sid) -> Self {
+                        v.as_str()
+                    }
+                }
+            },
+        )
+    };
+    let f_repr = if let Some(repr) = repr {
+        assert!(
+            !has_capture && !has_prefix,
+            "repr is not supported for enums with data"
+        );
+        let ids = sitem.variants.iter().map(|v| &v.ident);
+        let ids2 = ids.clone();
+        quote! {
+            impl ::std::convert::TryFrom<#repr> for #sid {
+                type Error = #err_ty;
+                fn try_from(v: #repr) -> Result<Self, Self::Error> {
+                    #(if v == #sid::#ids as #repr {
+                        return Ok(#sid::#ids2);
+                    })*
+                    #f_err
+                }
+            }
+            impl From<#sid> for #repr {
+                fn from(v: #sid) -> Self {
+                    v as #repr
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
+    let f_serde = if serde {
+        quote! {
+            impl ::serde::Serialize for #sid {
+                fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+                where
+                    S: ::serde::Serializer,
+                {
+                    serializer.collect_str(self)
+                }
+            }
+            impl<'de> ::serde::Deserialize<'de> for #sid {
+                fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+                where
+                    D: ::serde::Deserializer<'de>,
+                {
+                    let s = <String as ::serde::Deserialize>::deserialize(deserializer)?;
+                    s.parse().map_err(::serde::de::Error::custom)
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
+    let f_help = if help {
+        quote! {
+            impl #sid {
+                pub fn variants_help() -> String {
+                    Self::NAMES.join(", ")
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
+    let f_clap = if clap {
+        quote! {
+            impl ::clap::ValueEnum for #sid {
+                fn value_variants<'a>() -> &'a [Self] {
+                    Self::ALL
+                }
+                fn to_possible_value(&self) -> Option<::clap::builder::PossibleValue> {
+                    #[allow(unreachable_patterns)]
+                    match self {
+                        #(#m_possible,)*
+                        _ => None
+                    }
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
     let tr = quote! {
+        #f_repr
+        #f_serde
+        #f_help
+        #f_clap
+        impl #sid {
+            pub const ALL: &'static [#sid] = &[#(#all),*];
+            pub const NAMES: &'static [&'static str] = &[#(#all_names),*];
+            pub fn iter() -> impl Iterator<Item = &'static #sid> {
+                Self::ALL.iter()
+            }
+            #f_as_str
+        }
+        #f_static
         impl ::std::fmt::Display for #sid {
             fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> ::std::fmt::Result {
-                write!(f, "{}", #m_to)
+                match self { #(#m_disp,)* }
             }
         }
         impl ::std::str::FromStr for #sid {
-            type Err = String;
+            type Err = #err_ty;
             fn from_str(s: &str) -> Result<Self, Self::Err> {
-                #m_from
+                let input = #f_input;
+                #(#m_prefix)*
+                match #f_match {
+                    #(#m_from,)*
+                    _ => #m_default
+                }
+            }
+        }
+    };
+    TokenStream::from(tr)
+}
+
+/// Generates a fieldless tag enum for enums with data (`{Enum}Tag` by default, can be overriden
+/// with enumtag(name = "Name")), plus `tag()` method for the source enum.
+///
+/// The tag enum implements Debug, Copy, Clone, Eq, PartialEq, Hash, Display and has const
+/// `as_str()` method. Tag names use snake_case by default, can be altered with
+/// enumtag(rename_all = "case") and individual variants with enumtag(rename = "name"), using the
+/// same rules as EnumStr.
+///
+/// # Panics
+///
+/// Will panic on invalid attributes and if the expression is not an enum
+///
+/// ```rust
+/// use bmart_derive::EnumTag;
+///
+/// #[derive(EnumTag)]
+/// #[enumtag(rename_all = "kebab-case")]
+/// enum Command {
+///     Start(String),
+///     Stop { force: bool },
+///     #[enumtag(rename = "status")]
+///     GetStatus,
+/// }
+///
+/// assert_eq!(Command::Stop { force: true }.tag().to_string(), "stop");
+/// assert_eq!(Command::tag(&Command::GetStatus), CommandTag::GetStatus);
+/// ```
+#[proc_macro_derive(EnumTag, attributes(enumtag))]
+pub fn enumtag_derive(input: TokenStream) -> TokenStream {
+    let sitem = parse_macro_input!(input as syn::ItemEnum);
+    let sid = &sitem.ident;
+    let vis = &sitem.vis;
+    let mut case = Case::Snake;
+    let mut tid = format_ident!("{}Tag", sid);
+    for a in &sitem.attrs {
+        if a.path.is_ident("enumtag") {
+            let Ok(nameval) = a.parse_args::<syn::MetaNameValue>() else {
+                panic!("invalid attribute")
+            };
+            if nameval.path.is_ident("rename_all") {
+                case = litstr!(nameval.lit).parse().unwrap();
+            } else if nameval.path.is_ident("name") {
+                tid = format_ident!("{}", litstr!(nameval.lit));
+            } else {
+                panic!("invalid attribute")
+            }
+        }
+    }
+    let mut ids = Vec::new();
+    let mut names = Vec::new();
+    let mut patterns = Vec::new();
+    for var in &sitem.variants {
+        let id = &var.ident;
+        let mut name = format_case(&id.to_string(), case);
+        for a in &var.attrs {
+            if a.path.is_ident("enumtag") {
+                let Ok(nameval) = a.parse_args::<syn::MetaNameValue>() else {
+                    panic!("invalid attribute")
+                };
+                if nameval.path.is_ident("rename") {
+                    name = litstr!(nameval.lit);
+                } else {
+                    panic!("invalid attribute")
+                }
+            }
+        }
+        patterns.push(match var.fields {
+            syn::Fields::Unit => quote! { #sid::#id },
+            syn::Fields::Unnamed(_) => quote! { #sid::#id(..) },
+            syn::Fields::Named(_) => quote! { #sid::#id { .. } },
+        });
+        ids.push(id);
+        names.push(name);
+    }
+    let (impl_generics, ty_generics, where_clause) = sitem.generics.split_for_impl();
+    let tr = quote! {
+        #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+        #vis enum #tid {
+            #(#ids),*
+        }
+        impl #tid {
+            pub const fn as_str(&self) -> &'static str {
+                match self {
+                    #(#tid::#ids => #names),*
+                }
+            }
+        }
+        impl ::std::fmt::Display for #tid {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                f.write_str(self.as_str())
+            }
+        }
+        impl #impl_generics #sid #ty_generics #where_clause {
+            pub fn tag(&self) -> #tid {
+                match self {
+                    #(#patterns => #tid::#ids),*
+                }
+            }
+        }
+    };
+    TokenStream::from(tr)
+}
+
+fn push_field(result: &mut Vec<String>, field: &str) -> Result<(), String> {
+    if field.is_empty() || field.starts_with(|c: char| c.is_ascii_digit()) {
+        return Err("positional arguments are not supported".to_owned());
+    }
+    if !result.iter().any(|f| f == field) {
+        result.push(field.to_owned());
+    }
+    Ok(())
+}
+
+/// Collects the named arguments of the template, escapes and format specs are handled as in
+/// format!, including width and precision arguments (name$)
+fn template_fields(template: &str) -> Result<Vec<String>, String> {
+    let mut result: Vec<String> = Vec::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => {
+                if chars.peek() == Some(&'{') {
+                    chars.next();
+                    continue;
+                }
+                let mut field = String::new();
+                let mut spec = None;
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(':') if spec.is_none() => spec = Some(String::new()),
+                        Some(c) => spec.as_mut().unwrap_or(&mut field).push(c),
+                        None => return Err("unterminated format argument".to_owned()),
+                    }
+                }
+                push_field(&mut result, field.trim())?;
+                if let Some(spec) = spec {
+                    if spec.contains(".*") {
+                        return Err("precision .* is not supported".to_owned());
+                    }
+                    let mut arg = String::new();
+                    for c in spec.chars() {
+                        if c.is_alphanumeric() || c == '_' {
+                            arg.push(c);
+                        } else {
+                            if c == '$' && !arg.is_empty() {
+                                push_field(&mut result, &arg)?;
+                            }
+                            arg.clear();
+                        }
+                    }
+                }
+            }
+            '}' => {
+                if chars.next() != Some('}') {
+                    return Err("unmatched `}` in the template, use `}}` to escape".to_owned());
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(result)
+}
+
+/// Implements Display for structures with named fields using a format template, set with
+/// display("template") attribute. The template refers to fields by name and supports the
+/// standard format specs, width and precision may refer to fields as well (`{value:>width$}`).
+/// Braces are escaped as `{{` and `}}`.
+///
+/// # Panics
+///
+/// Will panic on invalid attributes and if the expression is not a struct
+///
+/// ```rust
+/// use bmart_derive::StructDisplay;
+///
+/// #[derive(StructDisplay)]
+/// #[display("{name} ({value:.2})")]
+/// struct MyStruct {
+///     name: String,
+///     value: f64,
+/// }
+///
+/// let s = MyStruct { name: "temp".to_owned(), value: 25.0 };
+/// assert_eq!(s.to_string(), "temp (25.00)");
+///
+/// #[derive(StructDisplay)]
+/// #[display("{{{name:>width$}}}")]
+/// struct Padded {
+///     name: String,
+///     width: usize,
+/// }
+///
+/// let s = Padded { name: "x".to_owned(), width: 3 };
+/// assert_eq!(s.to_string(), "{  x}");
+/// ```
+///
+/// Unmatched braces and positional arguments are rejected at compile time:
+///
+/// ```compile_fail
+/// use bmart_derive::StructDisplay;
+///
+/// #[derive(StructDisplay)]
+/// #[display("{name}}")]
+/// struct MyStruct {
+///     name: String,
+/// }
+/// ```
+#[proc_macro_derive(StructDisplay, attributes(display))]
+pub fn struct_display_derive(input: TokenStream) -> TokenStream {
+    let sitem = parse_macro_input!(input as syn::ItemStruct);
+    let sid = &sitem.ident;
+    let mut template: Option<syn::LitStr> = None;
+    for a in &sitem.attrs {
+        if a.path.is_ident("display") {
+            template = Some(a.parse_args().expect("invalid attribute"));
+        }
+    }
+    let template = template.expect("display template not specified");
+    let fields = match template_fields(&template.value()) {
+        Ok(v) => v.into_iter().map(|f| format_ident!("{}", f)),
+        Err(e) => {
+            return syn::Error::new(template.span(), e)
+                .to_compile_error()
+                .into()
+        }
+    };
+    let fields2 = fields.clone();
+    let (impl_generics, ty_generics, where_clause) = sitem.generics.split_for_impl();
+    let tr = quote! {
+        impl #impl_generics ::std::fmt::Display for #sid #ty_generics #where_clause {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                write!(f, #template, #(#fields = self.#fields2),*)
             }
         }
     };
@@ -0,0 +1,97 @@
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveTime, TimeZone, Weekday};
+
+/// Scheduler behavior for ticks outside the allowed windows
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum OutsideWindow {
+    /// Drop the tick
+    #[default]
+    Suppress,
+    /// Fire the tick at the beginning of the next allowed window
+    Defer,
+}
+
+/// Calendar of allowed (local time) windows. If no windows are set, the whole day is allowed,
+/// excluding the blackout weekdays and dates
+#[derive(Debug, Clone, Default)]
+pub struct Calendar {
+    // start, end (None for the end of the day)
+    windows: Vec<(NaiveTime, Option<NaiveTime>)>,
+    excluded_weekdays: Vec<Weekday>,
+    excluded_dates: Vec<NaiveDate>,
+}
+
+impl Calendar {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Adds an allowed daily window, if the end is less than the start, the window ends on the
+    /// next day
+    #[must_use]
+    pub fn allow(mut self, start: NaiveTime, end: NaiveTime) -> Self {
+        if end < start {
+            self.windows.push((start, None));
+            self.windows.push((NaiveTime::MIN, Some(end)));
+        } else {
+            self.windows.push((start, Some(end)));
+        }
+        self.windows.sort_by_key(|w| w.0);
+        self
+    }
+    #[inline]
+    #[must_use]
+    pub fn exclude_weekday(mut self, weekday: Weekday) -> Self {
+        self.excluded_weekdays.push(weekday);
+        self
+    }
+    /// Excludes the date (e.g. a holiday)
+    #[inline]
+    #[must_use]
+    pub fn exclude_date(mut self, date: NaiveDate) -> Self {
+        self.excluded_dates.push(date);
+        self
+    }
+    fn is_date_allowed(&self, date: NaiveDate) -> bool {
+        !self.excluded_weekdays.contains(&date.weekday()) && !self.excluded_dates.contains(&date)
+    }
+    fn windows(&self) -> &[(NaiveTime, Option<NaiveTime>)] {
+        const WHOLE_DAY: &[(NaiveTime, Option<NaiveTime>)] = &[(NaiveTime::MIN, None)];
+        if self.windows.is_empty() {
+            WHOLE_DAY
+        } else {
+            &self.windows
+        }
+    }
+    pub fn is_allowed(&self, t: DateTime<Local>) -> bool {
+        if !self.is_date_allowed(t.date_naive()) {
+            return false;
+        }
+        let time = t.time();
+        self.windows()
+            .iter()
+            .any(|(start, end)| time >= *start && end.map_or(true, |end| time < end))
+    }
+    /// Returns the next allowed time, starting from t (within one year)
+    pub fn next_allowed(&self, t: DateTime<Local>) -> Option<DateTime<Local>> {
+        let mut date = t.date_naive();
+        for day in 0..=366 {
+            if self.is_date_allowed(date) {
+                for (start, end) in self.windows() {
+                    let start = if day == 0 && t.time() > *start {
+                        t.time()
+                    } else {
+                        *start
+                    };
+                    if end.map_or(true, |end| start < end) {
+                        if let Some(v) = Local.from_local_datetime(&date.and_time(start)).earliest()
+                        {
+                            return Some(v);
+                        }
+                    }
+                }
+            }
+            date = date.succ_opt()?;
+        }
+        None
+    }
+}
@@ -26,13 +26,85 @@ macro_rules! worker {
         }};
 }
 
-#[derive(Debug, Eq, PartialEq)]
+/// Same as `worker!` but also returns a shutdown `Notify`. When notified with `notify_one`, the
+/// worker loop exits after the current run is finished, so the returned `JoinHandle` can be
+/// awaited
+#[macro_export]
+macro_rules! worker_cancellable {
+    ($target: expr, $($arg:tt)+) => {{
+        let trigger = std::sync::Arc::new(tokio::sync::Notify::new());
+        let shutdown = std::sync::Arc::new(tokio::sync::Notify::new());
+        let tc = trigger.clone();
+        let sc = shutdown.clone();
+        let fut = tokio::task::spawn(async move {
+            loop {
+                tokio::select! {
+                    biased;
+                    () = sc.notified() => break,
+                    () = tc.notified() => {}
+                }
+                $target($($arg)+).await;
+            }
+        });
+        (trigger, shutdown, fut)
+    }};
+    ($target: expr) => {{
+        let trigger = std::sync::Arc::new(tokio::sync::Notify::new());
+        let shutdown = std::sync::Arc::new(tokio::sync::Notify::new());
+        let tc = trigger.clone();
+        let sc = shutdown.clone();
+        let fut = tokio::task::spawn(async move {
+            loop {
+                tokio::select! {
+                    biased;
+                    () = sc.notified() => break,
+                    () = tc.notified() => {}
+                }
+                $target().await;
+            }
+        });
+        (trigger, shutdown, fut)
+    }};
+}
+
+/// Same as `worker!` but backed by a bounded queue: N triggers cause N runs, up to the queue
+/// capacity. Returns `workers::QueuedTrigger` instead of `Notify`
+#[macro_export]
+macro_rules! worker_queued {
+    ($capacity: expr, $overflow: expr, $target: expr, $($arg:tt)+) => {{
+        let (trigger, mut rx) = $crate::workers::QueuedTrigger::new($capacity, $overflow);
+        let fut = tokio::task::spawn(async move {
+            while rx.recv().await.is_some() {
+                $target($($arg)+).await;
+            }
+        });
+        (trigger, fut)
+    }};
+    ($capacity: expr, $overflow: expr, $target: expr) => {{
+        let (trigger, mut rx) = $crate::workers::QueuedTrigger::new($capacity, $overflow);
+        let fut = tokio::task::spawn(async move {
+            while rx.recv().await.is_some() {
+                $target().await;
+            }
+        });
+        (trigger, fut)
+    }};
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum ErrorKind {
     Duplicate,
     NotFound,
     Timeout,
     InvalidData,
     Internal,
+    Shutdown,
+    AccessDenied,
+    Busy,
+    Unsupported,
+    NotReady,
+    IO,
+    Canceled,
 }
 
 impl ErrorKind {
@@ -43,48 +115,195 @@ impl ErrorKind {
             ErrorKind::Timeout => "Timeout",
             ErrorKind::Internal => "Internal",
             ErrorKind::InvalidData => "InvalidData",
+            ErrorKind::Shutdown => "Shutdown",
+            ErrorKind::AccessDenied => "Access denied",
+            ErrorKind::Busy => "Busy",
+            ErrorKind::Unsupported => "Unsupported",
+            ErrorKind::NotReady => "Not ready",
+            ErrorKind::IO => "I/O",
+            ErrorKind::Canceled => "Canceled",
         }
     }
+    /// Stable numeric code, can be used to transport errors over RPC
+    pub fn code(&self) -> i16 {
+        match self {
+            ErrorKind::Duplicate => 1,
+            ErrorKind::NotFound => 2,
+            ErrorKind::Timeout => 3,
+            ErrorKind::InvalidData => 4,
+            ErrorKind::Internal => 5,
+            ErrorKind::Shutdown => 6,
+            ErrorKind::AccessDenied => 7,
+            ErrorKind::Busy => 8,
+            ErrorKind::Unsupported => 9,
+            ErrorKind::NotReady => 10,
+            ErrorKind::IO => 11,
+            ErrorKind::Canceled => 12,
+        }
+    }
+    pub fn from_code(code: i16) -> Option<Self> {
+        Some(match code {
+            1 => ErrorKind::Duplicate,
+            2 => ErrorKind::NotFound,
+            3 => ErrorKind::Timeout,
+            4 => ErrorKind::InvalidData,
+            5 => ErrorKind::Internal,
+            6 => ErrorKind::Shutdown,
+            7 => ErrorKind::AccessDenied,
+            8 => ErrorKind::Busy,
+            9 => ErrorKind::Unsupported,
+            10 => ErrorKind::NotReady,
+            11 => ErrorKind::IO,
+            12 => ErrorKind::Canceled,
+            _ => return None,
+        })
+    }
 }
 
+/// The crate error. Use the constructors to create errors, the struct can not be built with a
+/// literal as the error source is private (since 0.3)
 #[derive(Debug)]
 pub struct Error {
     pub kind: ErrorKind,
     pub message: Option<String>,
+    source: Option<Box<dyn std::error::Error + Send + Sync + 'static>>,
 }
 
 #[allow(clippy::must_use_candidate)]
 impl Error {
+    pub fn new<T: fmt::Display>(kind: ErrorKind, message: T) -> Self {
+        Self {
+            kind,
+            message: Some(message.to_string()),
+            source: None,
+        }
+    }
     pub fn duplicate<T: fmt::Display>(message: T) -> Self {
         Self {
             kind: ErrorKind::Duplicate,
             message: Some(message.to_string()),
+            source: None,
         }
     }
     pub fn not_found<T: fmt::Display>(message: T) -> Self {
         Self {
             kind: ErrorKind::NotFound,
             message: Some(message.to_string()),
+            source: None,
         }
     }
     pub fn timeout() -> Self {
         Self {
             kind: ErrorKind::Timeout,
             message: None,
+            source: None,
+        }
+    }
+    pub fn shutdown() -> Self {
+        Self {
+            kind: ErrorKind::Shutdown,
+            message: None,
+            source: None,
         }
     }
     pub fn internal<T: fmt::Display>(message: T) -> Self {
         Self {
             kind: ErrorKind::Internal,
             message: Some(message.to_string()),
+            source: None,
         }
     }
     pub fn invalid_data<T: fmt::Display>(message: T) -> Self {
         Self {
             kind: ErrorKind::InvalidData,
             message: Some(message.to_string()),
+            source: None,
         }
     }
+    pub fn access_denied<T: fmt::Display>(message: T) -> Self {
+        Self::new(ErrorKind::AccessDenied, message)
+    }
+    pub fn busy<T: fmt::Display>(message: T) -> Self {
+        Self::new(ErrorKind::Busy, message)
+    }
+    pub fn unsupported<T: fmt::Display>(message: T) -> Self {
+        Self::new(ErrorKind::Unsupported, message)
+    }
+    pub fn not_ready<T: fmt::Display>(message: T) -> Self {
+        Self::new(ErrorKind::NotReady, message)
+    }
+    pub fn io<T: fmt::Display>(message: T) -> Self {
+        Self::new(ErrorKind::IO, message)
+    }
+    pub fn canceled() -> Self {
+        Self {
+            kind: ErrorKind::Canceled,
+            message: None,
+            source: None,
+        }
+    }
+    pub fn internal_with_source<T, E>(message: T, source: E) -> Self
+    where
+        T: fmt::Display,
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        Self {
+            kind: ErrorKind::Internal,
+            message: Some(message.to_string()),
+            source: Some(Box::new(source)),
+        }
+    }
+    /// Sets the error source
+    #[must_use]
+    pub fn with_source<E>(mut self, source: E) -> Self
+    where
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        self.source = Some(Box::new(source));
+        self
+    }
+    fn from_source<C, E>(context: C, source: E) -> Self
+    where
+        C: fmt::Display,
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        let source: Box<dyn std::error::Error + Send + Sync + 'static> = Box::new(source);
+        let kind = match source.downcast_ref::<Error>() {
+            Some(e) => e.kind,
+            None => ErrorKind::Internal,
+        };
+        Self {
+            kind,
+            message: Some(context.to_string()),
+            source: Some(source),
+        }
+    }
+}
+
+/// Adds context to errors, keeping the original error as the source
+///
+/// bmart errors keep their kind, other errors become Internal
+pub trait ResultExt<T> {
+    /// # Errors
+    ///
+    /// Will return `Err` with the context message if the result is `Err`
+    fn context<C: fmt::Display>(self, context: C) -> Result<T, Error>;
+    /// # Errors
+    ///
+    /// Will return `Err` with the context message if the result is `Err`
+    fn with_context<C: fmt::Display, F: FnOnce() -> C>(self, f: F) -> Result<T, Error>;
+}
+
+impl<T, E> ResultExt<T> for Result<T, E>
+where
+    E: std::error::Error + Send + Sync + 'static,
+{
+    fn context<C: fmt::Display>(self, context: C) -> Result<T, Error> {
+        self.map_err(|e| Error::from_source(context, e))
+    }
+    fn with_context<C: fmt::Display, F: FnOnce() -> C>(self, f: F) -> Result<T, Error> {
+        self.map_err(|e| Error::from_source(f(), e))
+    }
 }
 
 impl fmt::Display for Error {
@@ -97,8 +316,129 @@ impl fmt::Display for Error {
     }
 }
 
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|e| e.as_ref() as &(dyn std::error::Error + 'static))
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        let kind = match e.kind() {
+            std::io::ErrorKind::NotFound => ErrorKind::NotFound,
+            std::io::ErrorKind::PermissionDenied => ErrorKind::AccessDenied,
+            std::io::ErrorKind::AlreadyExists => ErrorKind::Duplicate,
+            std::io::ErrorKind::TimedOut => ErrorKind::Timeout,
+            std::io::ErrorKind::InvalidInput | std::io::ErrorKind::InvalidData => {
+                ErrorKind::InvalidData
+            }
+            std::io::ErrorKind::Unsupported => ErrorKind::Unsupported,
+            _ => ErrorKind::IO,
+        };
+        Self {
+            kind,
+            message: Some(e.to_string()),
+            source: Some(Box::new(e)),
+        }
+    }
+}
+
+impl From<Error> for std::io::Error {
+    fn from(e: Error) -> Self {
+        let kind = match e.kind {
+            ErrorKind::NotFound => std::io::ErrorKind::NotFound,
+            ErrorKind::AccessDenied => std::io::ErrorKind::PermissionDenied,
+            ErrorKind::Duplicate => std::io::ErrorKind::AlreadyExists,
+            ErrorKind::Timeout => std::io::ErrorKind::TimedOut,
+            ErrorKind::InvalidData => std::io::ErrorKind::InvalidData,
+            ErrorKind::Unsupported => std::io::ErrorKind::Unsupported,
+            _ => std::io::ErrorKind::Other,
+        };
+        std::io::Error::new(kind, e)
+    }
+}
+
+impl From<tokio::time::error::Elapsed> for Error {
+    fn from(_: tokio::time::error::Elapsed) -> Self {
+        Self::timeout()
+    }
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Returns an error from the current function
+///
+/// The error kind can be given as a bare `ErrorKind` variant name, as a path or, for arbitrary
+/// expressions (e.g. variables), with `kind = `
+///
+/// ```
+/// use bmart::ErrorKind;
+///
+/// fn check(v: u32) -> bmart::Result<()> {
+///     if v > 100 {
+///         bmart::bail!(InvalidData, "value too large: {}", v);
+///     }
+///     if v == 0 {
+///         bmart::bail!(ErrorKind::InvalidData, "value is zero");
+///     }
+///     let kind = if v == 1 { ErrorKind::Busy } else { ErrorKind::Internal };
+///     if v < 3 {
+///         bmart::bail!(kind = kind, "value {} is reserved", v);
+///     }
+///     Ok(())
+/// }
+/// assert_eq!(check(1).unwrap_err().kind, ErrorKind::Busy);
+/// ```
+#[macro_export]
+macro_rules! bail {
+    (kind = $kind: expr, $($arg:tt)+) => {
+        return Err($crate::Error::new($kind, format!($($arg)+)).into())
+    };
+    ($kind: ident, $($arg:tt)+) => {
+        return Err($crate::Error::new($crate::ErrorKind::$kind, format!($($arg)+)).into())
+    };
+    ($kind: path, $($arg:tt)+) => {
+        return Err($crate::Error::new($kind, format!($($arg)+)).into())
+    };
+    ($err: expr) => {
+        return Err($err.into())
+    };
+}
+
+/// Returns an error from the current function if the condition is not met, accepts the same
+/// error arguments as [`bail!`]
+///
+/// ```
+/// use bmart::ErrorKind;
+///
+/// fn check(v: u32) -> bmart::Result<()> {
+///     bmart::ensure!(v <= 100, InvalidData, "value too large: {}", v);
+///     bmart::ensure!(v > 0, ErrorKind::InvalidData, "value is zero");
+///     let kind = ErrorKind::Busy;
+///     bmart::ensure!(v != 1, kind = kind, "value {} is reserved", v);
+///     Ok(())
+/// }
+/// assert_eq!(check(1).unwrap_err().kind, ErrorKind::Busy);
+/// ```
+#[macro_export]
+macro_rules! ensure {
+    ($cond: expr, $($arg:tt)+) => {
+        if !$cond {
+            $crate::bail!($($arg)+);
+        }
+    };
+}
+
+#[cfg(feature = "calendar")]
+pub mod calendar;
 pub mod mpsc;
+pub mod prelude;
 pub mod process;
+pub mod service;
 pub mod sync;
+#[cfg(feature = "testing")]
+pub mod testing;
 pub mod tools;
 pub mod workers;
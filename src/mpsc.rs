@@ -1,36 +1,1828 @@
+use crate::tools;
 use crate::Error;
+use log::warn;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::future::Future;
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::sync::atomic;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
-use tokio::sync::mpsc;
+use tokio::sync::{broadcast, mpsc, oneshot, Notify};
+use tokio::time::Instant;
+
+#[cfg(feature = "spillover")]
+mod spillover;
+#[cfg(feature = "spillover")]
+pub use spillover::{spillover_channel, SpillReceiver, SpillSender};
+#[cfg(all(feature = "ipc", unix))]
+pub mod ipc;
+
+const ERR_CHANNEL_CLOSED: &str = "Channel closed";
+const ERR_CALLER_GONE: &str = "Caller gone";
+const ERR_NO_RESPONSE: &str = "Request dropped without response";
+const ERR_CHANNEL_FULL: &str = "Channel full";
+
+/// Channel sender backend for `SafeSender`
+///
+/// Implementations may use `async fn` directly
+pub trait SendBackend<T>: Clone {
+    fn send_data(&self, data: T) -> impl Future<Output = Result<(), Error>>;
+    /// Sends the data without waiting, returns `Ok(false)` if the channel is full. Used by
+    /// non-blocking backpressure strategies
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the channel is closed
+    fn try_send_data(&self, data: T) -> Result<bool, Error>;
+}
+
+impl<T> SendBackend<T> for mpsc::Sender<T> {
+    async fn send_data(&self, data: T) -> Result<(), Error> {
+        self.send(data).await.map_err(Error::internal)
+    }
+    fn try_send_data(&self, data: T) -> Result<bool, Error> {
+        match self.try_send(data) {
+            Ok(()) => Ok(true),
+            Err(mpsc::error::TrySendError::Full(_)) => Ok(false),
+            Err(e) => Err(Error::internal(e)),
+        }
+    }
+}
+
+impl<T> SendBackend<T> for async_channel::Sender<T> {
+    async fn send_data(&self, data: T) -> Result<(), Error> {
+        self.send(data).await.map_err(Error::internal)
+    }
+    fn try_send_data(&self, data: T) -> Result<bool, Error> {
+        match self.try_send(data) {
+            Ok(()) => Ok(true),
+            Err(async_channel::TrySendError::Full(_)) => Ok(false),
+            Err(e) => Err(Error::internal(e)),
+        }
+    }
+}
+
+impl<T> SendBackend<T> for broadcast::Sender<T> {
+    /// Fails if there are no active receivers
+    async fn send_data(&self, data: T) -> Result<(), Error> {
+        self.send(data).map(|_| ()).map_err(Error::internal)
+    }
+    /// Broadcast channels never block, lagging receivers lose the oldest messages
+    fn try_send_data(&self, data: T) -> Result<bool, Error> {
+        self.send(data).map(|_| true).map_err(Error::internal)
+    }
+}
+
+/// `SafeSender` behavior when the channel is full
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum BackpressureStrategy {
+    /// Wait for a free slot until the sender timeout expires
+    #[default]
+    WaitTimeout,
+    /// Wait for a free slot with no timeout
+    WaitForever,
+    /// Fail immediately with `ErrorKind::Busy`
+    FailFast,
+    /// Drop the message and increment the dropped counter
+    DropAndCount,
+}
+
+#[derive(Debug)]
+pub struct SafeSender<T, B = mpsc::Sender<T>>
+where
+    B: SendBackend<T>,
+{
+    tx: B,
+    timeout: Duration,
+    strategy: BackpressureStrategy,
+    dropped: Arc<atomic::AtomicU64>,
+    _phantom: PhantomData<fn(T)>,
+}
+
+impl<T, B> Clone for SafeSender<T, B>
+where
+    B: SendBackend<T>,
+{
+    fn clone(&self) -> Self {
+        Self {
+            tx: self.tx.clone(),
+            timeout: self.timeout,
+            strategy: self.strategy,
+            dropped: self.dropped.clone(),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T, B> SafeSender<T, B>
+where
+    B: SendBackend<T>,
+{
+    #[must_use]
+    pub fn new(tx: B, timeout: Duration) -> Self {
+        Self {
+            tx,
+            timeout,
+            strategy: BackpressureStrategy::WaitTimeout,
+            dropped: <_>::default(),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Sets the backpressure strategy, the default is `BackpressureStrategy::WaitTimeout`.
+    /// Clones share the dropped counter
+    #[inline]
+    #[must_use]
+    pub fn strategy(mut self, strategy: BackpressureStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Messages dropped by `BackpressureStrategy::DropAndCount`
+    #[inline]
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(atomic::Ordering::SeqCst)
+    }
+
+    /// Sends the data according to the backpressure strategy
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if timeout occured, the channel is closed or full (`FailFast`)
+    pub async fn safe_send(&self, data: T) -> Result<(), Error> {
+        match self.strategy {
+            BackpressureStrategy::WaitTimeout => {
+                tools::timeout(self.timeout, self.tx.send_data(data)).await?
+            }
+            BackpressureStrategy::WaitForever => self.tx.send_data(data).await,
+            BackpressureStrategy::FailFast => {
+                if self.tx.try_send_data(data)? {
+                    Ok(())
+                } else {
+                    Err(Error::busy(ERR_CHANNEL_FULL))
+                }
+            }
+            BackpressureStrategy::DropAndCount => {
+                if !self.tx.try_send_data(data)? {
+                    self.dropped.fetch_add(1, atomic::Ordering::SeqCst);
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl<T> SafeSender<T, mpsc::Sender<T>> {
+    /// Sends the data immediately
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the channel is closed or with `ErrorKind::Busy` if full
+    pub fn try_send(&self, data: T) -> Result<(), Error> {
+        match self.tx.try_send(data) {
+            Ok(()) => Ok(()),
+            Err(mpsc::error::TrySendError::Full(_)) => Err(Error::busy(ERR_CHANNEL_FULL)),
+            Err(e) => Err(Error::internal(e)),
+        }
+    }
+
+    /// Sends the data from a synchronous context. Must not be called from async code
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the channel is closed
+    pub fn blocking_send(&self, data: T) -> Result<(), Error> {
+        self.tx.blocking_send(data).map_err(Error::internal)
+    }
+
+    /// Best-effort send, the data is silently dropped if the channel is full
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the channel is closed
+    pub fn send_or_drop(&self, data: T) -> Result<(), Error> {
+        match self.tx.try_send(data) {
+            Ok(()) | Err(mpsc::error::TrySendError::Full(_)) => Ok(()),
+            Err(e) => Err(Error::internal(e)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SafeReceiver<T> {
+    rx: mpsc::Receiver<T>,
+    timeout: Duration,
+}
+
+impl<T> SafeReceiver<T> {
+    #[must_use]
+    pub fn new(rx: mpsc::Receiver<T>, timeout: Duration) -> Self {
+        Self { rx, timeout }
+    }
+
+    /// Returns `Ok(None)` if the channel is closed and there are no more messages
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if timeout occured
+    pub async fn safe_recv(&mut self) -> Result<Option<T>, Error> {
+        tools::timeout(self.timeout, self.rx.recv()).await
+    }
+
+    /// Receives up to max messages, waiting not longer than max_wait in total. Returns the
+    /// messages arrived within the period (possibly none) or `None` if the channel is closed
+    /// and there are no more messages
+    pub async fn recv_many(&mut self, max: usize, max_wait: Duration) -> Option<Vec<T>> {
+        let deadline = Instant::now() + max_wait;
+        let mut batch = Vec::with_capacity(max.min(self.rx.max_capacity()));
+        while batch.len() < max {
+            let limit = max - batch.len();
+            match tokio::time::timeout_at(deadline, self.rx.recv_many(&mut batch, limit)).await {
+                Ok(0) => {
+                    if batch.is_empty() {
+                        return None;
+                    }
+                    break;
+                }
+                Ok(_) => {}
+                Err(_) => break,
+            }
+        }
+        Some(batch)
+    }
+
+    /// Closes the receiving half, the buffered messages can still be received
+    pub fn close(&mut self) {
+        self.rx.close();
+    }
+}
+
+impl<T, B> SafeSender<T, B>
+where
+    B: SendBackend<T>,
+{
+    /// Returns a sender adapter which converts the data with the function before sending
+    pub fn map_send<U, F>(&self, f: F) -> MapSender<U, T, B, F>
+    where
+        F: Fn(U) -> T,
+    {
+        MapSender {
+            tx: self.clone(),
+            f,
+            _phantom: PhantomData,
+        }
+    }
+    /// Returns a sender adapter which sends only the data matching the predicate
+    pub fn filter_send<F>(&self, predicate: F) -> FilterSender<T, B, F>
+    where
+        F: Fn(&T) -> bool,
+    {
+        FilterSender {
+            tx: self.clone(),
+            predicate,
+        }
+    }
+}
+
+/// Sender adapter, created with `SafeSender::map_send`
+#[derive(Debug)]
+pub struct MapSender<U, T, B, F>
+where
+    B: SendBackend<T>,
+{
+    tx: SafeSender<T, B>,
+    f: F,
+    _phantom: PhantomData<fn(U)>,
+}
+
+impl<U, T, B, F> MapSender<U, T, B, F>
+where
+    B: SendBackend<T>,
+    F: Fn(U) -> T,
+{
+    /// # Errors
+    ///
+    /// Will return `Err` if the underlying sender fails
+    pub async fn safe_send(&self, data: U) -> Result<(), Error> {
+        self.tx.safe_send((self.f)(data)).await
+    }
+}
+
+/// Sender adapter, created with `SafeSender::filter_send`
+#[derive(Debug)]
+pub struct FilterSender<T, B, F>
+where
+    B: SendBackend<T>,
+{
+    tx: SafeSender<T, B>,
+    predicate: F,
+}
+
+impl<T, B, F> FilterSender<T, B, F>
+where
+    B: SendBackend<T>,
+    F: Fn(&T) -> bool,
+{
+    /// Returns `Ok(false)` if the data has been filtered out
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the underlying sender fails
+    pub async fn safe_send(&self, data: T) -> Result<bool, Error> {
+        if (self.predicate)(&data) {
+            self.tx.safe_send(data).await?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+}
+
+/// Converts the receiver into a stream. The receiver timeout is not applied, the stream ends
+/// when all senders are dropped
+#[cfg(feature = "futures")]
+pub fn into_stream<T>(receiver: SafeReceiver<T>) -> impl futures::Stream<Item = T> {
+    futures::stream::unfold(receiver, |mut receiver| async move {
+        receiver.rx.recv().await.map(|v| (v, receiver))
+    })
+}
+
+/// Forwards the stream into a new bounded channel. The forwarding task is finished when the
+/// stream ends or the receiver is dropped
+#[cfg(feature = "futures")]
+pub fn from_stream<S>(stream: S, buf: usize) -> mpsc::Receiver<S::Item>
+where
+    S: futures::Stream + Send + 'static,
+    S::Item: Send + 'static,
+{
+    use futures::StreamExt;
+    let (tx, rx) = mpsc::channel(buf);
+    tokio::spawn(async move {
+        futures::pin_mut!(stream);
+        while let Some(v) = stream.next().await {
+            if tx.send(v).await.is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+/// Creates a bounded channel with timeout-aware sender and receiver
+#[must_use]
+pub fn safe_channel<T>(buf: usize, timeout: Duration) -> (SafeSender<T>, SafeReceiver<T>) {
+    let (tx, rx) = mpsc::channel(buf);
+    (SafeSender::new(tx, timeout), SafeReceiver::new(rx, timeout))
+}
+
+/// Send-side behavior of `policy_channel` when the buffer is full
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum OverflowPolicy {
+    /// Wait for a free slot
+    #[default]
+    Block,
+    /// Drop the message being sent
+    DropNewest,
+    /// Drop the oldest buffered message
+    DropOldest,
+    /// Keep the latest message only (conflation), the buffer size is ignored
+    Latest,
+}
+
+#[derive(Debug)]
+struct PolicyChannel<T> {
+    queue: Mutex<VecDeque<T>>,
+    capacity: usize,
+    policy: OverflowPolicy,
+    senders: atomic::AtomicUsize,
+    closed: atomic::AtomicBool,
+    dropped: atomic::AtomicU64,
+    data_available: Notify,
+    space_available: Notify,
+}
+
+#[derive(Debug)]
+pub struct PolicySender<T> {
+    channel: Arc<PolicyChannel<T>>,
+}
+
+impl<T> Clone for PolicySender<T> {
+    fn clone(&self) -> Self {
+        self.channel.senders.fetch_add(1, atomic::Ordering::SeqCst);
+        Self {
+            channel: self.channel.clone(),
+        }
+    }
+}
+
+impl<T> Drop for PolicySender<T> {
+    fn drop(&mut self) {
+        if self.channel.senders.fetch_sub(1, atomic::Ordering::SeqCst) == 1 {
+            self.channel.data_available.notify_one();
+        }
+    }
+}
+
+impl<T> PolicySender<T> {
+    /// # Errors
+    ///
+    /// Will return `Err` if the receiver is dropped
+    ///
+    /// # Panics
+    ///
+    /// Should not panic
+    pub async fn send(&self, value: T) -> Result<(), Error> {
+        let ch = &self.channel;
+        loop {
+            let notified = ch.space_available.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+            {
+                if ch.closed.load(atomic::Ordering::SeqCst) {
+                    return Err(Error::internal(ERR_CHANNEL_CLOSED));
+                }
+                let mut queue = ch.queue.lock().unwrap();
+                match ch.policy {
+                    OverflowPolicy::Latest => {
+                        ch.dropped
+                            .fetch_add(queue.len() as u64, atomic::Ordering::SeqCst);
+                        queue.clear();
+                    }
+                    OverflowPolicy::DropNewest if queue.len() >= ch.capacity => {
+                        ch.dropped.fetch_add(1, atomic::Ordering::SeqCst);
+                        return Ok(());
+                    }
+                    OverflowPolicy::DropOldest if queue.len() >= ch.capacity => {
+                        queue.pop_front();
+                        ch.dropped.fetch_add(1, atomic::Ordering::SeqCst);
+                    }
+                    _ => {}
+                }
+                if queue.len() < ch.capacity {
+                    queue.push_back(value);
+                    drop(queue);
+                    ch.data_available.notify_one();
+                    return Ok(());
+                }
+            }
+            // block policy, wait for a free slot
+            notified.await;
+        }
+    }
+
+    /// Number of messages dropped because of the overflow policy
+    pub fn dropped(&self) -> u64 {
+        self.channel.dropped.load(atomic::Ordering::SeqCst)
+    }
+}
+
+#[derive(Debug)]
+pub struct PolicyReceiver<T> {
+    channel: Arc<PolicyChannel<T>>,
+}
+
+impl<T> Drop for PolicyReceiver<T> {
+    fn drop(&mut self) {
+        self.channel.closed.store(true, atomic::Ordering::SeqCst);
+        self.channel.space_available.notify_waiters();
+    }
+}
+
+impl<T> PolicyReceiver<T> {
+    /// Returns `None` if all senders are dropped and there are no more messages
+    ///
+    /// # Panics
+    ///
+    /// Should not panic
+    pub async fn recv(&mut self) -> Option<T> {
+        let ch = &self.channel;
+        loop {
+            let notified = ch.data_available.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+            let value = ch.queue.lock().unwrap().pop_front();
+            if let Some(v) = value {
+                ch.space_available.notify_one();
+                return Some(v);
+            }
+            if ch.senders.load(atomic::Ordering::SeqCst) == 0 {
+                return None;
+            }
+            notified.await;
+        }
+    }
+
+    /// Number of buffered messages
+    ///
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn len(&self) -> usize {
+        self.channel.queue.lock().unwrap().len()
+    }
+
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of messages dropped because of the overflow policy
+    pub fn dropped(&self) -> u64 {
+        self.channel.dropped.load(atomic::Ordering::SeqCst)
+    }
+}
+
+/// Creates a bounded channel with the send-side overflow policy
+pub fn policy_channel<T>(
+    buf: usize,
+    policy: OverflowPolicy,
+) -> (PolicySender<T>, PolicyReceiver<T>) {
+    let channel = Arc::new(PolicyChannel {
+        queue: Mutex::new(VecDeque::with_capacity(buf)),
+        capacity: buf.max(1),
+        policy,
+        senders: atomic::AtomicUsize::new(1),
+        closed: atomic::AtomicBool::new(false),
+        dropped: atomic::AtomicU64::new(0),
+        data_available: Notify::new(),
+        space_available: Notify::new(),
+    });
+    (
+        PolicySender {
+            channel: channel.clone(),
+        },
+        PolicyReceiver { channel },
+    )
+}
+
+/// Creates a coalescing channel: sending overwrites the pending value, the receiver gets the
+/// most recent value exactly once. Sending never blocks
+pub fn latest_channel<T>() -> (PolicySender<T>, PolicyReceiver<T>) {
+    policy_channel(1, OverflowPolicy::Latest)
+}
+
+/// RPC request, received by `Responder`
+#[derive(Debug)]
+pub struct RpcRequest<Req, Resp> {
+    pub payload: Req,
+    reply: oneshot::Sender<Resp>,
+}
+
+/// Reply handle of a split RPC request
+#[derive(Debug)]
+pub struct RpcReply<Resp> {
+    tx: oneshot::Sender<Resp>,
+}
+
+impl<Resp> RpcReply<Resp> {
+    /// # Errors
+    ///
+    /// Will return `Err` if the caller is gone (e.g. timed out)
+    pub fn respond(self, response: Resp) -> Result<(), Error> {
+        self.tx
+            .send(response)
+            .map_err(|_| Error::internal(ERR_CALLER_GONE))
+    }
+}
+
+impl<Req, Resp> RpcRequest<Req, Resp> {
+    /// # Errors
+    ///
+    /// Will return `Err` if the caller is gone (e.g. timed out)
+    pub fn respond(self, response: Resp) -> Result<(), Error> {
+        self.reply
+            .send(response)
+            .map_err(|_| Error::internal(ERR_CALLER_GONE))
+    }
+    pub fn split(self) -> (Req, RpcReply<Resp>) {
+        (self.payload, RpcReply { tx: self.reply })
+    }
+}
+
+#[derive(Debug)]
+pub struct Caller<Req, Resp> {
+    tx: mpsc::Sender<RpcRequest<Req, Resp>>,
+}
+
+impl<Req, Resp> Clone for Caller<Req, Resp> {
+    fn clone(&self) -> Self {
+        Self {
+            tx: self.tx.clone(),
+        }
+    }
+}
+
+impl<Req, Resp> Caller<Req, Resp> {
+    /// Sends the request and waits for the response
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` on timeout, if the responder is dropped or the request is dropped
+    /// without a response
+    pub async fn call(&self, request: Req, timeout: Duration) -> Result<Resp, Error> {
+        let (reply, rx) = oneshot::channel();
+        tokio::time::timeout(timeout, async move {
+            self.tx
+                .send(RpcRequest {
+                    payload: request,
+                    reply,
+                })
+                .await
+                .map_err(|_| Error::internal(ERR_CHANNEL_CLOSED))?;
+            rx.await.map_err(|_| Error::internal(ERR_NO_RESPONSE))
+        })
+        .await
+        .map_err(|_| Error::timeout())?
+    }
+}
+
+#[derive(Debug)]
+pub struct Responder<Req, Resp> {
+    rx: mpsc::Receiver<RpcRequest<Req, Resp>>,
+}
+
+impl<Req, Resp> Responder<Req, Resp> {
+    /// Returns `None` if all callers are dropped
+    pub async fn recv(&mut self) -> Option<RpcRequest<Req, Resp>> {
+        self.rx.recv().await
+    }
+}
+
+/// Creates a request/response channel
+pub fn rpc_channel<Req, Resp>(buf: usize) -> (Caller<Req, Resp>, Responder<Req, Resp>) {
+    let (tx, rx) = mpsc::channel(buf);
+    (Caller { tx }, Responder { rx })
+}
+
+/// Instrumented channel metrics snapshot
+#[derive(Debug, Clone, Default)]
+pub struct ChannelMetrics {
+    /// Current number of queued messages
+    pub depth: usize,
+    /// Max number of queued messages observed
+    pub high_water_mark: usize,
+    pub sent: u64,
+    pub received: u64,
+    pub send_timeouts: u64,
+    /// Messages, not sent because the channel was full or closed
+    pub dropped: u64,
+}
+
+#[derive(Debug, Default)]
+struct ChannelStats {
+    high_water_mark: atomic::AtomicUsize,
+    sent: atomic::AtomicU64,
+    received: atomic::AtomicU64,
+    send_timeouts: atomic::AtomicU64,
+    dropped: atomic::AtomicU64,
+}
+
+impl ChannelStats {
+    fn sent(&self) {
+        let sent = self.sent.fetch_add(1, atomic::Ordering::SeqCst) + 1;
+        let depth = sent.saturating_sub(self.received.load(atomic::Ordering::SeqCst));
+        self.high_water_mark.fetch_max(
+            usize::try_from(depth).unwrap_or(usize::MAX),
+            atomic::Ordering::SeqCst,
+        );
+    }
+    fn metrics(&self) -> ChannelMetrics {
+        let sent = self.sent.load(atomic::Ordering::SeqCst);
+        let received = self.received.load(atomic::Ordering::SeqCst);
+        ChannelMetrics {
+            depth: usize::try_from(sent.saturating_sub(received)).unwrap_or(usize::MAX),
+            high_water_mark: self.high_water_mark.load(atomic::Ordering::SeqCst),
+            sent,
+            received,
+            send_timeouts: self.send_timeouts.load(atomic::Ordering::SeqCst),
+            dropped: self.dropped.load(atomic::Ordering::SeqCst),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct InstrumentedSender<T> {
+    tx: mpsc::Sender<T>,
+    stats: Arc<ChannelStats>,
+}
+
+impl<T> Clone for InstrumentedSender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            tx: self.tx.clone(),
+            stats: self.stats.clone(),
+        }
+    }
+}
+
+impl<T> InstrumentedSender<T> {
+    /// # Errors
+    ///
+    /// Will return `Err` if the channel is closed
+    pub async fn send(&self, value: T) -> Result<(), Error> {
+        if self.tx.send(value).await.is_ok() {
+            self.stats.sent();
+            Ok(())
+        } else {
+            self.stats.dropped.fetch_add(1, atomic::Ordering::SeqCst);
+            Err(Error::internal(ERR_CHANNEL_CLOSED))
+        }
+    }
+
+    /// # Errors
+    ///
+    /// Will return `Err` if timeout occured or the channel is closed
+    pub async fn send_timeout(&self, value: T, timeout: Duration) -> Result<(), Error> {
+        match tokio::time::timeout(timeout, self.tx.send(value)).await {
+            Ok(Ok(())) => {
+                self.stats.sent();
+                Ok(())
+            }
+            Ok(Err(_)) => {
+                self.stats.dropped.fetch_add(1, atomic::Ordering::SeqCst);
+                Err(Error::internal(ERR_CHANNEL_CLOSED))
+            }
+            Err(_) => {
+                self.stats
+                    .send_timeouts
+                    .fetch_add(1, atomic::Ordering::SeqCst);
+                Err(Error::timeout())
+            }
+        }
+    }
+
+    /// # Errors
+    ///
+    /// Will return `Err` if the channel is full or closed
+    pub fn try_send(&self, value: T) -> Result<(), Error> {
+        match self.tx.try_send(value) {
+            Ok(()) => {
+                self.stats.sent();
+                Ok(())
+            }
+            Err(e) => {
+                self.stats.dropped.fetch_add(1, atomic::Ordering::SeqCst);
+                Err(Error::internal(e))
+            }
+        }
+    }
+
+    pub fn metrics(&self) -> ChannelMetrics {
+        self.stats.metrics()
+    }
+}
+
+#[derive(Debug)]
+pub struct InstrumentedReceiver<T> {
+    rx: mpsc::Receiver<T>,
+    stats: Arc<ChannelStats>,
+}
+
+impl<T> InstrumentedReceiver<T> {
+    pub async fn recv(&mut self) -> Option<T> {
+        let value = self.rx.recv().await;
+        if value.is_some() {
+            self.stats.received.fetch_add(1, atomic::Ordering::SeqCst);
+        }
+        value
+    }
+
+    pub fn metrics(&self) -> ChannelMetrics {
+        self.stats.metrics()
+    }
+}
+
+/// Creates a bounded channel with metrics
+pub fn instrumented<T>(buf: usize) -> (InstrumentedSender<T>, InstrumentedReceiver<T>) {
+    let (tx, rx) = mpsc::channel(buf);
+    let stats: Arc<ChannelStats> = <_>::default();
+    (
+        InstrumentedSender {
+            tx,
+            stats: stats.clone(),
+        },
+        InstrumentedReceiver { rx, stats },
+    )
+}
+
+/// `FanOut` behavior for subscribers which buffers are full
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum LagPolicy {
+    /// Drop the message for the subscriber
+    DropMessage,
+    /// Disconnect the subscriber
+    Disconnect,
+    /// Wait for a free slot, disconnect the subscriber on timeout
+    Timeout(Duration),
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct SubscriptionId(u64);
+
+#[derive(Debug)]
+struct Subscriber<T> {
+    tx: mpsc::Sender<T>,
+    policy: LagPolicy,
+}
+
+/// Delivers each message to all subscribers, each subscriber has own buffer and lag policy
+#[derive(Debug)]
+pub struct FanOut<T: Clone> {
+    subscribers: Mutex<BTreeMap<u64, Subscriber<T>>>,
+    next_id: atomic::AtomicU64,
+}
+
+impl<T: Clone> Default for FanOut<T> {
+    fn default() -> Self {
+        Self {
+            subscribers: <_>::default(),
+            next_id: <_>::default(),
+        }
+    }
+}
+
+impl<T: Clone> FanOut<T> {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn subscribe(&self, buf: usize, policy: LagPolicy) -> (SubscriptionId, mpsc::Receiver<T>) {
+        let (tx, rx) = mpsc::channel(buf);
+        let id = self.next_id.fetch_add(1, atomic::Ordering::SeqCst);
+        self.subscribers
+            .lock()
+            .unwrap()
+            .insert(id, Subscriber { tx, policy });
+        (SubscriptionId(id), rx)
+    }
+
+    /// Returns false if not subscribed (or already disconnected)
+    ///
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn unsubscribe(&self, id: SubscriptionId) -> bool {
+        self.subscribers.lock().unwrap().remove(&id.0).is_some()
+    }
+
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn subscriber_count(&self) -> usize {
+        self.subscribers.lock().unwrap().len()
+    }
+
+    /// Sends the message to all subscribers, returns the number of subscribers the message has
+    /// been delivered to. Closed and lagging (according to the policy) subscribers are
+    /// disconnected
+    ///
+    /// # Panics
+    ///
+    /// Should not panic
+    pub async fn send(&self, value: T) -> usize {
+        let subscribers: Vec<(u64, mpsc::Sender<T>, LagPolicy)> = self
+            .subscribers
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id, s)| (*id, s.tx.clone(), s.policy))
+            .collect();
+        let mut delivered = 0;
+        let mut disconnect = Vec::new();
+        for (id, tx, policy) in subscribers {
+            match tx.try_send(value.clone()) {
+                Ok(()) => delivered += 1,
+                Err(mpsc::error::TrySendError::Closed(_)) => disconnect.push(id),
+                Err(mpsc::error::TrySendError::Full(v)) => match policy {
+                    LagPolicy::DropMessage => {}
+                    LagPolicy::Disconnect => disconnect.push(id),
+                    LagPolicy::Timeout(timeout) => {
+                        if let Ok(Ok(())) = tokio::time::timeout(timeout, tx.send(v)).await {
+                            delivered += 1;
+                        } else {
+                            disconnect.push(id);
+                        }
+                    }
+                },
+            }
+        }
+        if !disconnect.is_empty() {
+            let mut subscribers = self.subscribers.lock().unwrap();
+            for id in disconnect {
+                subscribers.remove(&id);
+            }
+        }
+        delivered
+    }
+}
 
 #[derive(Debug)]
-pub struct SafeSender<T> {
-    tx: mpsc::Sender<T>,
+pub struct PrioritySender<T> {
+    txs: Vec<mpsc::Sender<T>>,
+    notify: Arc<Notify>,
     timeout: Duration,
 }
 
-impl<T> Clone for SafeSender<T> {
+impl<T> Clone for PrioritySender<T> {
     fn clone(&self) -> Self {
         Self {
-            tx: self.tx.clone(),
+            txs: self.txs.clone(),
+            notify: self.notify.clone(),
             timeout: self.timeout,
         }
     }
 }
 
-impl<T> SafeSender<T> {
-    #[must_use]
-    pub fn new(tx: mpsc::Sender<T>, timeout: Duration) -> Self {
-        Self { tx, timeout }
+impl<T> Drop for PrioritySender<T> {
+    fn drop(&mut self) {
+        // drop the senders before waking the receiver up to let it detect the channel is closed
+        self.txs.clear();
+        self.notify.notify_one();
     }
+}
 
+impl<T> PrioritySender<T> {
+    /// Sends the data with the lowest priority
+    ///
     /// # Errors
     ///
     /// Will return `Err` if timeout occured
     pub async fn safe_send(&self, data: T) -> Result<(), Error> {
-        tokio::time::timeout(self.timeout, self.tx.send(data))
+        self.safe_send_with_priority(data, self.txs.len() - 1).await
+    }
+
+    /// Sends the data with the specified priority, 0 is the highest
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if timeout occured or the priority is invalid
+    pub async fn safe_send_with_priority(&self, data: T, priority: usize) -> Result<(), Error> {
+        let tx = self
+            .txs
+            .get(priority)
+            .ok_or_else(|| Error::invalid_data(format!("invalid priority: {}", priority)))?;
+        tokio::time::timeout(self.timeout, tx.send(data))
             .await
             .map_or(Err(Error::timeout()), |res| {
                 res.map_or_else(|e| Err(Error::internal(e)), |()| Ok(()))
+            })?;
+        self.notify.notify_one();
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct PriorityReceiver<T> {
+    rxs: Vec<mpsc::Receiver<T>>,
+    notify: Arc<Notify>,
+}
+
+impl<T> PriorityReceiver<T> {
+    /// Receives the message with the highest priority available. Returns `None` if all senders
+    /// are dropped and there are no more messages
+    pub async fn recv(&mut self) -> Option<T> {
+        loop {
+            let notified = self.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+            let mut closed = 0;
+            for rx in &mut self.rxs {
+                match rx.try_recv() {
+                    Ok(v) => return Some(v),
+                    Err(mpsc::error::TryRecvError::Empty) => {}
+                    Err(mpsc::error::TryRecvError::Disconnected) => closed += 1,
+                }
+            }
+            if closed == self.rxs.len() {
+                return None;
+            }
+            notified.await;
+        }
+    }
+}
+
+/// Creates a priority channel with the given number of priority levels (0 is the highest) and
+/// the buffer size per level
+pub fn priority_channel<T>(
+    levels: usize,
+    buf: usize,
+    timeout: Duration,
+) -> (PrioritySender<T>, PriorityReceiver<T>) {
+    let (txs, rxs) = (0..levels.max(1)).map(|_| mpsc::channel(buf)).unzip();
+    let notify = Arc::new(Notify::new());
+    (
+        PrioritySender {
+            txs,
+            notify: notify.clone(),
+            timeout,
+        },
+        PriorityReceiver { rxs, notify },
+    )
+}
+
+#[derive(Debug)]
+struct TtlMessage<T> {
+    data: T,
+    expires: Instant,
+}
+
+#[derive(Debug)]
+pub struct TtlSender<T> {
+    tx: mpsc::Sender<TtlMessage<T>>,
+    ttl: Duration,
+}
+
+impl<T> Clone for TtlSender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            tx: self.tx.clone(),
+            ttl: self.ttl,
+        }
+    }
+}
+
+impl<T> TtlSender<T> {
+    /// Sends the data with the default TTL
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the channel is closed
+    pub async fn send(&self, data: T) -> Result<(), Error> {
+        self.send_with_ttl(data, self.ttl).await
+    }
+
+    /// # Errors
+    ///
+    /// Will return `Err` if the channel is closed
+    pub async fn send_with_ttl(&self, data: T, ttl: Duration) -> Result<(), Error> {
+        self.tx
+            .send(TtlMessage {
+                data,
+                expires: Instant::now() + ttl,
             })
+            .await
+            .map_err(|_| Error::internal(ERR_CHANNEL_CLOSED))
+    }
+}
+
+#[derive(Debug)]
+pub struct TtlReceiver<T> {
+    rx: mpsc::Receiver<TtlMessage<T>>,
+    expired: u64,
+    log_expired: bool,
+}
+
+impl<T> TtlReceiver<T> {
+    /// Logs expired messages as warnings
+    #[inline]
+    #[must_use]
+    pub fn log_expired(mut self, log: bool) -> Self {
+        self.log_expired = log;
+        self
+    }
+
+    /// Receives the next non-expired message, the expired ones are dropped. Returns `None` if
+    /// all senders are dropped and there are no more messages
+    pub async fn recv(&mut self) -> Option<T> {
+        while let Some(msg) = self.rx.recv().await {
+            if msg.expires >= Instant::now() {
+                return Some(msg.data);
+            }
+            self.expired += 1;
+            if self.log_expired {
+                warn!("channel message expired, dropped");
+            }
+        }
+        None
+    }
+
+    /// Number of expired messages dropped
+    #[inline]
+    pub fn expired(&self) -> u64 {
+        self.expired
+    }
+}
+
+/// Creates a bounded channel with the default message TTL. The expired messages are dropped at
+/// receive time
+pub fn ttl_channel<T>(buf: usize, ttl: Duration) -> (TtlSender<T>, TtlReceiver<T>) {
+    let (tx, rx) = mpsc::channel(buf);
+    (
+        TtlSender { tx, ttl },
+        TtlReceiver {
+            rx,
+            expired: 0,
+            log_expired: false,
+        },
+    )
+}
+
+#[derive(Debug, Default)]
+struct ShutdownState {
+    triggered: atomic::AtomicBool,
+    notify: Notify,
+}
+
+/// Channel shutdown handle
+#[derive(Debug, Clone)]
+pub struct Shutdown {
+    state: Arc<ShutdownState>,
+}
+
+impl Shutdown {
+    /// Further sends return `ErrorKind::Shutdown`, the receiver gets the messages already
+    /// buffered and then the end of stream
+    pub fn trigger(&self) {
+        self.state.triggered.store(true, atomic::Ordering::SeqCst);
+        self.state.notify.notify_waiters();
+    }
+    pub fn is_triggered(&self) -> bool {
+        self.state.triggered.load(atomic::Ordering::SeqCst)
+    }
+}
+
+#[derive(Debug)]
+pub struct ShutdownSender<T> {
+    tx: mpsc::Sender<T>,
+    timeout: Duration,
+    shutdown: Shutdown,
+}
+
+impl<T> Clone for ShutdownSender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            tx: self.tx.clone(),
+            timeout: self.timeout,
+            shutdown: self.shutdown.clone(),
+        }
+    }
+}
+
+impl<T> ShutdownSender<T> {
+    /// # Errors
+    ///
+    /// Will return `Err` if timeout occured or with `ErrorKind::Shutdown` if the channel has
+    /// been shut down
+    pub async fn safe_send(&self, data: T) -> Result<(), Error> {
+        let notified = self.shutdown.state.notify.notified();
+        tokio::pin!(notified);
+        notified.as_mut().enable();
+        if self.shutdown.is_triggered() {
+            return Err(Error::shutdown());
+        }
+        tokio::select! {
+            biased;
+            () = notified => Err(Error::shutdown()),
+            res = tokio::time::timeout(self.timeout, self.tx.send(data)) => {
+                res.map_or(Err(Error::timeout()), |res| {
+                    res.map_or_else(|e| Err(Error::internal(e)), |()| Ok(()))
+                })
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ShutdownReceiver<T> {
+    rx: mpsc::Receiver<T>,
+    shutdown: Shutdown,
+}
+
+impl<T> ShutdownReceiver<T> {
+    /// Returns `None` if all senders are dropped or the channel has been shut down and the
+    /// buffered messages are flushed
+    pub async fn recv(&mut self) -> Option<T> {
+        let notified = self.shutdown.state.notify.notified();
+        tokio::pin!(notified);
+        notified.as_mut().enable();
+        if !self.shutdown.is_triggered() {
+            tokio::select! {
+                biased;
+                () = notified => {}
+                v = self.rx.recv() => return v,
+            }
+        }
+        self.rx.close();
+        self.rx.recv().await
+    }
+}
+
+/// Creates a bounded channel with a shutdown handle
+pub fn channel_with_shutdown<T>(
+    buf: usize,
+    timeout: Duration,
+) -> (ShutdownSender<T>, ShutdownReceiver<T>, Shutdown) {
+    let (tx, rx) = mpsc::channel(buf);
+    let shutdown = Shutdown {
+        state: <_>::default(),
+    };
+    (
+        ShutdownSender {
+            tx,
+            timeout,
+            shutdown: shutdown.clone(),
+        },
+        ShutdownReceiver {
+            rx,
+            shutdown: shutdown.clone(),
+        },
+        shutdown,
+    )
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum DedupMode {
+    /// Suppress consecutive duplicates
+    Consecutive,
+    /// Suppress duplicates sent within the time window
+    Window(Duration),
+}
+
+#[derive(Debug)]
+enum DedupState<T> {
+    Consecutive(Option<T>),
+    Window(HashMap<T, Instant>),
+}
+
+/// Sender adapter which suppresses duplicate messages
+#[derive(Debug)]
+pub struct DedupSender<T>
+where
+    T: Eq + Hash + Clone,
+{
+    tx: SafeSender<T>,
+    mode: DedupMode,
+    state: Mutex<DedupState<T>>,
+}
+
+impl<T> DedupSender<T>
+where
+    T: Eq + Hash + Clone,
+{
+    pub fn new(tx: SafeSender<T>, mode: DedupMode) -> Self {
+        let state = match mode {
+            DedupMode::Consecutive => DedupState::Consecutive(None),
+            DedupMode::Window(_) => DedupState::Window(HashMap::new()),
+        };
+        Self {
+            tx,
+            mode,
+            state: Mutex::new(state),
+        }
+    }
+
+    /// Returns `None` if the message is a duplicate, otherwise records it and returns the
+    /// previous consecutive message (if any) to roll back the state if the send fails
+    fn record(&self, data: &T) -> Option<Option<T>> {
+        let mut state = self.state.lock().unwrap();
+        match *state {
+            DedupState::Consecutive(ref mut last) => {
+                if last.as_ref() == Some(data) {
+                    None
+                } else {
+                    Some(last.replace(data.clone()))
+                }
+            }
+            DedupState::Window(ref mut sent) => {
+                let DedupMode::Window(window) = self.mode else {
+                    return Some(None);
+                };
+                let now = Instant::now();
+                sent.retain(|_, t| now.duration_since(*t) < window);
+                if sent.contains_key(data) {
+                    None
+                } else {
+                    sent.insert(data.clone(), now);
+                    Some(None)
+                }
+            }
+        }
+    }
+
+    fn rollback(&self, data: &T, prev: Option<T>) {
+        let mut state = self.state.lock().unwrap();
+        match *state {
+            DedupState::Consecutive(ref mut last) => {
+                if last.as_ref() == Some(data) {
+                    *last = prev;
+                }
+            }
+            DedupState::Window(ref mut sent) => {
+                sent.remove(data);
+            }
+        }
+    }
+
+    /// Returns `Ok(false)` if the message has been suppressed as a duplicate. A message which
+    /// has failed to be sent is not recorded, so it is not suppressed when retried
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if timeout occured
+    ///
+    /// # Panics
+    ///
+    /// Should not panic
+    pub async fn send(&self, data: T) -> Result<bool, Error> {
+        let Some(prev) = self.record(&data) else {
+            return Ok(false);
+        };
+        let key = data.clone();
+        if let Err(e) = self.tx.safe_send(data).await {
+            self.rollback(&key, prev);
+            return Err(e);
+        }
+        Ok(true)
+    }
+}
+
+/// Delivery token of `AckMessage`, identifies a single delivery attempt of the message
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct AckToken {
+    id: u64,
+    attempt: u32,
+}
+
+/// Message received from `acked_channel`, must be acknowledged with `AckReceiver::ack`
+#[derive(Debug)]
+pub struct AckMessage<T> {
+    pub data: T,
+    id: u64,
+    attempt: u32,
+}
+
+impl<T> AckMessage<T> {
+    #[inline]
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+    /// Delivery attempt, starting from 1
+    #[inline]
+    pub fn attempt(&self) -> u32 {
+        self.attempt
+    }
+    /// The token to acknowledge this delivery with
+    #[inline]
+    pub fn token(&self) -> AckToken {
+        AckToken {
+            id: self.id,
+            attempt: self.attempt,
+        }
+    }
+}
+
+/// Message which has not been acknowledged after the maximum number of delivery attempts
+#[derive(Debug)]
+pub struct DeadLetter<T> {
+    pub data: T,
+    pub id: u64,
+    pub attempts: u32,
+}
+
+#[derive(Debug)]
+struct InFlight<T> {
+    data: T,
+    attempt: u32,
+    deadline: Instant,
+}
+
+#[derive(Debug)]
+struct AckState<T> {
+    queue: VecDeque<(u64, u32, T)>,
+    in_flight: BTreeMap<u64, InFlight<T>>,
+    next_id: u64,
+}
+
+#[derive(Debug)]
+struct AckChannel<T> {
+    state: Mutex<AckState<T>>,
+    capacity: usize,
+    visibility_timeout: Duration,
+    max_attempts: u32,
+    senders: atomic::AtomicUsize,
+    receivers: atomic::AtomicUsize,
+    dead_letters: mpsc::UnboundedSender<DeadLetter<T>>,
+    data_available: Notify,
+    space_available: Notify,
+}
+
+impl<T> AckChannel<T> {
+    /// Returns the expired in-flight messages to the queue or moves them to the dead letters,
+    /// returns the nearest in-flight deadline
+    fn requeue_expired(&self, state: &mut AckState<T>, now: Instant) -> Option<Instant> {
+        let expired: Vec<u64> = state
+            .in_flight
+            .iter()
+            .filter(|(_, m)| m.deadline <= now)
+            .map(|(id, _)| *id)
+            .collect();
+        // pushed to the front one by one, the newest first to keep the original order
+        for id in expired.into_iter().rev() {
+            let Some(msg) = state.in_flight.remove(&id) else {
+                continue;
+            };
+            if msg.attempt >= self.max_attempts {
+                warn!(
+                    "acked channel message {} not acknowledged after {} attempts, dead-lettered",
+                    id, msg.attempt
+                );
+                let _r = self.dead_letters.send(DeadLetter {
+                    data: msg.data,
+                    id,
+                    attempts: msg.attempt,
+                });
+                self.space_available.notify_one();
+            } else {
+                state.queue.push_front((id, msg.attempt, msg.data));
+            }
+        }
+        state.in_flight.values().map(|m| m.deadline).min()
+    }
+}
+
+#[derive(Debug)]
+pub struct AckSender<T> {
+    channel: Arc<AckChannel<T>>,
+}
+
+impl<T> Clone for AckSender<T> {
+    fn clone(&self) -> Self {
+        self.channel.senders.fetch_add(1, atomic::Ordering::SeqCst);
+        Self {
+            channel: self.channel.clone(),
+        }
+    }
+}
+
+impl<T> Drop for AckSender<T> {
+    fn drop(&mut self) {
+        if self.channel.senders.fetch_sub(1, atomic::Ordering::SeqCst) == 1 {
+            self.channel.data_available.notify_waiters();
+        }
+    }
+}
+
+impl<T> AckSender<T> {
+    /// Waits for a free slot, in-flight (unacknowledged) messages occupy slots as well. Returns
+    /// the message id
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if all receivers are dropped
+    ///
+    /// # Panics
+    ///
+    /// Should not panic
+    pub async fn send(&self, data: T) -> Result<u64, Error> {
+        let ch = &self.channel;
+        loop {
+            let notified = ch.space_available.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+            if ch.receivers.load(atomic::Ordering::SeqCst) == 0 {
+                return Err(Error::internal(ERR_CHANNEL_CLOSED));
+            }
+            let next_deadline = {
+                let mut state = ch.state.lock().unwrap();
+                // expired messages are processed here as well, as dead-lettering frees the slots
+                // even if no receiver is polling
+                let queued = state.queue.len();
+                let next_deadline = ch.requeue_expired(&mut state, Instant::now());
+                let requeued = state.queue.len() > queued;
+                if state.queue.len() + state.in_flight.len() < ch.capacity {
+                    let id = state.next_id;
+                    state.next_id += 1;
+                    state.queue.push_back((id, 0, data));
+                    drop(state);
+                    ch.data_available.notify_one();
+                    return Ok(id);
+                }
+                drop(state);
+                if requeued {
+                    ch.data_available.notify_waiters();
+                }
+                next_deadline
+            };
+            if let Some(deadline) = next_deadline {
+                tokio::select! {
+                    () = notified => {}
+                    () = tokio::time::sleep_until(deadline) => {}
+                }
+            } else {
+                notified.await;
+            }
+        }
+    }
+}
+
+/// Receiver of `acked_channel`, can be cloned to share the messages between several workers
+#[derive(Debug)]
+pub struct AckReceiver<T> {
+    channel: Arc<AckChannel<T>>,
+}
+
+impl<T> Clone for AckReceiver<T> {
+    fn clone(&self) -> Self {
+        self.channel
+            .receivers
+            .fetch_add(1, atomic::Ordering::SeqCst);
+        Self {
+            channel: self.channel.clone(),
+        }
+    }
+}
+
+impl<T> Drop for AckReceiver<T> {
+    fn drop(&mut self) {
+        if self
+            .channel
+            .receivers
+            .fetch_sub(1, atomic::Ordering::SeqCst)
+            == 1
+        {
+            self.channel.space_available.notify_waiters();
+        }
+    }
+}
+
+impl<T: Clone> AckReceiver<T> {
+    /// Receives the next message. The message is redelivered if not acknowledged within the
+    /// visibility timeout. Returns `None` if all senders are dropped and there are no more
+    /// queued or in-flight messages
+    ///
+    /// # Panics
+    ///
+    /// Should not panic
+    pub async fn recv(&self) -> Option<AckMessage<T>> {
+        let ch = &self.channel;
+        loop {
+            let notified = ch.data_available.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+            let next_deadline = {
+                let now = Instant::now();
+                let mut state = ch.state.lock().unwrap();
+                let next_deadline = ch.requeue_expired(&mut state, now);
+                if let Some((id, attempt, data)) = state.queue.pop_front() {
+                    let attempt = attempt + 1;
+                    state.in_flight.insert(
+                        id,
+                        InFlight {
+                            data: data.clone(),
+                            attempt,
+                            deadline: now + ch.visibility_timeout,
+                        },
+                    );
+                    return Some(AckMessage { data, id, attempt });
+                }
+                if state.in_flight.is_empty() && ch.senders.load(atomic::Ordering::SeqCst) == 0 {
+                    return None;
+                }
+                next_deadline
+            };
+            if let Some(deadline) = next_deadline {
+                tokio::select! {
+                    () = notified => {}
+                    () = tokio::time::sleep_until(deadline) => {}
+                }
+            } else {
+                notified.await;
+            }
+        }
+    }
+}
+
+impl<T> AckReceiver<T> {
+    /// Acknowledges the delivery, returns false if the message is unknown or has already been
+    /// acknowledged, dead-lettered or redelivered (the token of a previous delivery is stale)
+    ///
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn ack(&self, token: AckToken) -> bool {
+        let ch = &self.channel;
+        let acked = {
+            let mut state = ch.state.lock().unwrap();
+            if state
+                .in_flight
+                .get(&token.id)
+                .map_or(false, |m| m.attempt == token.attempt)
+            {
+                state.in_flight.remove(&token.id);
+                true
+            } else {
+                false
+            }
+        };
+        if acked {
+            ch.space_available.notify_one();
+            // wake up receivers waiting for the in-flight messages to finish the stream
+            ch.data_available.notify_waiters();
+        }
+        acked
+    }
+
+    /// Negative acknowledgement, the message is redelivered immediately (or dead-lettered if
+    /// the maximum number of attempts is reached). Stale tokens are ignored, returning false
+    ///
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn nack(&self, token: AckToken) -> bool {
+        let ch = &self.channel;
+        let mut state = ch.state.lock().unwrap();
+        if let Some(msg) = state
+            .in_flight
+            .get_mut(&token.id)
+            .filter(|m| m.attempt == token.attempt)
+        {
+            msg.deadline = Instant::now();
+            ch.requeue_expired(&mut state, Instant::now());
+            drop(state);
+            ch.data_available.notify_one();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Number of delivered but not yet acknowledged messages
+    ///
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn in_flight(&self) -> usize {
+        self.channel.state.lock().unwrap().in_flight.len()
+    }
+}
+
+/// Creates a channel with at-least-once delivery: the received messages must be acknowledged,
+/// otherwise they are redelivered after the visibility timeout. Messages not acknowledged after
+/// max_attempts deliveries are sent to the dead letter receiver (dropped if it is gone)
+///
+/// ```rust
+/// use bmart::mpsc::acked_channel;
+/// use std::time::Duration;
+///
+/// # #[tokio::main] async fn main() {
+/// let (tx, rx, _dead) = acked_channel(10, Duration::from_secs(5), 3);
+/// tx.send("job").await.unwrap();
+/// let msg = rx.recv().await.unwrap();
+/// assert_eq!(msg.data, "job");
+/// assert!(rx.ack(msg.token()));
+/// # }
+/// ```
+pub fn acked_channel<T>(
+    buf: usize,
+    visibility_timeout: Duration,
+    max_attempts: u32,
+) -> (
+    AckSender<T>,
+    AckReceiver<T>,
+    mpsc::UnboundedReceiver<DeadLetter<T>>,
+) {
+    let (dead_letters, dead_rx) = mpsc::unbounded_channel();
+    let channel = Arc::new(AckChannel {
+        state: Mutex::new(AckState {
+            queue: VecDeque::with_capacity(buf),
+            in_flight: BTreeMap::new(),
+            next_id: 0,
+        }),
+        capacity: buf.max(1),
+        visibility_timeout,
+        max_attempts: max_attempts.max(1),
+        senders: atomic::AtomicUsize::new(1),
+        receivers: atomic::AtomicUsize::new(1),
+        dead_letters,
+        data_available: Notify::new(),
+        space_available: Notify::new(),
+    });
+    (
+        AckSender {
+            channel: channel.clone(),
+        },
+        AckReceiver { channel },
+        dead_rx,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::acked_channel;
+    use std::time::Duration;
+
+    const VISIBILITY: Duration = Duration::from_millis(20);
+
+    #[tokio::test]
+    async fn test_acked_redelivery() {
+        let (tx, rx, _dead) = acked_channel(10, VISIBILITY, 3);
+        let id = tx.send(1).await.unwrap();
+        let first = rx.recv().await.unwrap();
+        assert_eq!((first.id(), first.attempt()), (id, 1));
+        assert_eq!(rx.in_flight(), 1);
+        // not acknowledged within the visibility timeout
+        let second = rx.recv().await.unwrap();
+        assert_eq!((second.id(), second.attempt(), second.data), (id, 2, 1));
+        assert!(rx.ack(second.token()));
+        assert_eq!(rx.in_flight(), 0);
+        drop(tx);
+        assert!(rx.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_acked_nack() {
+        let (tx, rx, _dead) = acked_channel(10, Duration::from_secs(60), 3);
+        tx.send(1).await.unwrap();
+        let first = rx.recv().await.unwrap();
+        assert!(rx.nack(first.token()));
+        let second = tokio::time::timeout(Duration::from_secs(1), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(second.attempt(), 2);
+        assert!(rx.ack(second.token()));
+    }
+
+    #[tokio::test]
+    async fn test_acked_stale_token() {
+        let (tx, rx, _dead) = acked_channel(10, VISIBILITY, 3);
+        tx.send(1).await.unwrap();
+        let first = rx.recv().await.unwrap();
+        let second = rx.recv().await.unwrap();
+        assert_eq!(first.id(), second.id());
+        // the first consumer is too late, the message is owned by the second delivery
+        assert!(!rx.ack(first.token()));
+        assert!(!rx.nack(first.token()));
+        assert_eq!(rx.in_flight(), 1);
+        assert!(rx.ack(second.token()));
+        assert!(!rx.ack(second.token()));
+    }
+
+    #[tokio::test]
+    async fn test_acked_dead_letter() {
+        let (tx, rx, mut dead) = acked_channel(1, VISIBILITY, 2);
+        let id = tx.send("job").await.unwrap();
+        for attempt in 1..=2 {
+            let msg = rx.recv().await.unwrap();
+            assert_eq!(msg.attempt(), attempt);
+        }
+        let dead_letter = tokio::time::timeout(Duration::from_secs(1), async {
+            loop {
+                // drives the redelivery check
+                let _r = tokio::time::timeout(VISIBILITY * 2, rx.recv()).await;
+                if let Ok(d) = dead.try_recv() {
+                    break d;
+                }
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(
+            (dead_letter.id, dead_letter.attempts, dead_letter.data),
+            (id, 2, "job")
+        );
+        assert_eq!(rx.in_flight(), 0);
+        // the slot is freed
+        tokio::time::timeout(Duration::from_secs(1), tx.send("next"))
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_acked_requeue_order() {
+        let (tx, rx, _dead) = acked_channel(10, VISIBILITY, 3);
+        for i in 0..3 {
+            tx.send(i).await.unwrap();
+        }
+        for _ in 0..3 {
+            rx.recv().await.unwrap();
+        }
+        tokio::time::sleep(VISIBILITY * 2).await;
+        for i in 0..3 {
+            let msg = rx.recv().await.unwrap();
+            assert_eq!((msg.data, msg.attempt()), (i, 2));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_acked_send_no_receiver_polling() {
+        let (tx, rx, mut dead) = acked_channel(1, VISIBILITY, 1);
+        tx.send(1).await.unwrap();
+        let msg = rx.recv().await.unwrap();
+        assert_eq!(msg.attempt(), 1);
+        // the receiver is alive but does not poll, the slot is freed by the sender
+        tokio::time::timeout(Duration::from_secs(1), tx.send(2))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(dead.try_recv().unwrap().data, 1);
     }
 }
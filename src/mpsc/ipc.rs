@@ -0,0 +1,273 @@
+//! Local inter-process channels over Unix domain sockets
+//!
+//! Messages are serialized to JSON and framed with a 32-bit big-endian length prefix. Each
+//! side gets a `SafeSender` for outgoing and a `SafeReceiver` for incoming messages, the
+//! connection is re-established automatically: the listener accepts the next client, the
+//! connecting side reconnects. Outgoing messages are buffered in the channel while
+//! disconnected, a message written right before the peer is gone may be lost.
+//!
+//! ```rust,no_run
+//! use bmart::mpsc::ipc::{ipc_connect, ipc_listen};
+//! use std::time::Duration;
+//!
+//! # async fn f() -> Result<(), bmart::Error> {
+//! let (_tx, mut rx) = ipc_listen::<String, String>("/tmp/helper.sock", 100, Duration::from_secs(5)).await?;
+//! let (tx, _rx) = ipc_connect::<String, String>("/tmp/helper.sock", 100, Duration::from_secs(5)).await?;
+//! tx.safe_send("hello".to_owned()).await?;
+//! assert_eq!(rx.safe_recv().await?.unwrap(), "hello");
+//! # Ok(())
+//! # }
+//! ```
+use super::{SafeReceiver, SafeSender};
+use crate::Error;
+use serde::{de::DeserializeOwned, Serialize};
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{unix, UnixListener, UnixStream};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// Maximum frame size, larger frames are considered as a protocol error
+pub const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+const ERR_FRAME_TOO_LARGE: &str = "IPC frame too large";
+const ERR_NOT_A_SOCKET: &str = "IPC path exists and is not a socket";
+const ERR_SOCKET_IN_USE: &str = "IPC socket is in use";
+
+async fn read_frame<R: DeserializeOwned>(reader: &mut unix::OwnedReadHalf) -> Result<R, Error> {
+    let len = reader.read_u32().await? as usize;
+    if len > MAX_FRAME_SIZE {
+        return Err(Error::invalid_data(ERR_FRAME_TOO_LARGE));
+    }
+    let mut buf = vec![0; len];
+    reader.read_exact(&mut buf).await?;
+    serde_json::from_slice(&buf).map_err(Error::invalid_data)
+}
+
+async fn write_frame(writer: &mut unix::OwnedWriteHalf, frame: &[u8]) -> Result<(), Error> {
+    let len = u32::try_from(frame.len()).map_err(|_| Error::invalid_data(ERR_FRAME_TOO_LARGE))?;
+    writer.write_u32(len).await?;
+    writer.write_all(frame).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+enum Endpoint {
+    Listener(UnixListener, PathBuf),
+    Client(PathBuf),
+}
+
+impl Drop for Endpoint {
+    fn drop(&mut self) {
+        if let Endpoint::Listener(_, path) = self {
+            if let Err(e) = std::fs::remove_file(&path) {
+                log::debug!("IPC socket {} removal failed: {}", path.display(), e);
+            }
+        }
+    }
+}
+
+impl Endpoint {
+    async fn connect(&self) -> UnixStream {
+        loop {
+            let result = match self {
+                Endpoint::Listener(listener, _) => {
+                    listener.accept().await.map(|(stream, _)| stream)
+                }
+                Endpoint::Client(path) => UnixStream::connect(path).await,
+            };
+            match result {
+                Ok(stream) => return stream,
+                Err(e) => {
+                    log::debug!("IPC connection failed: {}", e);
+                    tokio::time::sleep(RECONNECT_DELAY).await;
+                }
+            }
+        }
+    }
+}
+
+fn spawn_reader<R>(mut reader: unix::OwnedReadHalf, tx: mpsc::Sender<R>) -> JoinHandle<()>
+where
+    R: DeserializeOwned + Send + 'static,
+{
+    tokio::spawn(async move {
+        loop {
+            match read_frame(&mut reader).await {
+                Ok(data) => {
+                    // incoming messages are discarded if the local receiver is dropped
+                    let _r = tx.send(data).await;
+                }
+                Err(e) => {
+                    if e.kind != crate::ErrorKind::IO {
+                        log::warn!("IPC read error: {}", e);
+                    }
+                    break;
+                }
+            }
+        }
+    })
+}
+
+fn encode<S: Serialize>(data: &S) -> Option<Vec<u8>> {
+    serde_json::to_vec(data)
+        .map_err(|e| log::error!("IPC message serialization error: {}", e))
+        .ok()
+}
+
+async fn bridge<S, R>(
+    endpoint: Endpoint,
+    mut stream: Option<UnixStream>,
+    mut out_rx: mpsc::Receiver<S>,
+    in_tx: mpsc::Sender<R>,
+) where
+    S: Serialize + Send + 'static,
+    R: DeserializeOwned + Send + 'static,
+{
+    // the frame which failed to be written, resent after reconnecting
+    let mut pending: Option<Vec<u8>> = None;
+    // false when all local senders are dropped, the bridge keeps receiving until the local
+    // receiver is dropped as well
+    let mut out_open = true;
+    loop {
+        let stream = if let Some(stream) = stream.take() {
+            stream
+        } else {
+            tokio::select! {
+                stream = endpoint.connect() => stream,
+                data = out_rx.recv(), if out_open && pending.is_none() => {
+                    if let Some(data) = data {
+                        pending = encode(&data);
+                    } else {
+                        out_open = false;
+                    }
+                    continue;
+                }
+                () = in_tx.closed(), if !out_open => return,
+            }
+        };
+        let (reader, mut writer) = stream.into_split();
+        let mut reader_fut = spawn_reader(reader, in_tx.clone());
+        loop {
+            let frame = if let Some(frame) = pending.take() {
+                frame
+            } else {
+                tokio::select! {
+                    biased;
+                    _ = &mut reader_fut => break,
+                    data = out_rx.recv(), if out_open => {
+                        let Some(data) = data else {
+                            out_open = false;
+                            continue;
+                        };
+                        let Some(frame) = encode(&data) else {
+                            continue;
+                        };
+                        frame
+                    }
+                    () = in_tx.closed(), if !out_open => {
+                        reader_fut.abort();
+                        return;
+                    }
+                }
+            };
+            if let Err(e) = write_frame(&mut writer, &frame).await {
+                log::debug!("IPC write error: {}", e);
+                pending.replace(frame);
+                break;
+            }
+        }
+        reader_fut.abort();
+    }
+}
+
+fn channels<S, R>(
+    endpoint: Endpoint,
+    stream: Option<UnixStream>,
+    buf: usize,
+    timeout: Duration,
+) -> (SafeSender<S>, SafeReceiver<R>)
+where
+    S: Serialize + Send + 'static,
+    R: DeserializeOwned + Send + 'static,
+{
+    let (out_tx, out_rx) = mpsc::channel(buf);
+    let (in_tx, in_rx) = mpsc::channel(buf);
+    tokio::spawn(bridge(endpoint, stream, out_rx, in_tx));
+    (
+        SafeSender::new(out_tx, timeout),
+        SafeReceiver::new(in_rx, timeout),
+    )
+}
+
+/// Listens on the socket path and serves one peer at a time. A stale socket file (no one is
+/// listening on it) is removed. The bridge task is finished and the socket file is removed
+/// when all the senders and the receiver are dropped
+///
+/// # Errors
+///
+/// Will return `Err` if the socket can not be bound or with `ErrorKind::Busy` if the path is
+/// not a socket or another process is listening on it
+pub async fn ipc_listen<S, R>(
+    path: impl AsRef<Path>,
+    buf: usize,
+    timeout: Duration,
+) -> Result<(SafeSender<S>, SafeReceiver<R>), Error>
+where
+    S: Serialize + Send + 'static,
+    R: DeserializeOwned + Send + 'static,
+{
+    let path = path.as_ref();
+    match tokio::fs::symlink_metadata(path).await {
+        Ok(meta) => {
+            if !meta.file_type().is_socket() {
+                return Err(Error::busy(format!(
+                    "{}: {}",
+                    ERR_NOT_A_SOCKET,
+                    path.display()
+                )));
+            }
+            if UnixStream::connect(path).await.is_ok() {
+                return Err(Error::busy(format!(
+                    "{}: {}",
+                    ERR_SOCKET_IN_USE,
+                    path.display()
+                )));
+            }
+            tokio::fs::remove_file(path).await?;
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+    let listener = UnixListener::bind(path)?;
+    Ok(channels(
+        Endpoint::Listener(listener, path.to_owned()),
+        None,
+        buf,
+        timeout,
+    ))
+}
+
+/// Connects to the socket path, reconnecting automatically if the connection is lost. The
+/// bridge task is finished when all the senders and the receiver are dropped
+///
+/// # Errors
+///
+/// Will return `Err` if the initial connection fails
+pub async fn ipc_connect<S, R>(
+    path: impl AsRef<Path>,
+    buf: usize,
+    timeout: Duration,
+) -> Result<(SafeSender<S>, SafeReceiver<R>), Error>
+where
+    S: Serialize + Send + 'static,
+    R: DeserializeOwned + Send + 'static,
+{
+    let path = path.as_ref().to_owned();
+    let stream = UnixStream::connect(&path).await?;
+    Ok(channels(Endpoint::Client(path), Some(stream), buf, timeout))
+}
@@ -0,0 +1,229 @@
+use crate::Error;
+use serde::{de::DeserializeOwned, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+
+#[derive(Debug)]
+struct SpillQueue {
+    path: PathBuf,
+    file: File,
+    offset: u64,
+    spilled: usize,
+}
+
+impl SpillQueue {
+    fn push<T: Serialize>(&mut self, data: &T) -> Result<(), Error> {
+        let mut buf = serde_json::to_vec(data).map_err(Error::invalid_data)?;
+        buf.push(b'\n');
+        self.file.seek(SeekFrom::End(0))?;
+        self.file.write_all(&buf)?;
+        self.spilled += 1;
+        Ok(())
+    }
+    fn pop<T: DeserializeOwned>(&mut self) -> Result<T, Error> {
+        self.file.seek(SeekFrom::Start(self.offset))?;
+        let mut line = String::new();
+        let len = BufReader::new(&self.file).read_line(&mut line)?;
+        self.offset += len as u64;
+        self.spilled -= 1;
+        if self.spilled == 0 {
+            self.file.set_len(0)?;
+            self.offset = 0;
+        }
+        serde_json::from_str(&line).map_err(Error::invalid_data)
+    }
+}
+
+/// Sender of a spillover channel
+///
+/// When the channel is full, messages are written to the disk queue and replayed to the
+/// receiver in the original order
+#[derive(Debug)]
+pub struct SpillSender<T> {
+    tx: mpsc::Sender<T>,
+    queue: Arc<Mutex<SpillQueue>>,
+}
+
+impl<T> Clone for SpillSender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            tx: self.tx.clone(),
+            queue: self.queue.clone(),
+        }
+    }
+}
+
+impl<T: Serialize> SpillSender<T> {
+    /// The method is synchronous: when the channel is full, the message is written to the disk
+    /// queue in the current thread, use `send_async` from async code
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the receiver is dropped or the message can not be written to the
+    /// disk queue
+    ///
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn send(&self, data: T) -> Result<(), Error> {
+        let mut queue = self.queue.lock().unwrap();
+        if queue.spilled == 0 {
+            match self.tx.try_send(data) {
+                Ok(()) => Ok(()),
+                Err(mpsc::error::TrySendError::Full(data)) => queue.push(&data),
+                Err(e) => Err(Error::internal(e)),
+            }
+        } else if self.tx.is_closed() {
+            Err(Error::internal(super::ERR_CHANNEL_CLOSED))
+        } else {
+            queue.push(&data)
+        }
+    }
+    /// Same as `send` but the disk queue is written in a blocking task
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the receiver is dropped or the message can not be written to the
+    /// disk queue
+    pub async fn send_async(&self, data: T) -> Result<(), Error>
+    where
+        T: Send + 'static,
+    {
+        let sender = self.clone();
+        tokio::task::spawn_blocking(move || sender.send(data))
+            .await
+            .map_err(Error::internal)?
+    }
+    /// Number of messages currently stored on disk
+    ///
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn spilled(&self) -> usize {
+        self.queue.lock().unwrap().spilled
+    }
+}
+
+/// Receiver of a spillover channel, the disk queue file is removed on drop
+#[derive(Debug)]
+pub struct SpillReceiver<T> {
+    rx: mpsc::Receiver<T>,
+    queue: Arc<Mutex<SpillQueue>>,
+}
+
+impl<T> Drop for SpillReceiver<T> {
+    fn drop(&mut self) {
+        if let Ok(queue) = self.queue.lock() {
+            let _r = fs::remove_file(&queue.path);
+        }
+    }
+}
+
+impl<T: DeserializeOwned + Send + 'static> SpillReceiver<T> {
+    /// Returns `Ok(None)` if all senders are dropped and both the channel and the disk queue
+    /// are empty
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if a spilled message can not be read from the disk queue
+    ///
+    /// # Panics
+    ///
+    /// Should not panic
+    pub async fn recv(&mut self) -> Result<Option<T>, Error> {
+        // messages in the channel are always older than spilled ones, as senders stop using
+        // the channel until the disk queue is drained. The channel is checked under the queue
+        // lock, as senders spill under it: if the channel is empty, the first spilled message
+        // stays the oldest one, as no messages are put into the channel until it is popped
+        let spilled = {
+            let queue = self.queue.lock().unwrap();
+            if let Ok(data) = self.rx.try_recv() {
+                return Ok(Some(data));
+            }
+            queue.spilled > 0
+        };
+        if spilled {
+            return self.pop_spilled().await;
+        }
+        if let Some(data) = self.rx.recv().await {
+            return Ok(Some(data));
+        }
+        self.pop_spilled().await
+    }
+    async fn pop_spilled(&self) -> Result<Option<T>, Error> {
+        let queue = self.queue.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut queue = queue.lock().unwrap();
+            if queue.spilled == 0 {
+                Ok(None)
+            } else {
+                queue.pop().map(Some)
+            }
+        })
+        .await
+        .map_err(Error::internal)?
+    }
+}
+
+/// Creates a bounded channel which spills overflow messages to the disk queue file
+///
+/// # Errors
+///
+/// Will return `Err` if the disk queue file can not be created
+pub fn spillover_channel<T, P>(
+    buf: usize,
+    path: P,
+) -> Result<(SpillSender<T>, SpillReceiver<T>), Error>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref().to_owned();
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)?;
+    let queue = Arc::new(Mutex::new(SpillQueue {
+        path,
+        file,
+        offset: 0,
+        spilled: 0,
+    }));
+    let (tx, rx) = mpsc::channel(buf);
+    Ok((
+        SpillSender {
+            tx,
+            queue: queue.clone(),
+        },
+        SpillReceiver { rx, queue },
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::spillover_channel;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_spillover_fifo() {
+        const COUNT: u32 = 5_000;
+        let path = std::env::temp_dir().join(format!("bmart-spill-{}", std::process::id()));
+        let (tx, mut rx) = spillover_channel::<u32, _>(2, &path).unwrap();
+        // the sender fills the channel and spills while the receiver is in the middle of recv
+        let sender = std::thread::spawn(move || {
+            for i in 0..COUNT {
+                tx.send(i).unwrap();
+            }
+        });
+        let mut expected = 0;
+        while let Some(v) = rx.recv().await.unwrap() {
+            assert_eq!(v, expected);
+            expected += 1;
+        }
+        assert_eq!(expected, COUNT);
+        sender.join().unwrap();
+    }
+}
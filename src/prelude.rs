@@ -0,0 +1,12 @@
+//! Commonly used items
+//!
+//! ```
+//! use bmart::prelude::*;
+//! ```
+pub use crate::mpsc::{safe_channel, SafeReceiver, SafeSender};
+pub use crate::process::Options;
+pub use crate::sync::SharedLockFactory;
+pub use crate::tools::FutureTimeoutExt;
+pub use crate::workers::WorkerFactory;
+pub use crate::{bail, ensure, worker, worker_cancellable, worker_queued};
+pub use crate::{Error, ErrorKind, ResultExt};
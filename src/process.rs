@@ -1,3 +1,5 @@
+use crate::tools::framed_lines;
+use crate::ErrorKind;
 use async_channel::Receiver;
 use colored::Colorize;
 use log::error;
@@ -5,42 +7,68 @@ use log::error;
 pub use nix::sys::signal::Signal;
 #[cfg(not(target_os = "windows"))]
 use nix::{sys::signal, unistd};
-use std::collections::HashMap;
-#[cfg(not(target_os = "windows"))]
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::ffi::OsStr;
+use std::fmt;
 use std::io;
 use std::process::Stdio;
 use std::time::Duration;
 #[cfg(not(target_os = "windows"))]
 use std::time::Instant;
 #[cfg(not(target_os = "windows"))]
-use sysinfo::{Pid, PidExt, ProcessExt, System, SystemExt};
+use sysinfo::PidExt;
+use sysinfo::{Pid, ProcessExt, System, SystemExt};
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter};
 use tokio::process::Command;
 use tokio::task;
 use tokio::time::sleep;
 
+#[cfg(not(target_os = "windows"))]
+mod confine;
+mod monitor;
+mod service_ctl;
+#[cfg(feature = "verify-binary")]
+mod verify;
 #[cfg(target_os = "windows")]
-use std::ptr::null_mut;
-#[cfg(target_os = "windows")]
-use winapi::um::processthreadsapi::{OpenProcess, TerminateProcess};
+mod win_ctrl;
+
+#[cfg(all(target_os = "linux", feature = "namespaces"))]
+pub use confine::Namespaces;
+pub use monitor::{monitor, monitor_tree, ProcSample};
+pub use service_ctl::{
+    service_ctl, service_ctl_with, ActiveState, ServiceAction, ServiceManager, ServiceStatus,
+};
+#[cfg(feature = "verify-binary")]
+pub use verify::Sha256;
 #[cfg(target_os = "windows")]
-use winapi::um::winnt::{PROCESS_QUERY_INFORMATION, PROCESS_TERMINATE};
+pub use win_ctrl::terminate_process;
 
 pub const SLEEP_STEP: Duration = Duration::from_millis(100);
 
-#[cfg(target_os = "windows")]
-fn kill(pid: u32) {
-    let pc = unsafe { OpenProcess(PROCESS_QUERY_INFORMATION | PROCESS_TERMINATE, 0, pid) };
-    if pc != null_mut() {
-        unsafe { TerminateProcess(pc, 1) };
+pub fn suicide(timeout: Duration, warn: bool) {
+    if warn {
+        let msg = format!("Killing process in {:?}", timeout);
+        eprintln!("{}", msg.red().bold());
     }
+    std::thread::spawn(move || {
+        let pid = std::process::id();
+        std::thread::sleep(timeout);
+        #[allow(clippy::cast_possible_wrap)]
+        #[cfg(not(target_os = "windows"))]
+        let _ = signal::kill(unistd::Pid::from_raw(pid as i32), Signal::SIGKILL);
+        #[cfg(target_os = "windows")]
+        win_ctrl::kill(pid);
+    });
 }
 
-pub fn suicide(timeout: Duration, warn: bool) {
+/// Same as `suicide` but asks the process to terminate gracefully first (SIGTERM on Unix,
+/// Ctrl-Break on Windows) and kills it if it is still alive after the grace period.
+///
+/// On Windows Ctrl-Break can be delivered only if the process is a process group leader (e.g.
+/// started by `command` with tki set), it can be handled with `tokio::signal::windows::ctrl_break`
+pub fn suicide_graceful(timeout: Duration, grace: Duration, warn: bool) {
     if warn {
-        let msg = format!("Killing process in {:?}", timeout);
+        let msg = format!("Terminating process in {:?}", timeout);
         eprintln!("{}", msg.red().bold());
     }
     std::thread::spawn(move || {
@@ -48,9 +76,15 @@ pub fn suicide(timeout: Duration, warn: bool) {
         std::thread::sleep(timeout);
         #[allow(clippy::cast_possible_wrap)]
         #[cfg(not(target_os = "windows"))]
+        let _ = signal::kill(unistd::Pid::from_raw(pid as i32), Signal::SIGTERM);
+        #[cfg(target_os = "windows")]
+        win_ctrl::ctrl_break(pid);
+        std::thread::sleep(grace);
+        #[allow(clippy::cast_possible_wrap)]
+        #[cfg(not(target_os = "windows"))]
         let _ = signal::kill(unistd::Pid::from_raw(pid as i32), Signal::SIGKILL);
         #[cfg(target_os = "windows")]
-        kill(pid);
+        win_ctrl::kill(pid);
     });
 }
 
@@ -86,7 +120,6 @@ impl CommandResult {
     }
 }
 
-#[cfg(not(target_os = "windows"))]
 fn get_child_pids_recursive(pid: Pid, sys: &System, to: &mut HashSet<Pid>) {
     for (i, p) in sys.processes() {
         if let Some(parent) = p.parent() {
@@ -174,11 +207,21 @@ enum CommandFrame {
     Error(io::Error),
 }
 
+const REDACTED: &str = "***";
+
 #[derive(Default, Clone)]
 pub struct Options<'a> {
     environment: HashMap<&'a str, &'a str>,
+    secrets: std::collections::BTreeSet<&'a str>,
     tki: Option<Duration>,
     input_data: Option<std::borrow::Cow<'a, Vec<u8>>>,
+    max_line_len: Option<usize>,
+    #[cfg(feature = "verify-binary")]
+    verify_binary: Option<Sha256>,
+    #[cfg(not(target_os = "windows"))]
+    chroot: Option<std::path::PathBuf>,
+    #[cfg(all(target_os = "linux", feature = "namespaces"))]
+    namespaces: Namespaces,
 }
 
 impl<'a> Options<'a> {
@@ -186,6 +229,9 @@ impl<'a> Options<'a> {
     pub fn new() -> Self {
         Self::default()
     }
+    /// Term-kill interval: on timeout the process tree gets SIGTERM and is killed if still
+    /// alive after the interval. On Windows the child is started in a new process group and
+    /// gets Ctrl-Break instead of SIGTERM
     #[inline]
     pub fn tki(mut self, t: Duration) -> Self {
         self.tki.replace(t);
@@ -196,26 +242,102 @@ impl<'a> Options<'a> {
         self.input_data.replace(data);
         self
     }
+    /// Verifies the executable checksum before spawning, the process is not started on
+    /// mismatch (`InvalidData` error). If chroot is set, the executable is looked up and verified
+    /// inside the new root directory.
+    ///
+    /// On Linux the verified file is executed by its descriptor (via `/proc/self/fd`), so it can
+    /// not be replaced between the check and exec. Elsewhere, with chroot or if `/proc` is not
+    /// mounted the program is executed by the path and the race remains: the binary must be
+    /// placed in a directory writable by trusted users only
+    #[cfg(feature = "verify-binary")]
+    #[inline]
+    pub fn verify_binary(mut self, digest: Sha256) -> Self {
+        self.verify_binary.replace(digest);
+        self
+    }
+    /// Changes the root directory of the child process (requires privileges)
+    #[cfg(not(target_os = "windows"))]
+    #[inline]
+    pub fn chroot<P: Into<std::path::PathBuf>>(mut self, path: P) -> Self {
+        self.chroot.replace(path.into());
+        self
+    }
+    /// Moves the child process into new namespaces (requires privileges)
+    #[cfg(all(target_os = "linux", feature = "namespaces"))]
+    #[inline]
+    pub fn unshare(mut self, namespaces: Namespaces) -> Self {
+        self.namespaces |= namespaces;
+        self
+    }
+    /// Maximum output line length for [`command_pipe`], longer lines are dropped
+    #[inline]
+    pub fn max_line_len(mut self, max_len: usize) -> Self {
+        self.max_line_len.replace(max_len);
+        self
+    }
     #[inline]
     pub fn env(mut self, name: &'a str, value: &'a str) -> Self {
         self.environment.insert(name, value);
         self
     }
+    /// Sets an environment variable which value is masked in the output produced by the crate
+    /// (`Debug`, tracing). Once marked as secret, the variable stays secret if overridden
+    #[inline]
+    pub fn secret_env(mut self, name: &'a str, value: &'a str) -> Self {
+        self.secrets.insert(name);
+        self.environment.insert(name, value);
+        self
+    }
+    #[inline]
+    pub fn is_secret_env(&self, name: &str) -> bool {
+        self.secrets.contains(name)
+    }
+    #[cfg(feature = "verify-binary")]
+    fn root_dir(&self) -> Option<&std::path::Path> {
+        #[cfg(not(target_os = "windows"))]
+        return self.chroot.as_deref();
+        #[cfg(target_os = "windows")]
+        None
+    }
     #[inline]
     pub fn environment(&self) -> &HashMap<&str, &str> {
         &self.environment
     }
+    /// Environment with secret values masked, sorted by name
+    pub fn redacted_environment(&self) -> BTreeMap<&str, &str> {
+        self.environment
+            .iter()
+            .map(|(&k, &v)| (k, if self.is_secret_env(k) { REDACTED } else { v }))
+            .collect()
+    }
     #[inline]
     pub fn environment_mut(&'a mut self) -> &mut HashMap<&str, &str> {
         &mut self.environment
     }
 }
 
-#[allow(clippy::too_many_lines)]
-#[allow(clippy::missing_panics_doc)]
+impl fmt::Debug for Options<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("Options");
+        d.field("environment", &self.redacted_environment())
+            .field("tki", &self.tki)
+            .field("input_data_len", &self.input_data.as_ref().map(|v| v.len()))
+            .field("max_line_len", &self.max_line_len);
+        #[cfg(feature = "verify-binary")]
+        d.field("verify_binary", &self.verify_binary);
+        #[cfg(not(target_os = "windows"))]
+        d.field("chroot", &self.chroot);
+        #[cfg(all(target_os = "linux", feature = "namespaces"))]
+        d.field("namespaces", &self.namespaces);
+        d.finish()
+    }
+}
+
 /// # Errors
 ///
 /// Will return `Err` on I/O errors
+#[cfg(feature = "tracing")]
 pub async fn command<P, I, S>(
     program: P,
     args: I,
@@ -227,14 +349,98 @@ where
     I: IntoIterator<Item = S>,
     S: AsRef<OsStr>,
 {
-    let mut child = Command::new(program)
-        .stdin(Stdio::piped())
+    use tracing::{field, Instrument};
+    let span = tracing::info_span!(
+        "command",
+        program = %program.as_ref().to_string_lossy(),
+        env = ?opts.redacted_environment(),
+        pid = field::Empty,
+        exit_code = field::Empty,
+        duration = field::Empty,
+    );
+    let started = std::time::Instant::now();
+    let result = command_impl(program, args, timeout, opts)
+        .instrument(span.clone())
+        .await;
+    span.record("duration", field::debug(started.elapsed()));
+    match result {
+        Ok(ref res) => {
+            if let Some(code) = res.code {
+                span.record("exit_code", code);
+            } else {
+                tracing::warn!(parent: &span, "command terminated");
+            }
+        }
+        Err(ref e) => tracing::error!(parent: &span, error = %e, "command failed"),
+    }
+    result
+}
+
+/// # Errors
+///
+/// Will return `Err` on I/O errors
+#[cfg(not(feature = "tracing"))]
+#[inline]
+pub async fn command<P, I, S>(
+    program: P,
+    args: I,
+    timeout: Duration,
+    opts: Options<'_>,
+) -> Result<CommandResult, io::Error>
+where
+    P: AsRef<OsStr>,
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    command_impl(program, args, timeout, opts).await
+}
+
+#[allow(clippy::too_many_lines)]
+async fn command_impl<P, I, S>(
+    program: P,
+    args: I,
+    timeout: Duration,
+    opts: Options<'_>,
+) -> Result<CommandResult, io::Error>
+where
+    P: AsRef<OsStr>,
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    #[cfg(feature = "verify-binary")]
+    let verified = if let Some(digest) = opts.verify_binary {
+        let program = program.as_ref().to_owned();
+        let root = opts.root_dir().map(ToOwned::to_owned);
+        Some(
+            task::spawn_blocking(move || {
+                verify::verified_program(&program, &digest, root.as_deref())
+            })
+            .await
+            .map_err(io::Error::other)??,
+        )
+    } else {
+        None
+    };
+    #[cfg(feature = "verify-binary")]
+    let mut cmd = verified.as_ref().map_or_else(
+        || Command::new(program.as_ref()),
+        verify::VerifiedProgram::command,
+    );
+    #[cfg(not(feature = "verify-binary"))]
+    let mut cmd = Command::new(program);
+    cmd.stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .kill_on_drop(true)
         .args(args)
-        .envs(opts.environment)
-        .spawn()?;
+        .envs(opts.environment());
+    #[cfg(not(target_os = "windows"))]
+    confine::confine(&mut cmd, &opts)?;
+    #[cfg(target_os = "windows")]
+    if opts.tki.is_some() {
+        win_ctrl::new_process_group(&mut cmd);
+    }
+    let mut child = cmd.spawn()?;
     let stdin = if opts.input_data.is_some() {
         match child.stdin.take() {
             Some(v) => Some(v),
@@ -264,6 +470,10 @@ where
     };
     let mut stderr_reader = BufReader::new(stderr).lines();
     let ppid = child.id();
+    #[cfg(feature = "tracing")]
+    if let Some(pid) = ppid {
+        tracing::Span::current().record("pid", pid);
+    }
     let (tx_runner, rx) = async_channel::bounded(2);
     let tx_guard = tx_runner.clone();
     let tx_out = tx_runner.clone();
@@ -288,7 +498,7 @@ where
             #[cfg(not(target_os = "windows"))]
             kill_pstree(pid, opts.tki, true).await;
             #[cfg(target_os = "windows")]
-            kill(pid);
+            win_ctrl::terminate_process(pid, opts.tki).await;
             let _r = tx_guard.send(CommandFrame::Terminated).await;
         })
     });
@@ -366,7 +576,7 @@ where
                     #[cfg(not(target_os = "windows"))]
                     kill_pstree(pid, opts.tki, true).await;
                     #[cfg(target_os = "windows")]
-                    kill(pid);
+                    win_ctrl::terminate_process(pid, opts.tki).await;
                 });
                 return Err(e);
             }
@@ -384,6 +594,10 @@ pub enum CommandPipeOutput {
     Terminated(i32),
 }
 
+/// If the binary verification is enabled, the program file is hashed on the calling thread.
+/// On a multi-threaded runtime the worker thread is released with `block_in_place`, on a
+/// current-thread runtime the runtime is blocked until the file is hashed
+///
 /// # Panics
 ///
 /// Should not panic
@@ -398,15 +612,43 @@ where
     S: AsRef<OsStr>,
 {
     let (output_tx, output_rx) = async_channel::bounded(512);
-
-    let mut child = Command::new(program)
-        .args(args)
+    let max_line_len = opts.max_line_len.unwrap_or(usize::MAX);
+    #[cfg(feature = "verify-binary")]
+    let verified = if let Some(ref digest) = opts.verify_binary {
+        let verify = || verify::verified_program(program.as_ref(), digest, opts.root_dir());
+        // the program file is hashed synchronously, the worker thread is handed over to other
+        // tasks while hashing if the runtime allows it
+        let multi_thread = tokio::runtime::Handle::try_current().map_or(false, |h| {
+            h.runtime_flavor() == tokio::runtime::RuntimeFlavor::MultiThread
+        });
+        Some(if multi_thread {
+            task::block_in_place(verify)?
+        } else {
+            verify()?
+        })
+    } else {
+        None
+    };
+    #[cfg(feature = "verify-binary")]
+    let mut cmd = verified.as_ref().map_or_else(
+        || Command::new(program.as_ref()),
+        verify::VerifiedProgram::command,
+    );
+    #[cfg(not(feature = "verify-binary"))]
+    let mut cmd = Command::new(program);
+    cmd.args(args)
         .stdin(Stdio::piped())
         .stderr(Stdio::piped())
         .stdout(Stdio::piped())
         .kill_on_drop(true)
-        .envs(opts.environment())
-        .spawn()?;
+        .envs(opts.environment());
+    #[cfg(not(target_os = "windows"))]
+    confine::confine(&mut cmd, &opts)?;
+    #[cfg(target_os = "windows")]
+    if opts.tki.is_some() {
+        win_ctrl::new_process_group(&mut cmd);
+    }
+    let mut child = cmd.spawn()?;
     let stdin = if opts.input_data.is_some() {
         match child.stdin.take() {
             Some(v) => Some(v),
@@ -448,36 +690,42 @@ where
         let output_tx_stderr = output_tx.clone();
 
         let stderr_handle = tokio::spawn(async move {
-            let mut reader = BufReader::new(stderr);
-            let mut line = String::new();
-            while reader.read_line(&mut line).await.is_ok() {
-                if line.is_empty()
-                    || (output_tx_stderr
-                        .send(CommandPipeOutput::Stderr(line.clone()))
-                        .await)
-                        .is_err()
-                {
-                    break;
+            let mut reader = framed_lines(stderr, max_line_len).keep_newline(true);
+            loop {
+                match reader.next_line().await {
+                    Ok(Some(line)) => {
+                        if output_tx_stderr
+                            .send(CommandPipeOutput::Stderr(line))
+                            .await
+                            .is_err()
+                        {
+                            break;
+                        }
+                    }
+                    Err(e) if e.kind == ErrorKind::InvalidData => {}
+                    Ok(None) | Err(_) => break,
                 }
-                line.clear();
             }
         });
 
         let output_tx_stdout = output_tx.clone();
 
         let stdout_handle = tokio::spawn(async move {
-            let mut reader = BufReader::new(stdout);
-            let mut line = String::new();
-            while reader.read_line(&mut line).await.is_ok() {
-                if line.is_empty()
-                    || (output_tx_stdout
-                        .send(CommandPipeOutput::Stdout(line.clone()))
-                        .await)
-                        .is_err()
-                {
-                    break;
+            let mut reader = framed_lines(stdout, max_line_len).keep_newline(true);
+            loop {
+                match reader.next_line().await {
+                    Ok(Some(line)) => {
+                        if output_tx_stdout
+                            .send(CommandPipeOutput::Stdout(line))
+                            .await
+                            .is_err()
+                        {
+                            break;
+                        }
+                    }
+                    Err(e) if e.kind == ErrorKind::InvalidData => {}
+                    Ok(None) | Err(_) => break,
                 }
-                line.clear();
             }
         });
 
@@ -501,3 +749,84 @@ where
 
     Ok(output_rx)
 }
+
+/// Log levels for [`log_bridge`]
+#[derive(Debug, Copy, Clone)]
+pub struct LevelMap {
+    stdout: log::Level,
+    stderr: log::Level,
+    parse_prefixes: bool,
+}
+
+impl Default for LevelMap {
+    fn default() -> Self {
+        Self {
+            stdout: log::Level::Info,
+            stderr: log::Level::Warn,
+            parse_prefixes: false,
+        }
+    }
+}
+
+impl LevelMap {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+    #[inline]
+    #[must_use]
+    pub fn stdout(mut self, level: log::Level) -> Self {
+        self.stdout = level;
+        self
+    }
+    #[inline]
+    #[must_use]
+    pub fn stderr(mut self, level: log::Level) -> Self {
+        self.stderr = level;
+        self
+    }
+    /// Use level prefixes provided by the child ("ERROR:", "WARN:" etc.) when present
+    #[inline]
+    #[must_use]
+    pub fn parse_prefixes(mut self, parse: bool) -> Self {
+        self.parse_prefixes = parse;
+        self
+    }
+    fn map<'a>(&self, line: &'a str, default: log::Level) -> (log::Level, &'a str) {
+        if self.parse_prefixes {
+            if let Some((prefix, rest)) = line.split_once(':') {
+                let level = match prefix.trim().to_ascii_uppercase().as_str() {
+                    "ERROR" | "ERR" | "CRIT" | "CRITICAL" => Some(log::Level::Error),
+                    "WARN" | "WARNING" => Some(log::Level::Warn),
+                    "INFO" => Some(log::Level::Info),
+                    "DEBUG" => Some(log::Level::Debug),
+                    "TRACE" => Some(log::Level::Trace),
+                    _ => None,
+                };
+                if let Some(level) = level {
+                    return (level, rest.trim_start());
+                }
+            }
+        }
+        (default, line)
+    }
+}
+
+/// Forwards [`command_pipe`] output into the log crate, returns the process exit code when
+/// terminated or `None` if the channel has been closed
+pub async fn log_bridge(
+    rx: Receiver<CommandPipeOutput>,
+    target: &str,
+    level_map: LevelMap,
+) -> Option<i32> {
+    while let Ok(output) = rx.recv().await {
+        let (line, default) = match output {
+            CommandPipeOutput::Stdout(ref line) => (line, level_map.stdout),
+            CommandPipeOutput::Stderr(ref line) => (line, level_map.stderr),
+            CommandPipeOutput::Terminated(code) => return Some(code),
+        };
+        let (level, msg) = level_map.map(line.trim_end_matches(['\r', '\n']), default);
+        log::log!(target: target, level, "{}", msg);
+    }
+    None
+}
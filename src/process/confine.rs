@@ -0,0 +1,84 @@
+use super::Options;
+use nix::libc;
+use std::ffi::CString;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use tokio::process::Command;
+
+/// Linux namespaces to unshare for child processes
+///
+/// Note that a new PID namespace is applied to the children of the started process only, the
+/// process itself stays in the parent namespace.
+#[cfg(all(target_os = "linux", feature = "namespaces"))]
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub struct Namespaces(libc::c_int);
+
+#[cfg(all(target_os = "linux", feature = "namespaces"))]
+impl Namespaces {
+    pub const MOUNT: Namespaces = Namespaces(libc::CLONE_NEWNS);
+    pub const PID: Namespaces = Namespaces(libc::CLONE_NEWPID);
+    pub const NET: Namespaces = Namespaces(libc::CLONE_NEWNET);
+    pub const IPC: Namespaces = Namespaces(libc::CLONE_NEWIPC);
+    pub const UTS: Namespaces = Namespaces(libc::CLONE_NEWUTS);
+    #[inline]
+    pub const fn empty() -> Self {
+        Namespaces(0)
+    }
+    #[inline]
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+    #[inline]
+    pub const fn contains(self, other: Namespaces) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+#[cfg(all(target_os = "linux", feature = "namespaces"))]
+impl std::ops::BitOr for Namespaces {
+    type Output = Self;
+    fn bitor(self, rhs: Self) -> Self {
+        Namespaces(self.0 | rhs.0)
+    }
+}
+
+#[cfg(all(target_os = "linux", feature = "namespaces"))]
+impl std::ops::BitOrAssign for Namespaces {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// Applies chroot and namespace options to the command
+pub(super) fn confine(cmd: &mut Command, opts: &Options<'_>) -> Result<(), io::Error> {
+    let root = opts
+        .chroot
+        .as_ref()
+        .map(|p| CString::new(p.as_os_str().as_bytes()))
+        .transpose()?;
+    #[cfg(all(target_os = "linux", feature = "namespaces"))]
+    let namespaces = opts.namespaces;
+    #[cfg(all(target_os = "linux", feature = "namespaces"))]
+    let has_namespaces = !namespaces.is_empty();
+    #[cfg(not(all(target_os = "linux", feature = "namespaces")))]
+    let has_namespaces = false;
+    if root.is_none() && !has_namespaces {
+        return Ok(());
+    }
+    // SAFETY: the closure calls async-signal-safe functions only and does not allocate
+    unsafe {
+        cmd.pre_exec(move || {
+            #[cfg(all(target_os = "linux", feature = "namespaces"))]
+            if has_namespaces && libc::unshare(namespaces.0) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            if let Some(ref root) = root {
+                if libc::chroot(root.as_ptr()) != 0 || libc::chdir(b"/\0".as_ptr().cast()) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        });
+    }
+    Ok(())
+}
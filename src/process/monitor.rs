@@ -0,0 +1,125 @@
+use super::get_child_pids_recursive;
+use async_channel::Receiver;
+use std::collections::HashSet;
+use std::time::{Duration, SystemTime};
+use sysinfo::{Pid, PidExt, ProcessExt, ProcessStatus, System, SystemExt};
+use tokio::task;
+
+/// Process resource usage sample
+#[derive(Debug, Clone)]
+pub struct ProcSample {
+    pub pid: u32,
+    pub time: SystemTime,
+    /// CPU usage percent since the previous sample, may exceed 100 on multi-core systems
+    pub cpu: f32,
+    /// Resident set size, bytes
+    pub rss: u64,
+    /// Open file descriptors, Linux only
+    pub fds: Option<usize>,
+    /// Threads, Linux only
+    pub threads: Option<usize>,
+    /// Number of processes aggregated (1 if the tree is not monitored)
+    pub processes: usize,
+}
+
+#[cfg(target_os = "linux")]
+fn count_proc_entries(pid: Pid, kind: &str) -> Option<usize> {
+    std::fs::read_dir(format!("/proc/{}/{}", pid.as_u32(), kind))
+        .ok()
+        .map(Iterator::count)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn count_proc_entries(_pid: Pid, _kind: &str) -> Option<usize> {
+    None
+}
+
+fn add_opt(a: Option<usize>, b: Option<usize>) -> Option<usize> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a + b),
+        (v, None) | (None, v) => v,
+    }
+}
+
+/// Returns None if the process is gone
+fn collect(sys: &mut System, pid: Pid, tree: bool) -> Option<ProcSample> {
+    let mut pids = HashSet::new();
+    if tree {
+        sys.refresh_processes();
+        get_child_pids_recursive(pid, sys, &mut pids);
+    } else if !sys.refresh_process(pid) {
+        return None;
+    }
+    if matches!(sys.process(pid)?.status(), ProcessStatus::Zombie) {
+        return None;
+    }
+    pids.insert(pid);
+    let mut sample = ProcSample {
+        pid: pid.as_u32(),
+        time: SystemTime::now(),
+        cpu: 0.0,
+        rss: 0,
+        fds: None,
+        threads: None,
+        processes: 0,
+    };
+    for p in pids {
+        if let Some(process) = sys.process(p) {
+            sample.cpu += process.cpu_usage();
+            sample.rss += process.memory();
+            sample.fds = add_opt(sample.fds, count_proc_entries(p, "fd"));
+            sample.threads = add_opt(sample.threads, count_proc_entries(p, "task"));
+            sample.processes += 1;
+        }
+    }
+    Some(sample)
+}
+
+fn monitor_impl(pid: u32, interval: Duration, tree: bool) -> Receiver<ProcSample> {
+    let (tx, rx) = async_channel::bounded(1);
+    task::spawn(async move {
+        let pid = Pid::from_u32(pid);
+        let mut sys = System::new();
+        // the first refresh is required to calculate CPU usage
+        let mut sys_opt = task::spawn_blocking(move || {
+            collect(&mut sys, pid, tree)?;
+            Some(sys)
+        })
+        .await
+        .ok()
+        .flatten();
+        let mut int = tokio::time::interval(interval);
+        int.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        int.tick().await;
+        while let Some(mut sys) = sys_opt.take() {
+            int.tick().await;
+            let Ok((sys, sample)) = task::spawn_blocking(move || {
+                let sample = collect(&mut sys, pid, tree);
+                (sys, sample)
+            })
+            .await
+            else {
+                break;
+            };
+            let Some(sample) = sample else {
+                break;
+            };
+            if tx.send(sample).await.is_err() {
+                break;
+            }
+            sys_opt.replace(sys);
+        }
+    });
+    rx
+}
+
+/// Samples resource usage of the process every interval. The channel is closed when the
+/// process is gone or becomes a zombie
+pub fn monitor(pid: u32, interval: Duration) -> Receiver<ProcSample> {
+    monitor_impl(pid, interval, false)
+}
+
+/// Same as `monitor` but aggregates resource usage of the process and all its descendants
+pub fn monitor_tree(pid: u32, interval: Duration) -> Receiver<ProcSample> {
+    monitor_impl(pid, interval, true)
+}
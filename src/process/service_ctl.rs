@@ -0,0 +1,312 @@
+use super::{command, CommandResult, Options};
+use crate::{tools, Error};
+use bmart_derive::EnumStr;
+use std::time::Duration;
+
+const ERR_RELOAD_UNSUPPORTED: &str = "reload is not supported by the service manager";
+const ERR_NO_SERVICE_MANAGER: &str = "no supported service manager found";
+
+#[derive(EnumStr, Debug, Copy, Clone, Eq, PartialEq)]
+#[enumstr(rename_all = "lowercase")]
+pub enum ServiceAction {
+    Start,
+    Stop,
+    Restart,
+    Reload,
+    Enable,
+    Disable,
+    /// Query the status only
+    Status,
+}
+
+#[derive(EnumStr, Debug, Copy, Clone, Eq, PartialEq)]
+#[enumstr(rename_all = "lowercase")]
+pub enum ActiveState {
+    Active,
+    Inactive,
+    Failed,
+    Activating,
+    Deactivating,
+    Unknown,
+}
+
+#[derive(EnumStr, Debug, Copy, Clone, Eq, PartialEq)]
+#[enumstr(rename_all = "lowercase")]
+pub enum ServiceManager {
+    Systemd,
+    OpenRc,
+    Windows,
+}
+
+impl ServiceManager {
+    /// Detects the service manager of the running system
+    pub fn detect() -> Option<Self> {
+        if cfg!(target_os = "windows") {
+            Some(ServiceManager::Windows)
+        } else if std::path::Path::new("/run/systemd/system").is_dir() {
+            Some(ServiceManager::Systemd)
+        } else if [
+            "/sbin/rc-service",
+            "/usr/sbin/rc-service",
+            "/bin/rc-service",
+        ]
+        .iter()
+        .any(|p| std::path::Path::new(p).exists())
+        {
+            Some(ServiceManager::OpenRc)
+        } else {
+            None
+        }
+    }
+}
+
+/// OS service status. `enabled` is `None` if the service manager does not report it (e.g.
+/// static systemd units)
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct ServiceStatus {
+    pub active: ActiveState,
+    pub enabled: Option<bool>,
+}
+
+impl ServiceStatus {
+    #[inline]
+    pub fn is_active(&self) -> bool {
+        self.active == ActiveState::Active
+    }
+}
+
+/// Performs the action on an OS service with the detected service manager (systemd, OpenRC or
+/// Windows SCM) and returns the service status after the action
+///
+/// # Errors
+///
+/// Will return `Err` if no service manager is detected, the service is not found, the action
+/// fails or the timeout is reached
+pub async fn service_ctl(
+    name: &str,
+    action: ServiceAction,
+    timeout: Duration,
+) -> Result<ServiceStatus, Error> {
+    let manager =
+        ServiceManager::detect().ok_or_else(|| Error::unsupported(ERR_NO_SERVICE_MANAGER))?;
+    service_ctl_with(manager, name, action, timeout).await
+}
+
+/// Same as [`service_ctl`] but with the specified service manager
+///
+/// # Errors
+///
+/// Will return `Err` if the service is not found, the action fails or the timeout is reached
+pub async fn service_ctl_with(
+    manager: ServiceManager,
+    name: &str,
+    action: ServiceAction,
+    timeout: Duration,
+) -> Result<ServiceStatus, Error> {
+    tools::timeout(timeout, async {
+        match manager {
+            ServiceManager::Systemd => systemd(name, action, timeout).await,
+            ServiceManager::OpenRc => openrc(name, action, timeout).await,
+            ServiceManager::Windows => windows(name, action, timeout).await,
+        }
+    })
+    .await?
+}
+
+async fn run(program: &str, args: &[&str], timeout: Duration) -> Result<CommandResult, Error> {
+    let result = command(program, args, timeout, Options::new()).await?;
+    if result.code.is_none() {
+        return Err(Error::timeout());
+    }
+    Ok(result)
+}
+
+fn failed(program: &str, result: &CommandResult) -> Error {
+    let msg = result
+        .err
+        .iter()
+        .chain(result.out.iter())
+        .map(|s| s.trim())
+        .find(|s| !s.is_empty())
+        .unwrap_or_default();
+    Error::internal(format!(
+        "{} exited with code {}: {}",
+        program,
+        result.code.unwrap_or(-1),
+        msg
+    ))
+}
+
+async fn run_ok(program: &str, args: &[&str], timeout: Duration) -> Result<(), Error> {
+    let result = run(program, args, timeout).await?;
+    if result.ok() {
+        Ok(())
+    } else {
+        Err(failed(program, &result))
+    }
+}
+
+const SYSTEMCTL: &str = "systemctl";
+
+async fn systemd_show(name: &str, timeout: Duration) -> Result<CommandResult, Error> {
+    run(
+        SYSTEMCTL,
+        &[
+            "show",
+            name,
+            "--no-pager",
+            "--property=LoadState",
+            "--property=ActiveState",
+            "--property=UnitFileState",
+        ],
+        timeout,
+    )
+    .await
+}
+
+async fn systemd(
+    name: &str,
+    action: ServiceAction,
+    timeout: Duration,
+) -> Result<ServiceStatus, Error> {
+    let result = systemd_show(name, timeout).await?;
+    if !result.ok() {
+        return Err(failed(SYSTEMCTL, &result));
+    }
+    if parse_systemd(&result.out).0 == Some("not-found") {
+        return Err(Error::not_found(format!("service {} not found", name)));
+    }
+    if action != ServiceAction::Status {
+        run_ok(SYSTEMCTL, &[action.as_str(), name], timeout).await?;
+    }
+    let result = systemd_show(name, timeout).await?;
+    let (_, active, enabled) = parse_systemd(&result.out);
+    Ok(ServiceStatus {
+        active: match active {
+            Some("active" | "reloading") => ActiveState::Active,
+            Some("inactive") => ActiveState::Inactive,
+            Some("failed") => ActiveState::Failed,
+            Some("activating") => ActiveState::Activating,
+            Some("deactivating") => ActiveState::Deactivating,
+            _ => ActiveState::Unknown,
+        },
+        enabled: match enabled {
+            Some(v) if v.starts_with("enabled") => Some(true),
+            Some("disabled" | "masked" | "masked-runtime") => Some(false),
+            _ => None,
+        },
+    })
+}
+
+fn parse_systemd(out: &[String]) -> (Option<&str>, Option<&str>, Option<&str>) {
+    let (mut load, mut active, mut enabled) = (None, None, None);
+    for line in out {
+        if let Some((k, v)) = line.trim().split_once('=') {
+            match k {
+                "LoadState" => load = Some(v),
+                "ActiveState" => active = Some(v),
+                "UnitFileState" => enabled = Some(v),
+                _ => {}
+            }
+        }
+    }
+    (load, active, enabled)
+}
+
+async fn openrc(
+    name: &str,
+    action: ServiceAction,
+    timeout: Duration,
+) -> Result<ServiceStatus, Error> {
+    const RC_SERVICE: &str = "rc-service";
+    const RC_UPDATE: &str = "rc-update";
+    let result = run(RC_SERVICE, &["--exists", name], timeout).await?;
+    if !result.ok() {
+        return Err(Error::not_found(format!("service {} not found", name)));
+    }
+    match action {
+        ServiceAction::Enable => run_ok(RC_UPDATE, &["add", name, "default"], timeout).await?,
+        ServiceAction::Disable => run_ok(RC_UPDATE, &["del", name, "default"], timeout).await?,
+        ServiceAction::Status => {}
+        _ => run_ok(RC_SERVICE, &[name, action.as_str()], timeout).await?,
+    }
+    // the exit code is non-zero for stopped services
+    let result = run(RC_SERVICE, &[name, "status"], timeout).await?;
+    let active = result
+        .out
+        .iter()
+        .find_map(|line| line.split_once("status:").map(|(_, v)| v.trim()))
+        .map_or(ActiveState::Unknown, |v| match v {
+            "started" => ActiveState::Active,
+            "stopped" => ActiveState::Inactive,
+            "crashed" => ActiveState::Failed,
+            "starting" => ActiveState::Activating,
+            "stopping" => ActiveState::Deactivating,
+            _ => ActiveState::Unknown,
+        });
+    let result = run(RC_UPDATE, &["show", "default"], timeout).await?;
+    let enabled = result.ok().then(|| {
+        result
+            .out
+            .iter()
+            .any(|line| line.split('|').next().map(str::trim) == Some(name))
+    });
+    Ok(ServiceStatus { active, enabled })
+}
+
+async fn windows(
+    name: &str,
+    action: ServiceAction,
+    timeout: Duration,
+) -> Result<ServiceStatus, Error> {
+    const SC: &str = "sc.exe";
+    // ERROR_SERVICE_DOES_NOT_EXIST
+    const NOT_EXIST: i32 = 1060;
+    let result = run(SC, &["qc", name], timeout).await?;
+    if result.code == Some(NOT_EXIST) {
+        return Err(Error::not_found(format!("service {} not found", name)));
+    }
+    if !result.ok() {
+        return Err(failed(SC, &result));
+    }
+    match action {
+        ServiceAction::Start => run_ok(SC, &["start", name], timeout).await?,
+        ServiceAction::Stop => run_ok(SC, &["stop", name], timeout).await?,
+        ServiceAction::Restart => {
+            // stop fails if the service is not running
+            let _r = run(SC, &["stop", name], timeout).await?;
+            run_ok(SC, &["start", name], timeout).await?;
+        }
+        ServiceAction::Reload => return Err(Error::unsupported(ERR_RELOAD_UNSUPPORTED)),
+        ServiceAction::Enable => run_ok(SC, &["config", name, "start=", "auto"], timeout).await?,
+        ServiceAction::Disable => {
+            run_ok(SC, &["config", name, "start=", "disabled"], timeout).await?;
+        }
+        ServiceAction::Status => {}
+    }
+    let result = run(SC, &["query", name], timeout).await?;
+    let active = sc_value(&result.out, "STATE").map_or(ActiveState::Unknown, |v| {
+        if v.contains("RUNNING") {
+            ActiveState::Active
+        } else if v.contains("STOPPED") {
+            ActiveState::Inactive
+        } else if v.contains("START_PENDING") {
+            ActiveState::Activating
+        } else if v.contains("STOP_PENDING") {
+            ActiveState::Deactivating
+        } else {
+            ActiveState::Unknown
+        }
+    });
+    let result = run(SC, &["qc", name], timeout).await?;
+    let enabled = sc_value(&result.out, "START_TYPE").map(|v| v.contains("AUTO_START"));
+    Ok(ServiceStatus { active, enabled })
+}
+
+fn sc_value<'a>(out: &'a [String], key: &str) -> Option<&'a str> {
+    out.iter().find_map(|line| {
+        line.split_once(':')
+            .filter(|(k, _)| k.trim() == key)
+            .map(|(_, v)| v.trim())
+    })
+}
@@ -0,0 +1,313 @@
+use crate::Error;
+#[cfg(target_os = "linux")]
+use nix::libc;
+use sha2::Digest;
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+#[cfg(target_os = "linux")]
+use std::os::unix::io::AsRawFd;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+use tokio::process::Command;
+
+const ERR_INVALID_DIGEST: &str = "invalid SHA-256 digest";
+
+/// SHA-256 digest of an executable
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Sha256([u8; 32]);
+
+impl Sha256 {
+    #[inline]
+    pub const fn new(digest: [u8; 32]) -> Self {
+        Self(digest)
+    }
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+    /// Calculates the digest of a file
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the file can not be read
+    pub fn file<P: AsRef<Path>>(path: P) -> Result<Self, io::Error> {
+        Self::read_from(std::fs::File::open(path)?)
+    }
+    fn read_from<R: Read>(mut reader: R) -> Result<Self, io::Error> {
+        let mut hasher = sha2::Sha256::new();
+        let mut buf = [0_u8; 8192];
+        loop {
+            let n = reader.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+        }
+        Ok(Self(hasher.finalize().into()))
+    }
+}
+
+impl FromStr for Sha256 {
+    type Err = Error;
+    /// Parses a hex string
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.len() != 64 || !s.is_ascii() {
+            return Err(Error::invalid_data(ERR_INVALID_DIGEST));
+        }
+        let mut digest = [0_u8; 32];
+        for (i, b) in digest.iter_mut().enumerate() {
+            *b = u8::from_str_radix(&s[i * 2..i * 2 + 2], 16)
+                .map_err(|_| Error::invalid_data(ERR_INVALID_DIGEST))?;
+        }
+        Ok(Self(digest))
+    }
+}
+
+impl fmt::Display for Sha256 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for b in self.0 {
+            write!(f, "{:02x}", b)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+const MAX_SYMLINKS: usize = 40;
+
+#[cfg(not(target_os = "windows"))]
+fn path_parts(path: &Path) -> Vec<std::ffi::OsString> {
+    path.components()
+        .rev()
+        .filter_map(|c| match c {
+            Component::Normal(s) => Some(s.to_owned()),
+            Component::ParentDir => Some("..".into()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Maps a path, seen by a process chrooted to the root directory, to the host path. Symbolic
+/// links are resolved relative to the root as well
+#[cfg(not(target_os = "windows"))]
+fn host_path(root: &Path, path: &Path) -> Result<PathBuf, io::Error> {
+    let mut parts = path_parts(path);
+    let mut resolved = PathBuf::new();
+    let mut links = 0;
+    while let Some(part) = parts.pop() {
+        if part == ".." {
+            resolved.pop();
+            continue;
+        }
+        let candidate = resolved.join(&part);
+        let host = root.join(&candidate);
+        if std::fs::symlink_metadata(&host).map_or(false, |m| m.file_type().is_symlink()) {
+            links += 1;
+            if links > MAX_SYMLINKS {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("too many symbolic links in {}", path.display()),
+                ));
+            }
+            let target = std::fs::read_link(&host)?;
+            if target.has_root() {
+                resolved.clear();
+            }
+            parts.extend(path_parts(&target));
+        } else {
+            resolved = candidate;
+        }
+    }
+    Ok(root.join(resolved))
+}
+
+/// Returns the path to spawn and the path of the file on the host
+fn resolve_program(
+    program: &OsStr,
+    root: Option<&Path>,
+) -> Result<Option<(PathBuf, PathBuf)>, io::Error> {
+    let to_host = |path: &Path| -> Result<PathBuf, io::Error> {
+        match root {
+            #[cfg(not(target_os = "windows"))]
+            Some(root) => host_path(root, path),
+            _ => Ok(path.to_owned()),
+        }
+    };
+    let path = Path::new(program);
+    if path.components().count() > 1 {
+        return Ok(Some((path.to_owned(), to_host(path)?)));
+    }
+    let Some(paths) = std::env::var_os("PATH") else {
+        return Ok(None);
+    };
+    for dir in std::env::split_paths(&paths) {
+        let candidate = dir.join(program);
+        let host = to_host(&candidate)?;
+        if host.is_file() {
+            return Ok(Some((candidate, host)));
+        }
+        if cfg!(target_os = "windows") {
+            let candidate = candidate.with_extension("exe");
+            let host = to_host(&candidate)?;
+            if host.is_file() {
+                return Ok(Some((candidate, host)));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Verified executable, must be kept until the process is spawned
+#[derive(Debug)]
+pub(super) struct VerifiedProgram {
+    path: PathBuf,
+    // the verified file, executed via /proc/self/fd, so the binary can not be replaced between
+    // the check and exec
+    #[cfg(target_os = "linux")]
+    file: Option<File>,
+}
+
+impl VerifiedProgram {
+    pub(super) fn command(&self) -> Command {
+        #[cfg(target_os = "linux")]
+        if let Some(ref file) = self.file {
+            let fd = file.as_raw_fd();
+            let mut cmd = Command::new(format!("/proc/self/fd/{}", fd));
+            cmd.arg0(&self.path);
+            // SAFETY: fcntl is async-signal-safe. The descriptor is inherited by the program,
+            // as interpreters of scripts open it by the path after exec
+            unsafe {
+                cmd.pre_exec(move || {
+                    if libc::fcntl(fd, libc::F_SETFD, 0) == -1 {
+                        return Err(io::Error::last_os_error());
+                    }
+                    Ok(())
+                });
+            }
+            return cmd;
+        }
+        Command::new(&self.path)
+    }
+}
+
+/// Resolves the program path and verifies its digest. If the root is set, the program is
+/// looked up and verified inside the root directory, as the process sees it after chroot.
+///
+/// On Linux (without chroot) the verified file is executed by its descriptor, otherwise by the
+/// path, so the binary may be replaced between the check and exec
+pub(super) fn verified_program(
+    program: &OsStr,
+    expected: &Sha256,
+    root: Option<&Path>,
+) -> Result<VerifiedProgram, io::Error> {
+    let (path, host) = resolve_program(program, root)?.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("program {} not found", program.to_string_lossy()),
+        )
+    })?;
+    let file = File::open(&host)?;
+    let digest = Sha256::read_from(&file)?;
+    if digest == *expected {
+        #[cfg(target_os = "linux")]
+        let file = (root.is_none() && Path::new("/proc/self/fd").is_dir()).then_some(file);
+        #[cfg(not(target_os = "linux"))]
+        drop(file);
+        Ok(VerifiedProgram {
+            path,
+            #[cfg(target_os = "linux")]
+            file,
+        })
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "checksum mismatch for {}: expected {}, got {}",
+                host.display(),
+                expected,
+                digest
+            ),
+        ))
+    }
+}
+
+#[cfg(all(test, not(target_os = "windows")))]
+mod test {
+    use super::{verified_program, Sha256};
+    use std::ffi::OsStr;
+    use std::path::{Path, PathBuf};
+
+    struct TempRoot(PathBuf);
+
+    impl TempRoot {
+        fn new(name: &str) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("bmart-verify-{}-{}", name, std::process::id()));
+            let _r = std::fs::remove_dir_all(&path);
+            std::fs::create_dir_all(path.join("usr/bin")).unwrap();
+            std::os::unix::fs::symlink("usr/bin", path.join("bin")).unwrap();
+            std::fs::write(path.join("usr/bin/prog"), b"inside").unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for TempRoot {
+        fn drop(&mut self) {
+            let _r = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_verify_in_root() {
+        let root = TempRoot::new("root");
+        let inside = Sha256::file(root.0.join("usr/bin/prog")).unwrap();
+        // the path is kept as seen inside the root, the file is hashed on the host
+        let verified = verified_program(OsStr::new("/bin/prog"), &inside, Some(&root.0)).unwrap();
+        assert_eq!(verified.path, Path::new("/bin/prog"));
+        // absolute symlinks are resolved inside the root
+        std::os::unix::fs::symlink("/usr/bin/prog", root.0.join("usr/bin/link")).unwrap();
+        verified_program(OsStr::new("/usr/bin/link"), &inside, Some(&root.0)).unwrap();
+        verified_program(OsStr::new("/../usr/bin/prog"), &inside, Some(&root.0)).unwrap();
+        // absolute symlinks are not followed to the host files
+        let host_path = root.0.join("host");
+        std::fs::write(&host_path, b"host").unwrap();
+        let host = Sha256::file(&host_path).unwrap();
+        std::fs::remove_file(root.0.join("usr/bin/prog")).unwrap();
+        std::os::unix::fs::symlink(&host_path, root.0.join("usr/bin/prog")).unwrap();
+        let err = verified_program(OsStr::new("/bin/prog"), &host, Some(&root.0)).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_verified_file_executed() {
+        use std::os::unix::fs::PermissionsExt;
+        let root = TempRoot::new("exec");
+        let script = |name: &str, text: &str| {
+            let path = root.0.join(name);
+            std::fs::write(&path, format!("#!/bin/sh\necho {}\n", text)).unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+            path
+        };
+        let path = script("prog.sh", "original");
+        let digest = Sha256::file(&path).unwrap();
+        let verified = verified_program(path.as_os_str(), &digest, None).unwrap();
+        // the binary is replaced after the check
+        std::fs::rename(script("replaced.sh", "replaced"), &path).unwrap();
+        let output = verified.command().output().await.unwrap();
+        assert_eq!(output.stdout, b"original\n");
+    }
+
+    #[test]
+    fn test_verify_mismatch() {
+        let root = TempRoot::new("mismatch");
+        let digest = Sha256::new([0; 32]);
+        let err = verified_program(OsStr::new("/bin/prog"), &digest, Some(&root.0)).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        let err = verified_program(OsStr::new("/bin/none"), &digest, Some(&root.0)).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,60 @@
+use super::SLEEP_STEP;
+use std::time::{Duration, Instant};
+use tokio::process::Command;
+use tokio::time::sleep;
+use winapi::shared::winerror::WAIT_TIMEOUT;
+use winapi::um::handleapi::CloseHandle;
+use winapi::um::processthreadsapi::{OpenProcess, TerminateProcess};
+use winapi::um::synchapi::WaitForSingleObject;
+use winapi::um::winbase::CREATE_NEW_PROCESS_GROUP;
+use winapi::um::wincon::{GenerateConsoleCtrlEvent, CTRL_BREAK_EVENT};
+use winapi::um::winnt::{PROCESS_QUERY_INFORMATION, PROCESS_TERMINATE, SYNCHRONIZE};
+
+/// Starts the child in its own process group, so it can receive Ctrl-Break without affecting
+/// the parent
+pub(super) fn new_process_group(cmd: &mut Command) {
+    cmd.creation_flags(CREATE_NEW_PROCESS_GROUP);
+}
+
+/// Sends Ctrl-Break to the process group, the process must be the group leader (started with
+/// `CREATE_NEW_PROCESS_GROUP`). Returns false if the event can not be delivered
+pub(super) fn ctrl_break(pid: u32) -> bool {
+    unsafe { GenerateConsoleCtrlEvent(CTRL_BREAK_EVENT, pid) != 0 }
+}
+
+pub(super) fn is_running(pid: u32) -> bool {
+    let handle = unsafe { OpenProcess(SYNCHRONIZE, 0, pid) };
+    if handle.is_null() {
+        return false;
+    }
+    let running = unsafe { WaitForSingleObject(handle, 0) } == WAIT_TIMEOUT;
+    unsafe { CloseHandle(handle) };
+    running
+}
+
+pub(super) fn kill(pid: u32) {
+    let handle = unsafe { OpenProcess(PROCESS_QUERY_INFORMATION | PROCESS_TERMINATE, 0, pid) };
+    if !handle.is_null() {
+        unsafe {
+            TerminateProcess(handle, 1);
+            CloseHandle(handle);
+        }
+    }
+}
+
+/// Windows analog of `kill_pstree`: if tki is set, sends Ctrl-Break to the process group and
+/// waits up to tki for the process to exit, then terminates it
+pub async fn terminate_process(pid: u32, tki: Option<Duration>) {
+    if let Some(t) = tki {
+        if ctrl_break(pid) {
+            let now = Instant::now();
+            while now.elapsed() < t {
+                sleep(SLEEP_STEP).await;
+                if !is_running(pid) {
+                    return;
+                }
+            }
+        }
+    }
+    kill(pid);
+}
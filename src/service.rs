@@ -0,0 +1,95 @@
+use crate::Error;
+use bmart_derive::EnumStr;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::watch;
+
+/// Service lifecycle state
+#[derive(EnumStr, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum ServiceState {
+    Starting,
+    Active,
+    Degraded,
+    Stopping,
+    Stopped,
+}
+
+impl ServiceState {
+    #[inline]
+    pub fn is_shutting_down(self) -> bool {
+        self == ServiceState::Stopping || self == ServiceState::Stopped
+    }
+}
+
+/// Service state holder, components can observe the state changes. Clones share the same state
+#[derive(Debug, Clone)]
+pub struct ServiceStateHolder {
+    tx: Arc<watch::Sender<ServiceState>>,
+}
+
+impl Default for ServiceStateHolder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ServiceStateHolder {
+    /// Creates a new holder with `Starting` state
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            tx: Arc::new(watch::Sender::new(ServiceState::Starting)),
+        }
+    }
+    /// Sets the new state, returns the previous one
+    pub fn set(&self, state: ServiceState) -> ServiceState {
+        self.tx.send_replace(state)
+    }
+    pub fn get(&self) -> ServiceState {
+        *self.tx.borrow()
+    }
+    #[inline]
+    pub fn is_active(&self) -> bool {
+        self.get() == ServiceState::Active
+    }
+    pub fn subscribe(&self) -> watch::Receiver<ServiceState> {
+        self.tx.subscribe()
+    }
+    /// Waits until the service gets the specified state
+    pub async fn wait_for(&self, state: ServiceState) {
+        let mut rx = self.subscribe();
+        // the sender is held by self, the channel can not be closed
+        let _r = rx.wait_for(|s| *s == state).await;
+    }
+    /// Waits until the service is active
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the service is stopping or stopped
+    pub async fn wait_active(&self) -> Result<(), Error> {
+        let mut rx = self.subscribe();
+        let state = *rx
+            .wait_for(|s| *s == ServiceState::Active || s.is_shutting_down())
+            .await
+            .map_err(Error::internal)?;
+        if state == ServiceState::Active {
+            Ok(())
+        } else {
+            Err(Error::shutdown())
+        }
+    }
+    /// Waits until the service is active and runs the future. The future is cancelled if the
+    /// service is stopping
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the service is stopping or stopped
+    pub async fn run_when_active<F: Future>(&self, fut: F) -> Result<F::Output, Error> {
+        self.wait_active().await?;
+        let mut rx = self.subscribe();
+        tokio::select! {
+            res = fut => Ok(res),
+            _ = rx.wait_for(|s| s.is_shutting_down()) => Err(Error::shutdown()),
+        }
+    }
+}
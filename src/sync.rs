@@ -1,13 +1,17 @@
 use crate::Error;
 use std::collections::{btree_map, BTreeMap};
+use std::future::Future;
 use std::sync::atomic;
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::{mpsc, Mutex};
+use tokio::sync::{mpsc, watch, Mutex, Notify};
 use tokio::task;
+use tokio::time::Instant;
 use uuid::Uuid;
 
 const ERR_LOCK_NOT_DEFINED: &str = "Lock not defined";
+
+const MIN_RENEW_INTERVAL: Duration = Duration::from_millis(1);
 const ERR_INVALID_LOCK_TOKEN: &str = "Invalid lock token";
 
 #[derive(Debug, Clone)]
@@ -26,6 +30,8 @@ impl Lock {
 pub struct SharedLock {
     lock: Arc<Mutex<()>>,
     flag: Arc<atomic::AtomicBool>,
+    // notified when the flag is changed
+    changed: Arc<Notify>,
 }
 
 impl SharedLock {
@@ -38,23 +44,40 @@ impl SharedLock {
         let (lock_trigger, lock_listener) = triggered::trigger();
         let (unlock_trigger, mut unlock_listener) = mpsc::channel(1);
         let flag = self.flag.clone();
+        let changed = self.changed.clone();
+        #[cfg(feature = "tracing")]
+        let started = std::time::Instant::now();
         task::spawn(async move {
             // guard moved here
             let _g = lock.lock().await;
             // triggered as soon as the lock is acquired
             flag.store(true, atomic::Ordering::SeqCst);
+            changed.notify_waiters();
             lock_trigger.trigger();
             // exited as soon as unlocked or expired or unlock_trigger dropped
-            let _ = tokio::time::timeout(expires, unlock_listener.recv()).await;
+            if tokio::time::timeout(expires, unlock_listener.recv())
+                .await
+                .is_err()
+            {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(?expires, "shared lock expired");
+            }
             flag.store(false, atomic::Ordering::SeqCst);
+            changed.notify_waiters();
         });
         // want lock to be acquired
         lock_listener.await;
+        #[cfg(feature = "tracing")]
+        tracing::debug!(waited = ?started.elapsed(), ?expires, "shared lock acquired");
         Lock { unlock_trigger }
     }
     pub fn clone_flag(&self) -> Arc<atomic::AtomicBool> {
         self.flag.clone()
     }
+    #[cfg(feature = "testing")]
+    pub(crate) fn clone_changed(&self) -> Arc<Notify> {
+        self.changed.clone()
+    }
 }
 
 #[derive(Debug, Default)]
@@ -90,7 +113,13 @@ impl SharedLockFactory {
     pub async fn acquire(&self, lock_id: &str, expires: Duration) -> Result<Uuid, Error> {
         if let Some((v, _)) = self.shared_locks.get(lock_id) {
             // wait for the lock and block other futures accessing it
-            let lock = v.lock().await.acquire(expires).await;
+            let acquire = async { v.lock().await.acquire(expires).await };
+            #[cfg(feature = "tracing")]
+            let acquire = tracing::Instrument::instrument(
+                acquire,
+                tracing::debug_span!("shared_lock_acquire", lock_id),
+            );
+            let lock = acquire.await;
             let token = Uuid::new_v4();
             self.locks
                 .lock()
@@ -134,3 +163,781 @@ impl SharedLockFactory {
         result
     }
 }
+
+const ERR_RESERVATION_NOT_FOUND: &str = "Reservation not found or expired";
+const ERR_INVALID_RESERVATION_TOKEN: &str = "Invalid reservation token";
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ReservationState {
+    /// reserved but not confirmed yet, auto-expires unless confirmed
+    Reserved,
+    /// confirmed, the owner holds the resource
+    Confirmed,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReservationInfo {
+    pub owner: String,
+    pub state: ReservationState,
+    /// time left before the reservation expires, None for confirmed ones with no expiration
+    pub expires_in: Option<Duration>,
+}
+
+#[derive(Debug)]
+struct ReservationEntry {
+    owner: String,
+    token: Uuid,
+    state: ReservationState,
+    expires: Option<std::time::Instant>,
+}
+
+impl ReservationEntry {
+    #[inline]
+    fn is_expired(&self, now: std::time::Instant) -> bool {
+        self.expires.map_or(false, |e| e <= now)
+    }
+    fn info(&self, now: std::time::Instant) -> ReservationInfo {
+        ReservationInfo {
+            owner: self.owner.clone(),
+            state: self.state,
+            expires_in: self.expires.map(|e| e.saturating_duration_since(now)),
+        }
+    }
+}
+
+/// Two-phase exclusive ownership of named resources
+///
+/// A resource is reserved first with `reserve`, the returned token must be confirmed with
+/// `confirm` before the reservation TTL passes, otherwise the reservation expires and the
+/// resource becomes free again. Other parties can query the current owner with `reserved_by`.
+///
+/// Expired entries are purged lazily, no background tasks are spawned.
+#[derive(Debug, Default)]
+pub struct Reservations {
+    entries: std::sync::Mutex<BTreeMap<String, ReservationEntry>>,
+}
+
+impl Reservations {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Reserves the resource for the owner, the reservation must be confirmed within ttl
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the resource is already reserved or held by someone else
+    ///
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn reserve(&self, id: &str, owner: &str, ttl: Duration) -> Result<Uuid, Error> {
+        let now = std::time::Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        if let Some(entry) = entries.get(id) {
+            if !entry.is_expired(now) {
+                return Err(Error::busy(format!(
+                    "Resource {} is {} by {}",
+                    id,
+                    match entry.state {
+                        ReservationState::Reserved => "reserved",
+                        ReservationState::Confirmed => "held",
+                    },
+                    entry.owner
+                )));
+            }
+        }
+        let token = Uuid::new_v4();
+        entries.insert(
+            id.to_owned(),
+            ReservationEntry {
+                owner: owner.to_owned(),
+                token,
+                state: ReservationState::Reserved,
+                expires: Some(now + ttl),
+            },
+        );
+        #[cfg(feature = "tracing")]
+        tracing::debug!(id, owner, ?ttl, "resource reserved");
+        Ok(token)
+    }
+    /// Confirms the reservation, turning it into the real lock. If expires is None, the lock is
+    /// held until released
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the reservation is not found, expired or the token is invalid
+    ///
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn confirm(&self, id: &str, token: &Uuid, expires: Option<Duration>) -> Result<(), Error> {
+        let now = std::time::Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get_mut(id) {
+            Some(entry) if !entry.is_expired(now) => {
+                if entry.token != *token {
+                    return Err(Error::access_denied(ERR_INVALID_RESERVATION_TOKEN));
+                }
+                entry.state = ReservationState::Confirmed;
+                entry.expires = expires.map(|e| now + e);
+                #[cfg(feature = "tracing")]
+                tracing::debug!(id, owner = entry.owner, "reservation confirmed");
+                Ok(())
+            }
+            _ => Err(Error::not_found(ERR_RESERVATION_NOT_FOUND)),
+        }
+    }
+    /// Releases the reservation or the confirmed lock, None forcibly releases it
+    ///
+    /// Returns true if released, false if the entry has already expired
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the reservation is not found or the token is invalid
+    ///
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn release(&self, id: &str, token: Option<&Uuid>) -> Result<bool, Error> {
+        let mut entries = self.entries.lock().unwrap();
+        if let Some(entry) = entries.get(id) {
+            if let Some(t) = token {
+                if entry.token != *t {
+                    return Err(Error::access_denied(ERR_INVALID_RESERVATION_TOKEN));
+                }
+            }
+            let expired = entry.is_expired(std::time::Instant::now());
+            entries.remove(id);
+            Ok(!expired)
+        } else {
+            Err(Error::not_found(ERR_RESERVATION_NOT_FOUND))
+        }
+    }
+    /// Returns the current owner of the resource, None if free
+    ///
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn reserved_by(&self, id: &str) -> Option<ReservationInfo> {
+        let now = std::time::Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(id) {
+            Some(entry) if entry.is_expired(now) => {
+                entries.remove(id);
+                None
+            }
+            Some(entry) => Some(entry.info(now)),
+            None => None,
+        }
+    }
+    /// Lists active reservations and confirmed locks, purging expired ones
+    ///
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn list(&self) -> Vec<(String, ReservationInfo)> {
+        let now = std::time::Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, entry| !entry.is_expired(now));
+        entries
+            .iter()
+            .map(|(id, entry)| (id.clone(), entry.info(now)))
+            .collect()
+    }
+}
+
+/// Pluggable backend for named, owner-bound locks with TTL
+///
+/// Acquiring a lock already held by the same owner must extend its TTL, so backends can be
+/// used for lease renewal. The crate provides the in-process implementation for
+/// [`Reservations`], distributed backends (databases, coordination services) can be plugged in
+/// by implementing this trait.
+///
+/// Implementations may use `async fn` directly, the returned futures must be `Send`
+pub trait LockBackend: Send + Sync {
+    /// Acquires or renews the lock, returns false if held by another owner
+    fn try_acquire(
+        &self,
+        name: &str,
+        owner: &str,
+        ttl: Duration,
+    ) -> impl Future<Output = Result<bool, Error>> + Send;
+    /// Releases the lock if held by the owner
+    fn release(&self, name: &str, owner: &str) -> impl Future<Output = Result<(), Error>> + Send;
+}
+
+impl LockBackend for Reservations {
+    async fn try_acquire(&self, name: &str, owner: &str, ttl: Duration) -> Result<bool, Error> {
+        let now = std::time::Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get_mut(name) {
+            Some(entry) if !entry.is_expired(now) && entry.owner != owner => Ok(false),
+            Some(entry) if !entry.is_expired(now) => {
+                entry.state = ReservationState::Confirmed;
+                entry.expires = Some(now + ttl);
+                Ok(true)
+            }
+            _ => {
+                entries.insert(
+                    name.to_owned(),
+                    ReservationEntry {
+                        owner: owner.to_owned(),
+                        token: Uuid::new_v4(),
+                        state: ReservationState::Confirmed,
+                        expires: Some(now + ttl),
+                    },
+                );
+                Ok(true)
+            }
+        }
+    }
+    async fn release(&self, name: &str, owner: &str) -> Result<(), Error> {
+        let mut entries = self.entries.lock().unwrap();
+        if entries
+            .get(name)
+            .map_or(false, |entry| entry.owner == owner)
+        {
+            entries.remove(name);
+        }
+        Ok(())
+    }
+}
+
+/// Leader election over a [`LockBackend`]
+///
+/// The elector continuously tries to hold the named lock, renewing it every renew interval
+/// (ttl / 3 by default, at least 1 ms and at most ttl / 2). Leadership is lost as soon as a
+/// renewal fails, the lock is taken by another owner or the TTL of the last successful renewal
+/// passes, so a stalled backend can not keep two leaders at once.
+///
+/// ```rust,no_run
+/// use bmart::sync::{LeaderElector, Reservations};
+/// use std::sync::Arc;
+/// use std::time::Duration;
+///
+/// # async fn f() {
+/// let backend = Arc::new(Reservations::new());
+/// let elector = Arc::new(LeaderElector::new(backend, "controller", "node1", Duration::from_secs(3)));
+/// let mut changes = elector.subscribe();
+/// let e = elector.clone();
+/// tokio::spawn(async move { e.run().await });
+/// while changes.changed().await.is_ok() {
+///     println!("leader: {}", *changes.borrow());
+/// }
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct LeaderElector<B: LockBackend> {
+    backend: Arc<B>,
+    name: String,
+    owner: String,
+    ttl: Duration,
+    renew_interval: Duration,
+    tx: watch::Sender<bool>,
+}
+
+impl<B: LockBackend> LeaderElector<B> {
+    pub fn new(backend: Arc<B>, name: &str, owner: &str, ttl: Duration) -> Self {
+        let (tx, _) = watch::channel(false);
+        Self {
+            backend,
+            name: name.to_owned(),
+            owner: owner.to_owned(),
+            ttl,
+            renew_interval: (ttl / 3).max(MIN_RENEW_INTERVAL),
+            tx,
+        }
+    }
+    /// Values above ttl / 2 are clamped, values below 1 ms are rounded up
+    #[inline]
+    #[must_use]
+    pub fn renew_interval(mut self, renew_interval: Duration) -> Self {
+        self.renew_interval = renew_interval.min(self.ttl / 2).max(MIN_RENEW_INTERVAL);
+        self
+    }
+    #[inline]
+    pub fn is_leader(&self) -> bool {
+        *self.tx.borrow()
+    }
+    /// Subscribes to leadership changes
+    #[inline]
+    pub fn subscribe(&self) -> watch::Receiver<bool> {
+        self.tx.subscribe()
+    }
+    fn set_leader(&self, leader: bool) {
+        let changed = self.tx.send_if_modified(|v| {
+            if *v == leader {
+                false
+            } else {
+                *v = leader;
+                true
+            }
+        });
+        if changed {
+            if leader {
+                log::info!("{}: leadership acquired by {}", self.name, self.owner);
+            } else {
+                log::warn!("{}: leadership lost by {}", self.name, self.owner);
+            }
+        }
+    }
+    /// Runs the election loop forever, must be spawned as a task
+    pub async fn run(&self) {
+        let mut int = tokio::time::interval(self.renew_interval);
+        int.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        // the lock is held at least until the TTL of the last successful attempt passes
+        let mut lease_deadline: Option<Instant> = None;
+        loop {
+            if let Some(deadline) = lease_deadline {
+                tokio::select! {
+                    _ = int.tick() => {}
+                    () = tokio::time::sleep_until(deadline) => {
+                        log::error!("{}: leadership lease expired", self.name);
+                        lease_deadline = None;
+                        self.set_leader(false);
+                        continue;
+                    }
+                }
+            } else {
+                int.tick().await;
+            }
+            let started = Instant::now();
+            let timeout = lease_deadline.map_or(self.renew_interval, |deadline| {
+                self.renew_interval
+                    .min(deadline.saturating_duration_since(started))
+            });
+            let leader = match tokio::time::timeout(
+                timeout,
+                self.backend.try_acquire(&self.name, &self.owner, self.ttl),
+            )
+            .await
+            {
+                Ok(Ok(v)) => v,
+                Ok(Err(e)) => {
+                    log::error!("{}: lock backend error: {}", self.name, e);
+                    false
+                }
+                Err(_) => {
+                    log::error!("{}: lock backend timeout", self.name);
+                    false
+                }
+            };
+            lease_deadline = if leader {
+                Some(started + self.ttl)
+            } else {
+                None
+            };
+            self.set_leader(leader);
+        }
+    }
+    /// Gives up leadership, the election loop must be stopped before, otherwise it is
+    /// re-acquired on the next renewal
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the backend fails to release the lock
+    pub async fn resign(&self) -> Result<(), Error> {
+        let was_leader = self.is_leader();
+        self.set_leader(false);
+        if was_leader {
+            self.backend.release(&self.name, &self.owner).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Immutable registry snapshot
+pub type RegistrySnapshot<K, V> = Arc<BTreeMap<K, Arc<V>>>;
+
+/// Read-mostly concurrent map
+///
+/// Readers access an immutable map snapshot without locking, writers are serialized and
+/// replace the snapshot with a modified copy. Suitable for registries which are looked up on
+/// hot paths and modified rarely, writes cost O(n).
+#[derive(Debug)]
+pub struct Registry<K, V> {
+    map: arc_swap::ArcSwap<BTreeMap<K, Arc<V>>>,
+    writer: std::sync::Mutex<()>,
+}
+
+impl<K, V> Default for Registry<K, V> {
+    fn default() -> Self {
+        Self {
+            map: arc_swap::ArcSwap::from_pointee(BTreeMap::new()),
+            writer: <_>::default(),
+        }
+    }
+}
+
+impl<K, V> Registry<K, V>
+where
+    K: Ord + Clone,
+{
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+    #[inline]
+    pub fn get<Q>(&self, key: &Q) -> Option<Arc<V>>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.map.load().get(key).cloned()
+    }
+    #[inline]
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.map.load().contains_key(key)
+    }
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.map.load().len()
+    }
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.map.load().is_empty()
+    }
+    /// Returns the current snapshot, which is not affected by further modifications
+    #[inline]
+    pub fn iter_snapshot(&self) -> RegistrySnapshot<K, V> {
+        self.map.load_full()
+    }
+    /// Returns the previous value if exists
+    pub fn insert(&self, key: K, value: V) -> Option<Arc<V>> {
+        self.modify(|map| map.insert(key, Arc::new(value)))
+    }
+    pub fn remove<Q>(&self, key: &Q) -> Option<Arc<V>>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        if !self.contains_key(key) {
+            return None;
+        }
+        self.modify(|map| map.remove(key))
+    }
+    /// Modifies the registry in a single write, readers see either the previous or the
+    /// resulting map
+    ///
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn modify<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut BTreeMap<K, Arc<V>>) -> R,
+    {
+        let _w = self.writer.lock().unwrap();
+        let mut map = BTreeMap::clone(&self.map.load());
+        let result = f(&mut map);
+        self.map.store(Arc::new(map));
+        result
+    }
+}
+
+#[derive(Debug, Default)]
+struct CancelScopeInner {
+    canceled: atomic::AtomicBool,
+    notify: Notify,
+    // keeps intermediate scopes alive, so cancellation reaches descendants of dropped scopes
+    parent: Option<Arc<CancelScopeInner>>,
+    children: std::sync::Mutex<Vec<std::sync::Weak<CancelScopeInner>>>,
+}
+
+impl CancelScopeInner {
+    fn is_canceled(&self) -> bool {
+        self.canceled.load(atomic::Ordering::SeqCst)
+            || self.parent.as_ref().map_or(false, |p| p.is_canceled())
+    }
+    fn cancel(&self) {
+        if self.canceled.swap(true, atomic::Ordering::SeqCst) {
+            return;
+        }
+        self.notify.notify_waiters();
+        let children = std::mem::take(&mut *self.children.lock().unwrap());
+        for child in children {
+            if let Some(child) = child.upgrade() {
+                child.cancel();
+            }
+        }
+    }
+}
+
+/// Hierarchical cancellation scope
+///
+/// Cancelling a scope cancels all its children (and their children), cancelling a child does
+/// not affect the parent. Scopes are cheap to clone, clones share the same state.
+///
+/// ```rust
+/// use bmart::sync::CancelScope;
+/// use bmart::ErrorKind;
+///
+/// # #[tokio::main] async fn main() {
+/// let root = CancelScope::new();
+/// let child = root.child();
+/// let task = tokio::spawn(async move {
+///     child.run(std::future::pending::<()>()).await
+/// });
+/// root.cancel();
+/// assert_eq!(task.await.unwrap().unwrap_err().kind, ErrorKind::Canceled);
+///
+/// // descendants are canceled even if intermediate scopes are dropped
+/// let root = CancelScope::new();
+/// let gc = root.child().child();
+/// root.cancel();
+/// assert!(gc.is_canceled());
+/// gc.canceled().await;
+/// # }
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct CancelScope {
+    inner: Arc<CancelScopeInner>,
+}
+
+impl CancelScope {
+    /// Creates a new root scope
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Creates a child scope, canceled immediately if the parent is already canceled
+    ///
+    /// # Panics
+    ///
+    /// Should not panic
+    #[must_use]
+    pub fn child(&self) -> Self {
+        let child = Self {
+            inner: Arc::new(CancelScopeInner {
+                parent: Some(self.inner.clone()),
+                ..CancelScopeInner::default()
+            }),
+        };
+        let mut children = self.inner.children.lock().unwrap();
+        if self.is_canceled() {
+            child.cancel();
+        } else {
+            children.retain(|c| c.strong_count() > 0);
+            children.push(Arc::downgrade(&child.inner));
+        }
+        child
+    }
+    /// Cancels the scope and all its children
+    pub fn cancel(&self) {
+        #[cfg(feature = "tracing")]
+        tracing::debug!("cancel scope canceled");
+        self.inner.cancel();
+    }
+    #[inline]
+    pub fn is_canceled(&self) -> bool {
+        self.inner.is_canceled()
+    }
+    /// Completes when the scope is canceled
+    pub async fn canceled(&self) {
+        let notified = self.inner.notify.notified();
+        tokio::pin!(notified);
+        notified.as_mut().enable();
+        if self.is_canceled() {
+            return;
+        }
+        notified.await;
+    }
+    /// Runs the future until it completes or the scope is canceled
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` with `ErrorKind::Canceled` if the scope is canceled before the future
+    /// completes
+    pub async fn run<F, T>(&self, fut: F) -> Result<T, Error>
+    where
+        F: std::future::Future<Output = T>,
+    {
+        tokio::select! {
+            biased;
+            () = self.canceled() => Err(Error::canceled()),
+            v = fut => Ok(v),
+        }
+    }
+}
+
+/// Sequence behavior when the maximum value is reached
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum WrapPolicy {
+    /// restart from the initial value
+    #[default]
+    Wrap,
+    /// keep returning the maximum value
+    Saturate,
+    /// return an error
+    Fail,
+}
+
+const ERR_SEQ_EXHAUSTED: &str = "Sequence exhausted";
+
+/// Atomic monotonically increasing id generator
+///
+/// ```rust
+/// use bmart::sync::{Seq, WrapPolicy};
+///
+/// let seq = Seq::new(1).max(3, WrapPolicy::Wrap);
+/// let ids: Vec<u64> = (0..4).map(|_| seq.next().unwrap()).collect();
+/// assert_eq!(ids, [1, 2, 3, 1]);
+///
+/// let seq = Seq::new(u64::MAX - 1).max(u64::MAX, WrapPolicy::Fail);
+/// assert_eq!(seq.next().unwrap(), u64::MAX - 1);
+/// assert!(seq.next().is_err());
+/// ```
+#[derive(Debug)]
+pub struct Seq {
+    value: atomic::AtomicU64,
+    start: u64,
+    max: u64,
+    policy: WrapPolicy,
+}
+
+impl Default for Seq {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl Seq {
+    /// Creates a new sequence, the first value returned is start
+    ///
+    /// # Panics
+    ///
+    /// Will panic if start is `u64::MAX`
+    #[must_use]
+    pub fn new(start: u64) -> Self {
+        assert!(
+            start < u64::MAX,
+            "sequence start must be less than u64::MAX"
+        );
+        Self {
+            value: atomic::AtomicU64::new(start),
+            start,
+            max: u64::MAX - 1,
+            policy: WrapPolicy::Wrap,
+        }
+    }
+    /// Sets the maximum value (inclusive, capped at `u64::MAX - 1`) and the wrap policy
+    ///
+    /// # Panics
+    ///
+    /// Will panic if max is less than the sequence start
+    #[inline]
+    #[must_use]
+    pub fn max(mut self, max: u64, policy: WrapPolicy) -> Self {
+        assert!(
+            max >= self.start,
+            "sequence max must not be less than start"
+        );
+        self.max = max.min(u64::MAX - 1);
+        self.policy = policy;
+        self
+    }
+    /// Returns the next value
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the maximum value is reached and the policy is `WrapPolicy::Fail`
+    pub fn next(&self) -> Result<u64, Error> {
+        let mut result = None;
+        let _r = self
+            .value
+            .fetch_update(atomic::Ordering::SeqCst, atomic::Ordering::SeqCst, |v| {
+                // start <= max < u64::MAX, the increments never overflow
+                if v <= self.max {
+                    result = Some(v);
+                    Some(v.wrapping_add(1))
+                } else {
+                    match self.policy {
+                        WrapPolicy::Wrap => {
+                            result = Some(self.start);
+                            Some(self.start.wrapping_add(1))
+                        }
+                        WrapPolicy::Saturate => {
+                            result = Some(self.max);
+                            None
+                        }
+                        WrapPolicy::Fail => {
+                            result = None;
+                            None
+                        }
+                    }
+                }
+            });
+        result.ok_or_else(|| Error::busy(ERR_SEQ_EXHAUSTED))
+    }
+    /// Returns the last issued value, None if no values have been issued yet
+    #[inline]
+    pub fn last(&self) -> Option<u64> {
+        let v = self.value.load(atomic::Ordering::SeqCst);
+        if v == self.start {
+            None
+        } else {
+            Some((v - 1).min(self.max))
+        }
+    }
+}
+
+/// Configuration epoch
+///
+/// Bumped on configuration reloads, workers keep the epoch they have started with and check
+/// it with `is_current` to detect they operate on stale configuration.
+///
+/// ```rust
+/// use bmart::sync::Epoch;
+///
+/// let epoch = Epoch::new();
+/// let e = epoch.current();
+/// assert!(epoch.is_current(e));
+/// epoch.bump();
+/// assert!(!epoch.is_current(e));
+/// ```
+#[derive(Debug, Default)]
+pub struct Epoch {
+    value: atomic::AtomicU64,
+    changed: Notify,
+}
+
+impl Epoch {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+    #[inline]
+    pub fn current(&self) -> u64 {
+        self.value.load(atomic::Ordering::SeqCst)
+    }
+    #[inline]
+    pub fn is_current(&self, epoch: u64) -> bool {
+        self.current() == epoch
+    }
+    /// Starts a new epoch and returns its value
+    pub fn bump(&self) -> u64 {
+        let epoch = self
+            .value
+            .fetch_add(1, atomic::Ordering::SeqCst)
+            .wrapping_add(1);
+        self.changed.notify_waiters();
+        epoch
+    }
+    /// Completes when the epoch differs from the given one, returns the current epoch
+    pub async fn changed(&self, epoch: u64) -> u64 {
+        loop {
+            let notified = self.changed.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+            let current = self.current();
+            if current != epoch {
+                return current;
+            }
+            notified.await;
+        }
+    }
+}
@@ -0,0 +1,240 @@
+//! Deterministic helpers for testing code built on bmart primitives
+//!
+//! The helpers are deterministic on a current-thread runtime (`#[tokio::test]` default).
+use crate::sync::SharedLock;
+use crate::tools::BoxFuture;
+use crate::workers::Clock;
+use crate::Error;
+use std::sync::atomic;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+/// Manually advanced clock for `Scheduler`
+///
+/// Time moves only when `advance` or `set` is called, sleepers are woken as soon as the clock
+/// reaches their deadlines.
+#[derive(Debug)]
+pub struct ManualClock {
+    now: Mutex<Instant>,
+    changed: Notify,
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ManualClock {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::starting_at(Instant::now())
+    }
+    #[must_use]
+    pub fn starting_at(t: Instant) -> Self {
+        Self {
+            now: Mutex::new(t),
+            changed: Notify::new(),
+        }
+    }
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn advance(&self, d: Duration) {
+        *self.now.lock().unwrap() += d;
+        self.changed.notify_waiters();
+    }
+    /// Sets the clock to the specified point, moving backwards is ignored
+    ///
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn set(&self, t: Instant) {
+        {
+            let mut now = self.now.lock().unwrap();
+            if t <= *now {
+                return;
+            }
+            *now = t;
+        }
+        self.changed.notify_waiters();
+    }
+    /// Advances the clock step by step, yielding to the runtime after each step so woken tasks
+    /// can process ticks
+    pub async fn advance_by_steps(&self, d: Duration, step: Duration) {
+        let mut passed = Duration::ZERO;
+        while passed < d {
+            let s = step.min(d - passed);
+            self.advance(s);
+            passed += s;
+            settle().await;
+        }
+    }
+}
+
+impl Clock for ManualClock {
+    /// # Panics
+    ///
+    /// Should not panic
+    fn now(&self) -> Instant {
+        *self.now.lock().unwrap()
+    }
+    fn sleep_until(&self, deadline: Instant) -> BoxFuture<'_, ()> {
+        Box::pin(async move {
+            loop {
+                let notified = self.changed.notified();
+                tokio::pin!(notified);
+                notified.as_mut().enable();
+                if self.now() >= deadline {
+                    break;
+                }
+                notified.await;
+            }
+        })
+    }
+}
+
+/// Yields to the runtime several times, letting spawned tasks to process pending events
+pub async fn settle() {
+    for _ in 0..16 {
+        tokio::task::yield_now().await;
+    }
+}
+
+/// Counts `Notify` firings
+#[derive(Debug)]
+pub struct TriggerProbe {
+    count: Arc<atomic::AtomicUsize>,
+    changed: Arc<Notify>,
+    fut: JoinHandle<()>,
+}
+
+impl TriggerProbe {
+    /// Starts listening to the trigger. Both `notify_waiters` and `notify_one` firings are
+    /// counted, note that the probe competes with other `notify_one` consumers
+    pub fn new(trigger: Arc<Notify>) -> Self {
+        let count: Arc<atomic::AtomicUsize> = <_>::default();
+        let changed: Arc<Notify> = <_>::default();
+        let c = count.clone();
+        let ch = changed.clone();
+        let fut = tokio::spawn(async move {
+            let mut notified = Box::pin(trigger.notified());
+            notified.as_mut().enable();
+            loop {
+                notified.await;
+                notified = Box::pin(trigger.notified());
+                notified.as_mut().enable();
+                c.fetch_add(1, atomic::Ordering::SeqCst);
+                ch.notify_waiters();
+            }
+        });
+        Self {
+            count,
+            changed,
+            fut,
+        }
+    }
+    #[inline]
+    pub fn count(&self) -> usize {
+        self.count.load(atomic::Ordering::SeqCst)
+    }
+    #[inline]
+    pub fn reset(&self) {
+        self.count.store(0, atomic::Ordering::SeqCst);
+    }
+    /// Waits until the trigger is fired at least `n` times
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` on timeout
+    pub async fn wait_for(&self, n: usize, timeout: Duration) -> Result<(), Error> {
+        crate::tools::timeout(timeout, async {
+            loop {
+                let notified = self.changed.notified();
+                tokio::pin!(notified);
+                notified.as_mut().enable();
+                if self.count() >= n {
+                    break;
+                }
+                notified.await;
+            }
+        })
+        .await
+    }
+}
+
+impl Drop for TriggerProbe {
+    fn drop(&mut self) {
+        self.fut.abort();
+    }
+}
+
+/// `SharedLock` state assertions
+#[derive(Debug, Clone)]
+pub struct LockProbe {
+    flag: Arc<atomic::AtomicBool>,
+    changed: Arc<Notify>,
+}
+
+impl LockProbe {
+    pub fn new(lock: &SharedLock) -> Self {
+        Self {
+            flag: lock.clone_flag(),
+            changed: lock.clone_changed(),
+        }
+    }
+    #[inline]
+    pub fn is_locked(&self) -> bool {
+        self.flag.load(atomic::Ordering::SeqCst)
+    }
+    /// # Panics
+    ///
+    /// Will panic if the lock is not acquired
+    #[track_caller]
+    pub fn assert_locked(&self) {
+        assert!(self.is_locked(), "the lock is expected to be acquired");
+    }
+    /// # Panics
+    ///
+    /// Will panic if the lock is acquired
+    #[track_caller]
+    pub fn assert_unlocked(&self) {
+        assert!(!self.is_locked(), "the lock is expected to be released");
+    }
+    /// Waits until the lock is in the expected state
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` on timeout
+    pub async fn wait_state(&self, locked: bool, timeout: Duration) -> Result<(), Error> {
+        crate::tools::timeout(timeout, async {
+            loop {
+                let notified = self.changed.notified();
+                tokio::pin!(notified);
+                notified.as_mut().enable();
+                if self.is_locked() == locked {
+                    break;
+                }
+                notified.await;
+            }
+        })
+        .await
+    }
+    /// # Errors
+    ///
+    /// Will return `Err` on timeout
+    #[inline]
+    pub async fn wait_locked(&self, timeout: Duration) -> Result<(), Error> {
+        self.wait_state(true, timeout).await
+    }
+    /// # Errors
+    ///
+    /// Will return `Err` on timeout
+    #[inline]
+    pub async fn wait_unlocked(&self, timeout: Duration) -> Result<(), Error> {
+        self.wait_state(false, timeout).await
+    }
+}
@@ -1,2 +1,150 @@
+use crate::{Error, ErrorKind, ResultExt};
+use std::future::Future;
+use std::time::Duration;
+use uuid::Uuid;
+
+mod cache;
+pub mod case;
+mod clock;
+mod fs;
+mod lines;
+mod pool;
+mod signals;
+pub mod stats;
+pub mod system;
+mod units;
+mod watch;
+
+pub use cache::TtlCache;
+pub use clock::{monotonic, monotonic_ms, monotonic_us, now_ts, Uptime};
+pub use fs::{atomic_write, atomic_write_async};
+pub use lines::{framed_lines, framed_lines_writer, LineReader, LineWriter};
+pub use pool::TaskPool;
+pub use signals::{signals, Sig};
+pub use units::{format_duration, format_size, parse_duration, parse_size};
+pub use watch::{watch_and_reload, watch_file, FileChange};
+
 pub use bmart_derive::EnumStr;
+pub use bmart_derive::EnumTag;
 pub use bmart_derive::Sorting;
+pub use bmart_derive::StructDisplay;
+
+/// Boxed future for object-safe traits. Traits used with generics declare async methods
+/// natively (`async fn` or `-> impl Future`), no `async_trait` is required
+///
+/// Native async trait methods require Rust 1.75 (the crate `rust-version`), there is no
+/// fallback for older compilers: a boxed variant would change the method signatures, so trait
+/// implementations would compile on one side of the gate only. Older compilers are rejected by
+/// cargo with the MSRV error instead
+pub type BoxFuture<'a, T> = std::pin::Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Runs the future with a timeout
+///
+/// # Errors
+///
+/// Will return `Err` if timeout occured
+pub async fn timeout<F: Future>(timeout: Duration, fut: F) -> Result<F::Output, Error> {
+    tokio::time::timeout(timeout, fut).await.map_err(Into::into)
+}
+
+pub trait FutureTimeoutExt: Future + Sized {
+    /// Runs the future with a timeout, the output is wrapped in `Result`, `Err` is returned if
+    /// timeout occured
+    fn with_timeout(self, t: Duration) -> impl Future<Output = Result<Self::Output, Error>> {
+        timeout(t, self)
+    }
+}
+
+impl<F: Future> FutureTimeoutExt for F {}
+
+#[derive(Debug, Clone)]
+pub enum Backoff {
+    FixedDelay(Duration),
+    /// The delay starts with the base and is doubled after each attempt, up to the max. With
+    /// jitter, the actual delay is randomized between 50% and 100% of the calculated one
+    Exponential {
+        base: Duration,
+        max: Duration,
+        jitter: bool,
+    },
+}
+
+impl Backoff {
+    fn delay(&self, attempt: u32) -> Duration {
+        match self {
+            Backoff::FixedDelay(delay) => *delay,
+            Backoff::Exponential { base, max, jitter } => {
+                let delay = base
+                    .checked_mul(2_u32.saturating_pow(attempt))
+                    .map_or(*max, |d| d.min(*max));
+                if *jitter {
+                    #[allow(clippy::cast_possible_truncation)]
+                    let half = (delay.as_nanos() / 2).min(u128::from(u64::MAX)) as u64;
+                    #[allow(clippy::cast_possible_truncation)]
+                    let offset = (Uuid::new_v4().as_u128() as u64) % (half + 1);
+                    delay - Duration::from_nanos(offset)
+                } else {
+                    delay
+                }
+            }
+        }
+    }
+}
+
+/// Retry policy for `retry`
+#[derive(Debug, Clone)]
+pub struct Retry {
+    backoff: Backoff,
+    max_attempts: u32,
+    retryable: Option<Vec<ErrorKind>>,
+}
+
+impl Retry {
+    /// max_attempts includes the initial one
+    #[inline]
+    pub fn new(backoff: Backoff, max_attempts: u32) -> Self {
+        Self {
+            backoff,
+            max_attempts,
+            retryable: None,
+        }
+    }
+    /// Retry only errors of the specified kinds (default: retry all errors)
+    #[inline]
+    #[must_use]
+    pub fn retry_on(mut self, kinds: &[ErrorKind]) -> Self {
+        self.retryable = Some(kinds.to_vec());
+        self
+    }
+    fn is_retryable(&self, kind: ErrorKind) -> bool {
+        self.retryable.as_ref().map_or(true, |k| k.contains(&kind))
+    }
+}
+
+/// Calls the function until it succeeds, the max attempts are reached or a non-retryable error
+/// is returned
+///
+/// # Errors
+///
+/// Will return the last error, with the number of attempts in the message, the original error is
+/// set as the source
+pub async fn retry<F, Fut, T>(policy: &Retry, mut func: F) -> Result<T, Error>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, Error>>,
+{
+    let mut attempt: u32 = 0;
+    loop {
+        attempt += 1;
+        match func().await {
+            Ok(v) => return Ok(v),
+            Err(e) => {
+                if attempt >= policy.max_attempts || !policy.is_retryable(e.kind) {
+                    let message = format!("failed after {} attempt(s): {}", attempt, e);
+                    return Err(e).context(message);
+                }
+            }
+        }
+        tokio::time::sleep(policy.backoff.delay(attempt - 1)).await;
+    }
+}
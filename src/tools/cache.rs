@@ -0,0 +1,336 @@
+use crate::workers::{OverlapPolicy, WorkerFactory};
+use crate::Error;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+struct CacheEntry<V> {
+    value: V,
+    expires: Instant,
+    tick: u64,
+}
+
+struct CacheInner<K, V> {
+    entries: HashMap<K, CacheEntry<V>>,
+    // access tick -> key, the first one is the least recently used
+    lru: BTreeMap<u64, K>,
+    tick: u64,
+}
+
+impl<K, V> CacheInner<K, V>
+where
+    K: Eq + Hash + Clone,
+{
+    fn touch(&mut self, key: &K) {
+        self.tick += 1;
+        if let Some(entry) = self.entries.get_mut(key) {
+            self.lru.remove(&entry.tick);
+            entry.tick = self.tick;
+            self.lru.insert(self.tick, key.clone());
+        }
+    }
+    fn remove(&mut self, key: &K) -> Option<V> {
+        let entry = self.entries.remove(key)?;
+        self.lru.remove(&entry.tick);
+        Some(entry.value)
+    }
+}
+
+/// Cache with per-entry TTL and LRU eviction. The cache is shared between clones
+pub struct TtlCache<K, V> {
+    inner: Arc<Mutex<CacheInner<K, V>>>,
+    inflight: Arc<Mutex<HashMap<K, Arc<tokio::sync::Mutex<()>>>>>,
+    capacity: usize,
+    ttl: Duration,
+}
+
+impl<K, V> Clone for TtlCache<K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            inflight: self.inflight.clone(),
+            capacity: self.capacity,
+            ttl: self.ttl,
+        }
+    }
+}
+
+impl<K, V> TtlCache<K, V>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+{
+    /// Creates a new cache with the max capacity and the default entry TTL
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(CacheInner {
+                entries: HashMap::new(),
+                lru: BTreeMap::new(),
+                tick: 0,
+            })),
+            inflight: <_>::default(),
+            capacity,
+            ttl,
+        }
+    }
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn get(&self, key: &K) -> Option<V> {
+        let mut inner = self.inner.lock().unwrap();
+        let expires = inner.entries.get(key)?.expires;
+        if expires <= Instant::now() {
+            inner.remove(key);
+            return None;
+        }
+        inner.touch(key);
+        inner.entries.get(key).map(|e| e.value.clone())
+    }
+    #[inline]
+    pub fn insert(&self, key: K, value: V) {
+        self.insert_with_ttl(key, value, self.ttl);
+    }
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn insert_with_ttl(&self, key: K, value: V, ttl: Duration) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut inner = self.inner.lock().unwrap();
+        inner.remove(&key);
+        while inner.entries.len() >= self.capacity {
+            let Some((_, lru_key)) = inner.lru.pop_first() else {
+                break;
+            };
+            inner.entries.remove(&lru_key);
+        }
+        inner.tick += 1;
+        let tick = inner.tick;
+        inner.lru.insert(tick, key.clone());
+        inner.entries.insert(
+            key,
+            CacheEntry {
+                value,
+                expires: Instant::now() + ttl,
+                tick,
+            },
+        );
+    }
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn remove(&self, key: &K) -> Option<V> {
+        self.inner.lock().unwrap().remove(key)
+    }
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn clear(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.entries.clear();
+        inner.lru.clear();
+    }
+    /// Number of entries, including expired but not purged yet
+    ///
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().entries.len()
+    }
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Removes expired entries, returns the number of entries removed
+    ///
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn purge_expired(&self) -> usize {
+        let mut inner = self.inner.lock().unwrap();
+        let now = Instant::now();
+        let expired: Vec<K> = inner
+            .entries
+            .iter()
+            .filter(|(_, e)| e.expires <= now)
+            .map(|(k, _)| k.clone())
+            .collect();
+        for key in &expired {
+            inner.remove(key);
+        }
+        expired.len()
+    }
+    /// Gets the value from the cache or fetches it with the function. Concurrent calls for the
+    /// same key are deduplicated: the function is called once, other callers wait for its result
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the function has returned an error, which is not cached
+    ///
+    /// # Panics
+    ///
+    /// Should not panic
+    pub async fn get_or_fetch<F, Fut, E>(&self, key: K, func: F) -> Result<V, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<V, E>>,
+    {
+        if let Some(value) = self.get(&key) {
+            return Ok(value);
+        }
+        let flight = self
+            .inflight
+            .lock()
+            .unwrap()
+            .entry(key.clone())
+            .or_default()
+            .clone();
+        let result = {
+            let _g = flight.lock().await;
+            if let Some(value) = self.get(&key) {
+                Ok(value)
+            } else {
+                let result = func().await;
+                if let Ok(ref value) = result {
+                    self.insert(key.clone(), value.clone());
+                }
+                result
+            }
+        };
+        let mut inflight = self.inflight.lock().unwrap();
+        // the map and this call are the last holders
+        if Arc::strong_count(&flight) == 2 {
+            inflight.remove(&key);
+        }
+        result
+    }
+}
+
+impl<K, V> TtlCache<K, V>
+where
+    K: Eq + Hash + Clone + Send + 'static,
+    V: Clone + Send + 'static,
+{
+    /// Spawns a periodic worker which purges expired entries
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the worker with such id already exists
+    pub fn spawn_sweeper(
+        &self,
+        factory: &mut WorkerFactory,
+        worker_id: &str,
+        interval: Duration,
+    ) -> Result<(), Error> {
+        let cache = self.clone();
+        factory.spawn_periodic(worker_id, interval, OverlapPolicy::Skip, move || {
+            let cache = cache.clone();
+            async move {
+                cache.purge_expired();
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::TtlCache;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    const TTL: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn test_lru_eviction() {
+        let cache = TtlCache::new(2, TTL);
+        cache.insert(1, "a");
+        cache.insert(2, "b");
+        // 1 becomes the most recently used
+        assert_eq!(cache.get(&1), Some("a"));
+        cache.insert(3, "c");
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&2), None);
+        assert_eq!(cache.get(&1), Some("a"));
+        assert_eq!(cache.get(&3), Some("c"));
+    }
+
+    #[test]
+    fn test_replace_does_not_evict() {
+        let cache = TtlCache::new(2, TTL);
+        cache.insert(1, "a");
+        cache.insert(2, "b");
+        cache.insert(1, "x");
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&1), Some("x"));
+        assert_eq!(cache.get(&2), Some("b"));
+    }
+
+    #[test]
+    fn test_zero_capacity() {
+        let cache = TtlCache::new(0, TTL);
+        cache.insert(1, "a");
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_expiration() {
+        let cache = TtlCache::new(10, TTL);
+        cache.insert_with_ttl(1, "a", Duration::ZERO);
+        cache.insert_with_ttl(2, "b", Duration::ZERO);
+        cache.insert(3, "c");
+        assert_eq!(cache.get(&1), None);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.purge_expired(), 1);
+        assert_eq!(cache.get(&3), Some("c"));
+        // expired entries do not occupy the LRU queue
+        let inner = cache.inner.lock().unwrap();
+        assert_eq!(inner.lru.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_single_flight() {
+        let cache: TtlCache<u32, u32> = TtlCache::new(10, TTL);
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut tasks = Vec::new();
+        for _ in 0..10 {
+            let cache = cache.clone();
+            let calls = calls.clone();
+            tasks.push(tokio::spawn(async move {
+                cache
+                    .get_or_fetch(1, || async move {
+                        calls.fetch_add(1, Ordering::SeqCst);
+                        tokio::time::sleep(Duration::from_millis(50)).await;
+                        Ok::<_, ()>(42)
+                    })
+                    .await
+            }));
+        }
+        for task in tasks {
+            assert_eq!(task.await.unwrap(), Ok(42));
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(cache.inflight.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_error_not_cached() {
+        let cache: TtlCache<u32, u32> = TtlCache::new(10, TTL);
+        assert_eq!(
+            cache.get_or_fetch(1, || async { Err("failed") }).await,
+            Err("failed")
+        );
+        assert!(cache.is_empty());
+        assert_eq!(
+            cache.get_or_fetch(1, || async { Ok::<_, ()>(1) }).await,
+            Ok(1)
+        );
+        assert_eq!(cache.get(&1), Some(1));
+        assert!(cache.inflight.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,59 @@
+use bmart_derive::EnumStr;
+
+/// Identifier case, the rules are the same as used by `EnumStr` derive
+#[derive(EnumStr, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Case {
+    #[enumstr(rename = "lowercase")]
+    Lower,
+    #[enumstr(rename = "UPPERCASE")]
+    Upper,
+    #[enumstr(rename = "snake_case")]
+    Snake,
+    #[enumstr(rename = "SCREAMING_SNAKE_CASE")]
+    ScreamingSnake,
+    #[enumstr(rename = "kebab-case")]
+    Kebab,
+    #[enumstr(rename = "SCREAMING-KEBAB-CASE")]
+    ScreamingKebab,
+    /// as-is
+    #[enumstr(rename = "CamelCase")]
+    Camel,
+}
+
+/// Converts CamelCase string to the specified case
+pub fn convert(s: &str, case: Case) -> String {
+    match case {
+        Case::Camel => s.to_owned(),
+        Case::Lower => s.to_lowercase(),
+        Case::Upper => s.to_uppercase(),
+        Case::Snake | Case::ScreamingSnake | Case::Kebab | Case::ScreamingKebab => {
+            let sep = if case == Case::Snake || case == Case::ScreamingSnake {
+                "_"
+            } else {
+                "-"
+            };
+            let mut result = String::new();
+            for c in s.chars() {
+                if c.is_uppercase() && !result.is_empty() {
+                    result += sep;
+                }
+                result.push(c);
+            }
+            if case == Case::Snake || case == Case::Kebab {
+                result.to_lowercase()
+            } else {
+                result.to_uppercase()
+            }
+        }
+    }
+}
+
+#[inline]
+pub fn to_snake(s: &str) -> String {
+    convert(s, Case::Snake)
+}
+
+#[inline]
+pub fn to_kebab(s: &str) -> String {
+    convert(s, Case::Kebab)
+}
@@ -0,0 +1,70 @@
+use std::sync::OnceLock;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Uptime tracker
+#[derive(Debug, Copy, Clone)]
+pub struct Uptime {
+    started: Instant,
+    started_at: SystemTime,
+}
+
+impl Default for Uptime {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+static PROCESS_UPTIME: OnceLock<Uptime> = OnceLock::new();
+
+impl Uptime {
+    /// Starts tracking uptime from now
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            started_at: SystemTime::now(),
+        }
+    }
+    /// Process-wide uptime, started on the first call (call it early in main to capture the
+    /// process start)
+    pub fn process() -> &'static Uptime {
+        PROCESS_UPTIME.get_or_init(Uptime::new)
+    }
+    #[inline]
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+    #[inline]
+    pub fn started_at(&self) -> SystemTime {
+        self.started_at
+    }
+}
+
+/// Monotonic clock value (system-wide on Unix)
+pub fn monotonic() -> Duration {
+    #[cfg(not(target_os = "windows"))]
+    {
+        use nix::time::{clock_gettime, ClockId};
+        if let Ok(t) = clock_gettime(ClockId::CLOCK_MONOTONIC) {
+            return Duration::from(t);
+        }
+    }
+    Uptime::process().elapsed()
+}
+
+#[allow(clippy::cast_possible_truncation)]
+pub fn monotonic_ms() -> u64 {
+    monotonic().as_millis() as u64
+}
+
+#[allow(clippy::cast_possible_truncation)]
+pub fn monotonic_us() -> u64 {
+    monotonic().as_micros() as u64
+}
+
+/// Current UNIX timestamp as float seconds
+pub fn now_ts() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0.0, |d| d.as_secs_f64())
+}
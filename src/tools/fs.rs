@@ -0,0 +1,64 @@
+use crate::Error;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use uuid::Uuid;
+
+/// Writes the file atomically: the data is written to a temporary file in the same directory,
+/// synced and renamed. With sync_dir, the parent directory is synced as well (Unix only), so the
+/// rename survives power loss
+///
+/// # Errors
+///
+/// Will return `Err` on I/O errors
+pub fn atomic_write<P: AsRef<Path>>(path: P, data: &[u8], sync_dir: bool) -> Result<(), Error> {
+    let path = path.as_ref();
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| Error::invalid_data(format!("invalid file path: {}", path.display())))?;
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let tmp_path = dir.join(format!(
+        ".{}.{}.tmp",
+        file_name.to_string_lossy(),
+        Uuid::new_v4().to_simple()
+    ));
+    let write_tmp = || -> Result<(), Error> {
+        let mut f = File::create(&tmp_path)?;
+        f.write_all(data)?;
+        f.sync_all()?;
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    };
+    if let Err(e) = write_tmp() {
+        let _r = fs::remove_file(&tmp_path);
+        return Err(e);
+    }
+    #[cfg(not(target_os = "windows"))]
+    if sync_dir {
+        let dir = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        };
+        File::open(dir)?.sync_all()?;
+    }
+    #[cfg(target_os = "windows")]
+    let _ = sync_dir;
+    Ok(())
+}
+
+/// Async version of `atomic_write`, the data is written in a blocking thread
+///
+/// # Errors
+///
+/// Will return `Err` on I/O errors
+pub async fn atomic_write_async<P: AsRef<Path>>(
+    path: P,
+    data: Vec<u8>,
+    sync_dir: bool,
+) -> Result<(), Error> {
+    let path = path.as_ref().to_owned();
+    tokio::task::spawn_blocking(move || atomic_write(path, &data, sync_dir))
+        .await
+        .map_err(Error::internal)?
+}
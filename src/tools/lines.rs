@@ -0,0 +1,168 @@
+use crate::{Error, Result};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+
+const ERR_LINE_TOO_LONG: &str = "line is too long";
+const ERR_LINE_NEWLINE: &str = "line contains a newline character";
+
+/// Creates a line reader which enforces the maximum line length
+///
+/// Lines longer than `max_len` bytes (terminator excluded) are discarded and reported as
+/// `ErrorKind::InvalidData`, the reader stays usable and continues with the next line.
+pub fn framed_lines<R: AsyncRead + Unpin>(reader: R, max_len: usize) -> LineReader<BufReader<R>> {
+    LineReader::new(BufReader::new(reader), max_len)
+}
+
+/// Creates a line writer which enforces the maximum line length
+pub fn framed_lines_writer<W: AsyncWrite + Unpin>(writer: W, max_len: usize) -> LineWriter<W> {
+    LineWriter::new(writer, max_len)
+}
+
+pub struct LineReader<R> {
+    reader: R,
+    max_len: usize,
+    crlf: bool,
+    keep_newline: bool,
+    discarding: bool,
+    buf: Vec<u8>,
+}
+
+impl<R: AsyncBufRead + Unpin> LineReader<R> {
+    pub fn new(reader: R, max_len: usize) -> Self {
+        Self {
+            reader,
+            max_len,
+            crlf: false,
+            keep_newline: false,
+            discarding: false,
+            buf: Vec::new(),
+        }
+    }
+    /// Strip trailing CR from lines (ignored if newlines are kept)
+    #[inline]
+    #[must_use]
+    pub fn crlf(mut self, crlf: bool) -> Self {
+        self.crlf = crlf;
+        self
+    }
+    /// Keep line terminators in the output
+    #[inline]
+    #[must_use]
+    pub fn keep_newline(mut self, keep: bool) -> Self {
+        self.keep_newline = keep;
+        self
+    }
+    /// Reads the next line, `None` is returned on EOF
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` on I/O errors, if the line is too long or is not valid UTF-8
+    pub async fn next_line(&mut self) -> Result<Option<String>> {
+        loop {
+            let available = self.reader.fill_buf().await?;
+            if available.is_empty() {
+                self.discarding = false;
+                if self.buf.is_empty() {
+                    return Ok(None);
+                }
+                return self.take_line().map(Some);
+            }
+            let (chunk, complete) = match available.iter().position(|&b| b == b'\n') {
+                Some(pos) => (&available[..=pos], true),
+                None => (available, false),
+            };
+            let consumed = chunk.len();
+            if self.discarding {
+                self.reader.consume(consumed);
+                if complete {
+                    self.discarding = false;
+                }
+                continue;
+            }
+            self.buf.extend_from_slice(chunk);
+            self.reader.consume(consumed);
+            if self.content_len() > self.max_len {
+                self.buf.clear();
+                self.discarding = !complete;
+                return Err(Error::invalid_data(ERR_LINE_TOO_LONG));
+            }
+            if complete {
+                return self.take_line().map(Some);
+            }
+        }
+    }
+    #[inline]
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+    fn content_len(&self) -> usize {
+        let mut len = self.buf.len();
+        if self.buf.last() == Some(&b'\n') {
+            len -= 1;
+            if self.buf.get(len.wrapping_sub(1)) == Some(&b'\r') {
+                len -= 1;
+            }
+        }
+        len
+    }
+    fn take_line(&mut self) -> Result<String> {
+        let mut data = std::mem::take(&mut self.buf);
+        if !self.keep_newline && data.last() == Some(&b'\n') {
+            data.pop();
+            if self.crlf && data.last() == Some(&b'\r') {
+                data.pop();
+            }
+        }
+        String::from_utf8(data).map_err(Error::invalid_data)
+    }
+}
+
+pub struct LineWriter<W> {
+    writer: W,
+    max_len: usize,
+    crlf: bool,
+}
+
+impl<W: AsyncWrite + Unpin> LineWriter<W> {
+    pub fn new(writer: W, max_len: usize) -> Self {
+        Self {
+            writer,
+            max_len,
+            crlf: false,
+        }
+    }
+    /// Terminate lines with CRLF
+    #[inline]
+    #[must_use]
+    pub fn crlf(mut self, crlf: bool) -> Self {
+        self.crlf = crlf;
+        self
+    }
+    /// Writes a line, the terminator is appended automatically
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` on I/O errors, if the line is too long or contains a newline
+    pub async fn write_line(&mut self, line: &str) -> Result<()> {
+        if line.len() > self.max_len {
+            return Err(Error::invalid_data(ERR_LINE_TOO_LONG));
+        }
+        if line.contains('\n') {
+            return Err(Error::invalid_data(ERR_LINE_NEWLINE));
+        }
+        self.writer.write_all(line.as_bytes()).await?;
+        self.writer
+            .write_all(if self.crlf { b"\r\n" } else { b"\n" })
+            .await?;
+        Ok(())
+    }
+    /// # Errors
+    ///
+    /// Will return `Err` on I/O errors
+    pub async fn flush(&mut self) -> Result<()> {
+        self.writer.flush().await.map_err(Into::into)
+    }
+    #[inline]
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
@@ -0,0 +1,102 @@
+use crate::{Error, ErrorKind, Result};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tokio::sync::Semaphore;
+use tokio::task::{AbortHandle, JoinSet};
+
+const ERR_POOL_BUSY: &str = "task pool is busy";
+const ERR_POOL_CLOSED: &str = "task pool is closed";
+
+/// Bounded task spawner
+///
+/// At most `max_tasks` tasks are running at once. All tasks still running are aborted when the
+/// pool is dropped.
+pub struct TaskPool {
+    max_tasks: usize,
+    semaphore: Arc<Semaphore>,
+    tasks: Mutex<JoinSet<()>>,
+}
+
+impl TaskPool {
+    /// # Panics
+    ///
+    /// Will panic if `max_tasks` is zero
+    pub fn new(max_tasks: usize) -> Self {
+        assert!(max_tasks > 0, "max_tasks must be greater than zero");
+        Self {
+            max_tasks,
+            semaphore: Arc::new(Semaphore::new(max_tasks)),
+            tasks: <_>::default(),
+        }
+    }
+    /// Spawns a task, waits until a slot is free
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the pool is closed
+    pub async fn spawn<F>(&self, fut: F) -> Result<AbortHandle>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let permit = self
+            .semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|_| Error::new(ErrorKind::Canceled, ERR_POOL_CLOSED))?;
+        Ok(self.spawn_with(permit, fut))
+    }
+    /// Spawns a task, fails fast if there are no free slots
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` with `ErrorKind::Busy` if all slots are taken
+    pub fn try_spawn<F>(&self, fut: F) -> Result<AbortHandle>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let permit = self
+            .semaphore
+            .clone()
+            .try_acquire_owned()
+            .map_err(|_| Error::busy(ERR_POOL_BUSY))?;
+        Ok(self.spawn_with(permit, fut))
+    }
+    fn spawn_with<F>(&self, permit: tokio::sync::OwnedSemaphorePermit, fut: F) -> AbortHandle
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let mut tasks = self.tasks.lock().unwrap();
+        while tasks.try_join_next().is_some() {}
+        tasks.spawn(async move {
+            let _permit = permit;
+            fut.await;
+        })
+    }
+    /// Number of tasks currently running
+    #[inline]
+    pub fn active(&self) -> usize {
+        self.max_tasks - self.semaphore.available_permits()
+    }
+    #[inline]
+    pub fn max_tasks(&self) -> usize {
+        self.max_tasks
+    }
+    /// Aborts all running tasks
+    ///
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn abort_all(&self) {
+        self.tasks.lock().unwrap().abort_all();
+    }
+}
+
+impl Drop for TaskPool {
+    fn drop(&mut self) {
+        self.semaphore.close();
+        if let Ok(mut tasks) = self.tasks.lock() {
+            tasks.abort_all();
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use crate::Error;
+use bmart_derive::EnumStr;
+use tokio::sync::mpsc;
+
+/// Process signals, on Windows only Int (Ctrl-C) and Break (Ctrl-Break) are supported
+#[derive(EnumStr, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[enumstr(rename_all = "UPPERCASE")]
+pub enum Sig {
+    Hup,
+    Int,
+    Quit,
+    Term,
+    Usr1,
+    Usr2,
+    Break,
+}
+
+/// Installs handlers for the requested signals and delivers them to the returned channel
+///
+/// # Errors
+///
+/// Will return `Err` if a signal is not supported on the platform or the handler can not be
+/// installed
+#[cfg(not(target_os = "windows"))]
+pub fn signals(kinds: &[Sig], buf: usize) -> Result<mpsc::Receiver<Sig>, Error> {
+    use tokio::signal::unix::{signal, SignalKind};
+    let (tx, rx) = mpsc::channel(buf);
+    for kind in kinds {
+        let sig = *kind;
+        let signal_kind = match sig {
+            Sig::Hup => SignalKind::hangup(),
+            Sig::Int => SignalKind::interrupt(),
+            Sig::Quit => SignalKind::quit(),
+            Sig::Term => SignalKind::terminate(),
+            Sig::Usr1 => SignalKind::user_defined1(),
+            Sig::Usr2 => SignalKind::user_defined2(),
+            Sig::Break => return Err(Error::unsupported(sig)),
+        };
+        let mut stream = signal(signal_kind)?;
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            while stream.recv().await.is_some() {
+                if tx.send(sig).await.is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    Ok(rx)
+}
+
+/// Installs handlers for the requested signals and delivers them to the returned channel
+///
+/// # Errors
+///
+/// Will return `Err` if a signal is not supported on the platform or the handler can not be
+/// installed
+#[cfg(target_os = "windows")]
+pub fn signals(kinds: &[Sig], buf: usize) -> Result<mpsc::Receiver<Sig>, Error> {
+    use tokio::signal::windows::{ctrl_break, ctrl_c};
+    let (tx, rx) = mpsc::channel(buf);
+    for kind in kinds {
+        let sig = *kind;
+        match sig {
+            Sig::Int => {
+                let mut stream = ctrl_c()?;
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    while stream.recv().await.is_some() {
+                        if tx.send(sig).await.is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+            Sig::Break => {
+                let mut stream = ctrl_break()?;
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    while stream.recv().await.is_some() {
+                        if tx.send(sig).await.is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+            _ => return Err(Error::unsupported(sig)),
+        }
+    }
+    Ok(rx)
+}
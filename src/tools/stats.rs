@@ -0,0 +1,206 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Exponential moving average, lock-free
+#[derive(Debug)]
+pub struct Ewma {
+    alpha: f64,
+    // f64 bits, NaN if there are no samples yet
+    value: AtomicU64,
+}
+
+impl Ewma {
+    /// alpha is the weight of new samples, 0.0..=1.0
+    pub fn new(alpha: f64) -> Self {
+        Self {
+            alpha: alpha.clamp(0.0, 1.0),
+            value: AtomicU64::new(f64::NAN.to_bits()),
+        }
+    }
+    pub fn update(&self, sample: f64) {
+        let _r = self
+            .value
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |bits| {
+                let current = f64::from_bits(bits);
+                let value = if current.is_nan() {
+                    sample
+                } else {
+                    current + self.alpha * (sample - current)
+                };
+                Some(value.to_bits())
+            });
+    }
+    /// Returns `None` if there are no samples yet
+    pub fn value(&self) -> Option<f64> {
+        let value = f64::from_bits(self.value.load(Ordering::SeqCst));
+        if value.is_nan() {
+            None
+        } else {
+            Some(value)
+        }
+    }
+    pub fn reset(&self) {
+        self.value.store(f64::NAN.to_bits(), Ordering::SeqCst);
+    }
+}
+
+#[derive(Debug, Default)]
+struct RateBucket {
+    second: AtomicU64,
+    count: AtomicU64,
+}
+
+/// Counts events per second over a sliding window with one-second resolution, lock-free (the
+/// counts may be slightly approximate under contention at second boundaries)
+#[derive(Debug)]
+pub struct RateCounter {
+    started: Instant,
+    buckets: Vec<RateBucket>,
+}
+
+impl RateCounter {
+    /// The window is rounded to whole seconds (min 1s)
+    pub fn new(window: Duration) -> Self {
+        #[allow(clippy::cast_possible_truncation)]
+        let secs = window.as_secs().max(1) as usize;
+        Self {
+            started: Instant::now(),
+            buckets: (0..secs).map(|_| RateBucket::default()).collect(),
+        }
+    }
+    fn current_second(&self) -> u64 {
+        // starts with 1, as 0 marks unused buckets
+        self.started.elapsed().as_secs() + 1
+    }
+    #[inline]
+    pub fn inc(&self) {
+        self.add(1);
+    }
+    pub fn add(&self, n: u64) {
+        let second = self.current_second();
+        #[allow(clippy::cast_possible_truncation)]
+        let bucket = &self.buckets[(second % self.buckets.len() as u64) as usize];
+        let prev = bucket.second.swap(second, Ordering::SeqCst);
+        if prev == second {
+            bucket.count.fetch_add(n, Ordering::SeqCst);
+        } else {
+            bucket.count.store(n, Ordering::SeqCst);
+        }
+    }
+    /// Total events in the window
+    pub fn count(&self) -> u64 {
+        let second = self.current_second();
+        let window = self.buckets.len() as u64;
+        self.buckets
+            .iter()
+            .filter(|b| {
+                let s = b.second.load(Ordering::SeqCst);
+                s > 0 && s + window > second
+            })
+            .map(|b| b.count.load(Ordering::SeqCst))
+            .sum()
+    }
+    /// Events per second in the window
+    pub fn rate(&self) -> f64 {
+        #[allow(clippy::cast_precision_loss)]
+        let rate = self.count() as f64 / self.buckets.len() as f64;
+        rate
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct QuantilesSnapshot {
+    pub count: usize,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub p50: f64,
+    pub p90: f64,
+    pub p99: f64,
+}
+
+/// Calculates quantiles over the most recent samples
+#[derive(Debug)]
+pub struct Quantiles {
+    capacity: usize,
+    samples: Mutex<VecDeque<f64>>,
+}
+
+fn quantile_sorted(sorted: &[f64], q: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_precision_loss
+    )]
+    let pos = ((sorted.len() - 1) as f64 * q.clamp(0.0, 1.0)).round() as usize;
+    sorted[pos]
+}
+
+impl Quantiles {
+    /// Keeps up to capacity most recent samples
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            samples: Mutex::new(VecDeque::with_capacity(capacity.max(1))),
+        }
+    }
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn record(&self, sample: f64) {
+        let mut samples = self.samples.lock().unwrap();
+        if samples.len() == self.capacity {
+            samples.pop_front();
+        }
+        samples.push_back(sample);
+    }
+    #[inline]
+    pub fn record_duration(&self, d: Duration) {
+        self.record(d.as_secs_f64());
+    }
+    fn sorted(&self) -> Vec<f64> {
+        let mut sorted: Vec<f64> = self.samples.lock().unwrap().iter().copied().collect();
+        sorted.sort_by(f64::total_cmp);
+        sorted
+    }
+    /// Returns the quantile (0.0..=1.0) of the recorded samples, 0.0 if there are no samples
+    ///
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn quantile(&self, q: f64) -> f64 {
+        quantile_sorted(&self.sorted(), q)
+    }
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn snapshot(&self) -> QuantilesSnapshot {
+        let sorted = self.sorted();
+        if sorted.is_empty() {
+            return QuantilesSnapshot::default();
+        }
+        #[allow(clippy::cast_precision_loss)]
+        let mean = sorted.iter().sum::<f64>() / sorted.len() as f64;
+        QuantilesSnapshot {
+            count: sorted.len(),
+            min: sorted[0],
+            max: sorted[sorted.len() - 1],
+            mean,
+            p50: quantile_sorted(&sorted, 0.5),
+            p90: quantile_sorted(&sorted, 0.9),
+            p99: quantile_sorted(&sorted, 0.99),
+        }
+    }
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn clear(&self) {
+        self.samples.lock().unwrap().clear();
+    }
+}
@@ -0,0 +1,157 @@
+//! Host identity helpers
+//!
+//! All values are cached after the first successful call.
+use crate::{Error, Result, ResultExt};
+use tokio::sync::OnceCell;
+
+static HOSTNAME: OnceCell<String> = OnceCell::const_new();
+static FQDN: OnceCell<String> = OnceCell::const_new();
+static MACHINE_ID: OnceCell<String> = OnceCell::const_new();
+static BOOT_ID: OnceCell<String> = OnceCell::const_new();
+
+#[cfg(not(target_os = "windows"))]
+const MACHINE_ID_PATHS: &[&str] = &["/etc/machine-id", "/var/lib/dbus/machine-id"];
+#[cfg(not(target_os = "windows"))]
+const BOOT_ID_PATH: &str = "/proc/sys/kernel/random/boot_id";
+
+/// Returns the host name
+///
+/// # Errors
+///
+/// Will return `Err` if the host name can not be obtained
+pub async fn hostname() -> Result<&'static str> {
+    HOSTNAME
+        .get_or_try_init(|| async { get_hostname() })
+        .await
+        .map(String::as_str)
+}
+
+/// Returns the fully qualified domain name of the host, falls back to the host name if it can
+/// not be resolved
+///
+/// # Errors
+///
+/// Will return `Err` if the host name can not be obtained
+pub async fn fqdn() -> Result<&'static str> {
+    FQDN.get_or_try_init(|| async {
+        let host = hostname().await?;
+        #[cfg(not(target_os = "windows"))]
+        {
+            let h = host.to_owned();
+            if let Ok(Some(name)) = tokio::task::spawn_blocking(move || resolve_fqdn(&h)).await {
+                return Ok(name);
+            }
+        }
+        Ok::<_, Error>(host.to_owned())
+    })
+    .await
+    .map(String::as_str)
+}
+
+/// Returns the machine id (systemd/D-Bus)
+///
+/// # Errors
+///
+/// Will return `Err` if the machine id can not be read
+pub async fn machine_id() -> Result<&'static str> {
+    MACHINE_ID
+        .get_or_try_init(|| async {
+            #[cfg(not(target_os = "windows"))]
+            {
+                let mut err = None;
+                for path in MACHINE_ID_PATHS {
+                    match read_id(path).await {
+                        Ok(v) => return Ok(v),
+                        Err(e) => err = Some(e),
+                    }
+                }
+                Err(err.unwrap_or_else(|| Error::not_found("machine id")))
+            }
+            #[cfg(target_os = "windows")]
+            Err(Error::unsupported(
+                "machine id is not supported on this platform",
+            ))
+        })
+        .await
+        .map(String::as_str)
+}
+
+/// Returns the current boot id (Linux only)
+///
+/// # Errors
+///
+/// Will return `Err` if the boot id can not be read
+pub async fn boot_id() -> Result<&'static str> {
+    BOOT_ID
+        .get_or_try_init(|| async {
+            #[cfg(not(target_os = "windows"))]
+            {
+                read_id(BOOT_ID_PATH).await
+            }
+            #[cfg(target_os = "windows")]
+            Err(Error::unsupported(
+                "boot id is not supported on this platform",
+            ))
+        })
+        .await
+        .map(String::as_str)
+}
+
+#[cfg(not(target_os = "windows"))]
+fn get_hostname() -> Result<String> {
+    let mut buf = [0_u8; 256];
+    let name = nix::unistd::gethostname(&mut buf).context("unable to get the host name")?;
+    Ok(name.to_string_lossy().into_owned())
+}
+
+#[cfg(target_os = "windows")]
+fn get_hostname() -> Result<String> {
+    std::env::var("COMPUTERNAME").context("unable to get the host name")
+}
+
+#[cfg(not(target_os = "windows"))]
+async fn read_id(path: &str) -> Result<String> {
+    let data = tokio::fs::read_to_string(path)
+        .await
+        .map_err(Error::from)
+        .with_context(|| format!("unable to read {}", path))?;
+    let id = data.trim();
+    if id.is_empty() {
+        Err(Error::invalid_data(format!("{} is empty", path)))
+    } else {
+        Ok(id.to_owned())
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn resolve_fqdn(host: &str) -> Option<String> {
+    use nix::libc;
+    use std::ffi::{CStr, CString};
+    use std::ptr;
+    let c_host = CString::new(host).ok()?;
+    // SAFETY: hints is a plain C struct, zeroed is a valid initial state
+    let mut hints: libc::addrinfo = unsafe { std::mem::zeroed() };
+    hints.ai_family = libc::AF_UNSPEC;
+    hints.ai_flags = libc::AI_CANONNAME;
+    let mut res: *mut libc::addrinfo = ptr::null_mut();
+    // SAFETY: all pointers are valid, res is freed below
+    let rc = unsafe { libc::getaddrinfo(c_host.as_ptr(), ptr::null(), &hints, &mut res) };
+    if rc != 0 || res.is_null() {
+        return None;
+    }
+    // SAFETY: res is a valid list returned by getaddrinfo
+    let canon = unsafe { (*res).ai_canonname };
+    let result = if canon.is_null() {
+        None
+    } else {
+        // SAFETY: ai_canonname is a nul-terminated string owned by res
+        Some(
+            unsafe { CStr::from_ptr(canon) }
+                .to_string_lossy()
+                .into_owned(),
+        )
+    };
+    // SAFETY: res has been allocated by getaddrinfo
+    unsafe { libc::freeaddrinfo(res) };
+    result.filter(|v| !v.is_empty())
+}
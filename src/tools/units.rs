@@ -0,0 +1,252 @@
+use crate::Error;
+use std::fmt::Write as _;
+use std::time::Duration;
+
+const ERR_INVALID_DURATION: &str = "Invalid duration";
+const ERR_INVALID_SIZE: &str = "Invalid size";
+
+const SIZE_UNITS: &[(&str, u64)] = &[
+    ("T", 1 << 40),
+    ("G", 1 << 30),
+    ("M", 1 << 20),
+    ("K", 1 << 10),
+];
+
+fn split_number(s: &str) -> (&str, &str) {
+    let pos = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    s.split_at(pos)
+}
+
+/// Parses a duration string, e.g. "1h30m", "500ms", "1.5s", "2d 4h". Supported units: w, d, h,
+/// m, s, ms, us, ns. A single number without a unit is treated as seconds, in multi-component
+/// strings all the units must be specified ("1h30" is invalid)
+///
+/// # Errors
+///
+/// Will return `Err` if the string is not a valid duration
+pub fn parse_duration(s: &str) -> Result<Duration, Error> {
+    let mut rest = s.trim();
+    if rest.is_empty() {
+        return Err(Error::invalid_data(ERR_INVALID_DURATION));
+    }
+    let mut result = Duration::ZERO;
+    let mut first = true;
+    while !rest.is_empty() {
+        let (num, tail) = split_number(rest);
+        let value: f64 = num
+            .parse()
+            .map_err(|_| Error::invalid_data(format!("{}: {}", ERR_INVALID_DURATION, s)))?;
+        let unit_len = tail
+            .find(|c: char| c.is_ascii_digit() || c.is_whitespace())
+            .unwrap_or(tail.len());
+        let (unit, tail) = tail.split_at(unit_len);
+        let mult: u64 = match unit {
+            "w" => 604_800_000_000_000,
+            "d" => 86_400_000_000_000,
+            "h" => 3_600_000_000_000,
+            "m" => 60_000_000_000,
+            "" if first && tail.trim().is_empty() => 1_000_000_000,
+            "s" => 1_000_000_000,
+            "ms" => 1_000_000,
+            "us" => 1_000,
+            "ns" => 1,
+            _ => {
+                return Err(Error::invalid_data(format!(
+                    "{}: {}",
+                    ERR_INVALID_DURATION, s
+                )))
+            }
+        };
+        #[allow(clippy::cast_precision_loss)]
+        let nanos = (value * mult as f64).round();
+        #[allow(clippy::cast_precision_loss)]
+        if nanos >= u64::MAX as f64 {
+            return Err(Error::invalid_data(format!(
+                "{}: {}",
+                ERR_INVALID_DURATION, s
+            )));
+        }
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let nanos = nanos as u64;
+        result = result
+            .checked_add(Duration::from_nanos(nanos))
+            .ok_or_else(|| Error::invalid_data(format!("{}: {}", ERR_INVALID_DURATION, s)))?;
+        first = false;
+        rest = tail.trim_start();
+    }
+    Ok(result)
+}
+
+/// Formats a duration as a string, accepted by `parse_duration`, e.g. "1h30m", "2s500ms"
+pub fn format_duration(d: Duration) -> String {
+    let mut secs = d.as_secs();
+    let mut result = String::new();
+    for (unit, mult) in [("d", 86_400), ("h", 3_600), ("m", 60), ("s", 1)] {
+        if secs >= mult {
+            write!(result, "{}{}", secs / mult, unit).unwrap();
+            secs %= mult;
+        }
+    }
+    let nanos = d.subsec_nanos();
+    if nanos > 0 {
+        if nanos % 1_000_000 == 0 {
+            write!(result, "{}ms", nanos / 1_000_000).unwrap();
+        } else if nanos % 1_000 == 0 {
+            write!(result, "{}us", nanos / 1_000).unwrap();
+        } else {
+            write!(result, "{}ns", nanos).unwrap();
+        }
+    }
+    if result.is_empty() {
+        result.push_str("0s");
+    }
+    result
+}
+
+/// Parses a size string, e.g. "10M", "1.5GiB", "512K", "100". The units (K, M, G, T, optionally
+/// followed by "B" or "iB") are binary (1K = 1024). A number without a unit is treated as bytes
+///
+/// # Errors
+///
+/// Will return `Err` if the string is not a valid size
+pub fn parse_size(s: &str) -> Result<u64, Error> {
+    let (num, unit) = split_number(s.trim());
+    let value: f64 = num
+        .parse()
+        .map_err(|_| Error::invalid_data(format!("{}: {}", ERR_INVALID_SIZE, s)))?;
+    let unit = unit.trim().to_uppercase();
+    let unit = unit
+        .strip_suffix("IB")
+        .or_else(|| unit.strip_suffix('B'))
+        .unwrap_or(&unit);
+    let mult = if unit.is_empty() {
+        1
+    } else {
+        SIZE_UNITS
+            .iter()
+            .find(|(u, _)| *u == unit)
+            .map(|(_, m)| *m)
+            .ok_or_else(|| Error::invalid_data(format!("{}: {}", ERR_INVALID_SIZE, s)))?
+    };
+    #[allow(clippy::cast_precision_loss)]
+    let size = value * mult as f64;
+    #[allow(clippy::cast_precision_loss)]
+    if size >= u64::MAX as f64 {
+        return Err(Error::invalid_data(format!("{}: {}", ERR_INVALID_SIZE, s)));
+    }
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    Ok(size.round() as u64)
+}
+
+/// Formats a size as a string, accepted by `parse_size`, e.g. "10M", "1.5G", "100B". Values,
+/// which are not multiples of a unit, are rounded to one decimal place
+pub fn format_size(size: u64) -> String {
+    let mut larger: Option<&str> = None;
+    for (unit, mult) in SIZE_UNITS {
+        if size >= *mult {
+            return if size % mult == 0 {
+                format!("{}{}", size / mult, unit)
+            } else {
+                #[allow(clippy::cast_precision_loss)]
+                let value = size as f64 / *mult as f64;
+                match larger {
+                    // e.g. 1048575 is rounded to 1.0M instead of 1024.0K
+                    Some(larger) if (value * 10.0).round() >= 10240.0 => format!("1.0{}", larger),
+                    _ => format!("{:.1}{}", value, unit),
+                }
+            };
+        }
+        larger.replace(unit);
+    }
+    format!("{}B", size)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{format_duration, format_size, parse_duration, parse_size};
+    use std::time::Duration;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("0").unwrap(), Duration::ZERO);
+        assert_eq!(parse_duration("15").unwrap(), Duration::from_secs(15));
+        assert_eq!(parse_duration("1h30m").unwrap(), Duration::from_secs(5400));
+        assert_eq!(
+            parse_duration("2d 4h").unwrap(),
+            Duration::from_secs(187_200)
+        );
+        assert_eq!(parse_duration("1.5s").unwrap(), Duration::from_millis(1500));
+        assert_eq!(parse_duration("500ms").unwrap(), Duration::from_millis(500));
+        assert_eq!(parse_duration("1w").unwrap(), Duration::from_secs(604_800));
+        assert_eq!(
+            parse_duration("10us5ns").unwrap(),
+            Duration::from_nanos(10_005)
+        );
+    }
+
+    #[test]
+    fn test_parse_duration_invalid() {
+        for s in [
+            "", " ", "1h30", "30 1h", "1.5.5s", "1x", "h", "-1s", "1 h 2", ".s",
+        ] {
+            assert!(parse_duration(s).is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn test_parse_duration_overflow() {
+        assert!(parse_duration("1000000w").is_err());
+        assert!(parse_duration("18446744073709551616ns").is_err());
+        assert!(parse_duration("500000w 500000w").is_err());
+    }
+
+    #[test]
+    fn test_duration_round_trip() {
+        for d in [
+            Duration::ZERO,
+            Duration::from_nanos(1),
+            Duration::from_micros(1500),
+            Duration::from_millis(2500),
+            Duration::from_secs(59),
+            Duration::from_secs(5400),
+            Duration::from_secs(90_061),
+            Duration::new(86_400, 123),
+        ] {
+            assert_eq!(parse_duration(&format_duration(d)).unwrap(), d);
+        }
+        assert_eq!(format_duration(Duration::from_secs(5400)), "1h30m");
+        assert_eq!(format_duration(Duration::from_millis(2500)), "2s500ms");
+        assert_eq!(format_duration(Duration::ZERO), "0s");
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("0").unwrap(), 0);
+        assert_eq!(parse_size("100").unwrap(), 100);
+        assert_eq!(parse_size("100B").unwrap(), 100);
+        assert_eq!(parse_size("512K").unwrap(), 512 * 1024);
+        assert_eq!(parse_size("10M").unwrap(), 10 << 20);
+        assert_eq!(parse_size("1.5GiB").unwrap(), 3 << 29);
+        assert_eq!(parse_size(" 2 tb ").unwrap(), 2 << 40);
+        for s in ["", "K", "1.5.5M", "10X", "-1", "1KK"] {
+            assert!(parse_size(s).is_err(), "{}", s);
+        }
+        assert!(parse_size("16777216T").is_err());
+        assert!(parse_size("18446744073709551616").is_err());
+    }
+
+    #[test]
+    fn test_size_round_trip() {
+        for size in [0, 1, 1023, 1024, 1536, 10 << 20, 3 << 30, 5 << 40] {
+            assert_eq!(parse_size(&format_size(size)).unwrap(), size);
+        }
+        assert_eq!(format_size(100), "100B");
+        assert_eq!(format_size(10 << 20), "10M");
+        assert_eq!(format_size(1536), "1.5K");
+        assert_eq!(format_size(1_048_575), "1.0M");
+        assert_eq!(format_size((1 << 30) - 1), "1.0G");
+        assert_eq!(format_size(1_048_000), "1023.4K");
+    }
+}
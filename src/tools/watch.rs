@@ -0,0 +1,80 @@
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tokio::sync::mpsc;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum FileChange {
+    Created,
+    Modified,
+    Removed,
+}
+
+type FileStamp = Option<(Option<SystemTime>, u64)>;
+
+async fn file_stamp(path: &Path) -> FileStamp {
+    tokio::fs::metadata(path)
+        .await
+        .ok()
+        .map(|m| (m.modified().ok(), m.len()))
+}
+
+/// Watches the file for changes, polling its modification time and size. The events are
+/// debounced: a change is reported only after the file has stayed unchanged for one polling
+/// interval. The watcher is stopped when the receiver is dropped
+pub fn watch_file<P: AsRef<Path>>(
+    path: P,
+    interval: Duration,
+    buf: usize,
+) -> mpsc::Receiver<FileChange> {
+    let path: PathBuf = path.as_ref().to_owned();
+    let (tx, rx) = mpsc::channel(buf);
+    tokio::spawn(async move {
+        let mut int = tokio::time::interval(interval);
+        int.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        int.tick().await;
+        let mut reported = file_stamp(&path).await;
+        let mut last = reported;
+        loop {
+            tokio::select! {
+                _ = int.tick() => {}
+                () = tx.closed() => break,
+            }
+            let current = file_stamp(&path).await;
+            if current != last {
+                // still changing, wait until stable
+                last = current;
+                continue;
+            }
+            if current == reported {
+                continue;
+            }
+            let change = match (reported, current) {
+                (None, Some(_)) => FileChange::Created,
+                (Some(_), None) => FileChange::Removed,
+                _ => FileChange::Modified,
+            };
+            reported = current;
+            if tx.send(change).await.is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+/// Watches the file and calls the reload function when the file is created or modified. Runs
+/// forever, should be spawned as a task
+pub async fn watch_and_reload<P, F, Fut>(path: P, interval: Duration, reload: F)
+where
+    P: AsRef<Path>,
+    F: Fn() -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let mut rx = watch_file(path, interval, 1);
+    while let Some(change) = rx.recv().await {
+        if change != FileChange::Removed {
+            reload().await;
+        }
+    }
+}
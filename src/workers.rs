@@ -1,46 +1,815 @@
 // TODO logs
-use crate::Error;
-use std::collections::BTreeMap;
+#[cfg(feature = "calendar")]
+use crate::calendar::{Calendar, OutsideWindow};
+use crate::tools::BoxFuture;
+use crate::{Error, ErrorKind};
+use log::error;
+use std::any::Any;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::fmt;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::pin::Pin;
+use std::sync::atomic;
 use std::sync::Arc;
-use std::time::Duration;
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime};
 use tokio::sync::{mpsc, Notify};
-use tokio::task;
-use tokio::time::{sleep_until, Instant};
+use tokio::task::{self, JoinSet};
+use tokio::time::{sleep, sleep_until, Instant};
+use uuid::Uuid;
 
 const ERR_DUPLICATE_WORKER_ID: &str = "Duplicate worker ID";
 const ERR_WORKER_NOT_FOUND: &str = "Worker not found";
+const ERR_GROUP_NOT_FOUND: &str = "Worker group not found";
+const ERR_DUPLICATE_UNIT_ID: &str = "Duplicate unit ID";
+const ERR_TRIGGER_QUEUE_FULL: &str = "Trigger queue full";
+const ERR_WORKER_NOT_RUNNING: &str = "Worker not running";
+const ERR_DUPLICATE_TASK_NAME: &str = "Duplicate task name";
+const ERR_TASK_NOT_FOUND: &str = "Task not found";
+
+/// Scheduler behavior when a tick is missed (e.g. the runtime was stalled)
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum MissedTickBehavior {
+    /// Fire all missed ticks back-to-back to catch up (the default)
+    #[default]
+    Burst,
+    /// Skip missed ticks and fire at the next tick aligned to the original schedule
+    Skip,
+    /// Fire once and schedule the next tick one interval from now
+    Delay,
+}
+
+/// Behavior when a tick is fired while the previous invocation is still running
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum OverlapPolicy {
+    /// Skip the tick (the default)
+    #[default]
+    Skip,
+    /// Keep exactly one pending tick, which is processed as soon as the previous invocation is
+    /// finished
+    QueueOne,
+    /// Run invocations concurrently. For schedulers with external consumers (e.g. `worker!`)
+    /// the behavior is the same as `Skip`, as the consumer is responsible for spawning
+    Concurrent,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum WorkerKind {
+    Periodic,
+    OneShot,
+    Supervised,
+}
+
+#[derive(Debug, Clone)]
+pub struct WorkerInfo {
+    pub kind: WorkerKind,
+    /// None for one-shot workers
+    pub interval: Option<Duration>,
+    pub running: bool,
+    pub ticks: u64,
+    pub last_fire: Option<SystemTime>,
+    pub next_fire: Option<SystemTime>,
+    pub panicked: bool,
+    pub restarts: u64,
+    pub paused: bool,
+}
+
+#[derive(Debug, Default)]
+struct WorkerStats {
+    ticks: atomic::AtomicU64,
+    running: atomic::AtomicBool,
+    panicked: atomic::AtomicBool,
+    paused: atomic::AtomicBool,
+    restarts: atomic::AtomicU64,
+    // last fire, next fire
+    fire: std::sync::Mutex<(Option<SystemTime>, Option<SystemTime>)>,
+    timing: std::sync::Mutex<Option<SchedulerTiming>>,
+}
+
+impl WorkerStats {
+    fn fired(&self) {
+        self.ticks.fetch_add(1, atomic::Ordering::SeqCst);
+        self.fire.lock().unwrap().0.replace(SystemTime::now());
+    }
+    fn set_next(&self, next: Option<SystemTime>) {
+        self.fire.lock().unwrap().1 = next;
+    }
+    fn is_paused(&self) -> bool {
+        self.paused.load(atomic::Ordering::SeqCst)
+    }
+    fn record_wakeup(&self, expected: Instant, now: Instant) {
+        if let Some(ref mut timing) = *self.timing.lock().unwrap() {
+            timing.record_wakeup(expected, now);
+        }
+    }
+    fn record_execution(&self, d: Duration) {
+        if let Some(ref mut timing) = *self.timing.lock().unwrap() {
+            timing.record_execution(d);
+        }
+    }
+    fn timing(&self) -> Option<SchedulerTiming> {
+        self.timing.lock().unwrap().clone()
+    }
+}
+
+const HISTOGRAM_BOUNDS: [Duration; 10] = [
+    Duration::from_micros(100),
+    Duration::from_millis(1),
+    Duration::from_millis(5),
+    Duration::from_millis(10),
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+    Duration::from_millis(500),
+    Duration::from_secs(1),
+    Duration::from_secs(5),
+    Duration::from_secs(10),
+];
+
+/// Histogram of durations with fixed buckets (100us .. 10s)
+#[derive(Debug, Clone, Default)]
+pub struct DurationHistogram {
+    counts: [u64; HISTOGRAM_BOUNDS.len() + 1],
+}
+
+impl DurationHistogram {
+    fn record(&mut self, d: Duration) {
+        let idx = HISTOGRAM_BOUNDS
+            .iter()
+            .position(|b| d <= *b)
+            .unwrap_or(HISTOGRAM_BOUNDS.len());
+        self.counts[idx] += 1;
+    }
+    /// Returns buckets as (upper bound, count), the last bucket has no upper bound
+    pub fn buckets(&self) -> Vec<(Option<Duration>, u64)> {
+        HISTOGRAM_BOUNDS
+            .iter()
+            .copied()
+            .map(Some)
+            .chain(std::iter::once(None))
+            .zip(self.counts.iter().copied())
+            .collect()
+    }
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+}
+
+/// Scheduler self-measurement results
+#[derive(Debug, Clone, Default)]
+pub struct SchedulerTiming {
+    /// Late wake-ups (actual vs expected tick time)
+    pub drift: DurationHistogram,
+    pub mean_drift: Duration,
+    pub max_drift: Duration,
+    /// Actual intervals between scheduled ticks
+    pub interval: DurationHistogram,
+    /// Execution times (for workers run by the factory, e.g. `spawn_periodic`)
+    pub execution: DurationHistogram,
+    pub max_execution: Duration,
+    drift_total: Duration,
+    last_tick: Option<Instant>,
+}
+
+impl SchedulerTiming {
+    fn record_wakeup(&mut self, expected: Instant, now: Instant) {
+        let drift = now.saturating_duration_since(expected);
+        self.drift.record(drift);
+        self.drift_total += drift;
+        self.max_drift = self.max_drift.max(drift);
+        #[allow(clippy::cast_possible_truncation)]
+        let samples = self.drift.count().min(u64::from(u32::MAX)) as u32;
+        self.mean_drift = self.drift_total / samples.max(1);
+        if let Some(last) = self.last_tick.replace(now) {
+            self.interval.record(now.saturating_duration_since(last));
+        }
+    }
+    fn record_execution(&mut self, d: Duration) {
+        self.execution.record(d);
+        self.max_execution = self.max_execution.max(d);
+    }
+}
+
+/// Marks the worker as running while alive, detects panics of the worker task
+struct RunGuard(Arc<WorkerStats>);
+
+impl RunGuard {
+    fn new(stats: Arc<WorkerStats>) -> Self {
+        stats.running.store(true, atomic::Ordering::SeqCst);
+        Self(stats)
+    }
+}
+
+impl Drop for RunGuard {
+    fn drop(&mut self) {
+        self.0.running.store(false, atomic::Ordering::SeqCst);
+        if std::thread::panicking() {
+            self.0.panicked.store(true, atomic::Ordering::SeqCst);
+        }
+        self.0.set_next(None);
+    }
+}
+
+/// Restart policy for supervised workers. The restart delay starts with the initial backoff and
+/// is doubled after each restart, up to the max backoff. The delay is reset to the initial
+/// backoff if the worker has been running longer than the max backoff before panicking
+#[derive(Debug, Clone)]
+pub struct RestartPolicy {
+    max_restarts: Option<u64>,
+    backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            max_restarts: None,
+            backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+        }
+    }
+}
+
+impl RestartPolicy {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+    #[inline]
+    #[must_use]
+    pub fn max_restarts(mut self, max_restarts: u64) -> Self {
+        self.max_restarts.replace(max_restarts);
+        self
+    }
+    #[inline]
+    #[must_use]
+    pub fn backoff(mut self, backoff: Duration, max_backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self.max_backoff = max_backoff;
+        self
+    }
+}
+
+/// Polls the future, catching panics
+struct CatchUnwind<F> {
+    fut: Pin<Box<F>>,
+}
+
+impl<F: Future> CatchUnwind<F> {
+    fn new(fut: F) -> Self {
+        Self { fut: Box::pin(fut) }
+    }
+}
+
+impl<F: Future> Future for CatchUnwind<F> {
+    type Output = Result<F::Output, Box<dyn Any + Send>>;
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let fut = self.fut.as_mut();
+        match std::panic::catch_unwind(AssertUnwindSafe(|| fut.poll(cx))) {
+            Ok(Poll::Ready(v)) => Poll::Ready(Ok(v)),
+            Ok(Poll::Pending) => Poll::Pending,
+            Err(e) => Poll::Ready(Err(e)),
+        }
+    }
+}
+
+fn panic_message(e: &(dyn Any + Send)) -> &str {
+    if let Some(msg) = e.downcast_ref::<&str>() {
+        msg
+    } else if let Some(msg) = e.downcast_ref::<String>() {
+        msg.as_str()
+    } else {
+        "unknown panic"
+    }
+}
+
+/// Worker metrics hooks, all methods are no-op by default
+pub trait WorkerMetrics: Send + Sync {
+    /// Called on every scheduler tick / worker invocation
+    fn tick(&self, _worker_id: &str) {}
+    /// Called after every worker invocation
+    fn execution(&self, _worker_id: &str, _duration: Duration) {}
+    /// Called after a task worker has received an item
+    fn queue_depth(&self, _worker_id: &str, _depth: usize) {}
+    /// Called on worker invocation errors, timeouts and panics
+    fn error(&self, _worker_id: &str) {}
+}
+
+#[derive(Clone, Default)]
+struct MetricsHook(Option<(Arc<str>, Arc<dyn WorkerMetrics>)>);
+
+impl fmt::Debug for MetricsHook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("MetricsHook")
+            .field(&self.0.as_ref().map(|(id, _)| id))
+            .finish()
+    }
+}
+
+impl MetricsHook {
+    fn new(worker_id: &str, metrics: Arc<dyn WorkerMetrics>) -> Self {
+        Self(Some((worker_id.into(), metrics)))
+    }
+    #[cfg(feature = "tracing")]
+    fn worker_id(&self) -> Option<&str> {
+        self.0.as_ref().map(|(id, _)| &**id)
+    }
+    fn tick(&self) {
+        if let Some((ref id, ref m)) = self.0 {
+            m.tick(id);
+        }
+    }
+    fn execution(&self, duration: Duration) {
+        if let Some((ref id, ref m)) = self.0 {
+            m.execution(id, duration);
+        }
+    }
+    fn queue_depth(&self, depth: usize) {
+        if let Some((ref id, ref m)) = self.0 {
+            m.queue_depth(id, depth);
+        }
+    }
+    fn error(&self) {
+        if let Some((ref id, ref m)) = self.0 {
+            m.error(id);
+        }
+    }
+    async fn measure<Fut: Future>(&self, fut: Fut) -> Fut::Output {
+        let start = Instant::now();
+        let result = fut.await;
+        self.execution(start.elapsed());
+        result
+    }
+}
+
+/// Time source for schedulers
+pub trait Clock: fmt::Debug + Send + Sync {
+    fn now(&self) -> Instant;
+    /// Completes when the clock reaches the deadline
+    fn sleep_until(&self, deadline: Instant) -> BoxFuture<'_, ()>;
+}
+
+/// The default clock, backed by the tokio monotonic timer
+#[derive(Debug, Default, Copy, Clone)]
+pub struct MonotonicClock;
+
+impl Clock for MonotonicClock {
+    #[inline]
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+    fn sleep_until(&self, deadline: Instant) -> BoxFuture<'_, ()> {
+        Box::pin(sleep_until(deadline))
+    }
+}
+
+fn to_system_time(t: Instant, now: Instant) -> SystemTime {
+    if t >= now {
+        SystemTime::now() + (t - now)
+    } else {
+        SystemTime::now() - (now - t)
+    }
+}
+
+#[derive(Debug)]
+struct AdaptiveInterval {
+    min: Duration,
+    max: Duration,
+    factor: f64,
+    current: std::sync::Mutex<Duration>,
+    changed: Notify,
+}
+
+impl AdaptiveInterval {
+    fn new(min: Duration, max: Duration, factor: f64) -> Self {
+        Self {
+            min,
+            max,
+            factor: if factor.is_finite() {
+                factor.max(1.0)
+            } else {
+                1.0
+            },
+            current: std::sync::Mutex::new(min),
+            changed: Notify::new(),
+        }
+    }
+    fn interval(&self) -> Duration {
+        *self.current.lock().unwrap()
+    }
+    fn set(&self, interval: Duration) {
+        let interval = interval.clamp(self.min, self.max);
+        let prev = std::mem::replace(&mut *self.current.lock().unwrap(), interval);
+        if prev != interval {
+            self.changed.notify_waiters();
+        }
+    }
+}
+
+/// Feedback handle for adaptive schedulers
+#[derive(Debug, Clone)]
+pub struct Feedback(Arc<AdaptiveInterval>);
+
+impl Feedback {
+    /// The value has changed, poll faster (resets the interval to the minimum)
+    #[inline]
+    pub fn changed(&self) {
+        self.0.set(self.0.min);
+    }
+    /// The value is stable, back off
+    pub fn stable(&self) {
+        let current = self.0.interval();
+        let next = Duration::try_from_secs_f64(current.as_secs_f64() * self.0.factor)
+            .unwrap_or(self.0.max);
+        self.0.set(next);
+    }
+    /// Sets the interval explicitly, the value is clamped to the min/max bounds
+    #[inline]
+    pub fn set_interval(&self, interval: Duration) {
+        self.0.set(interval);
+    }
+    /// The current interval
+    #[inline]
+    pub fn interval(&self) -> Duration {
+        self.0.interval()
+    }
+}
 
 #[derive(Debug)]
 pub struct Scheduler {
     interval: Duration,
     trigger: Arc<Notify>,
+    missed_tick_behavior: MissedTickBehavior,
+    jitter: Duration,
+    overlap_policy: OverlapPolicy,
+    stats: Arc<WorkerStats>,
+    metrics: MetricsHook,
+    clock: Arc<dyn Clock>,
+    adaptive: Option<Arc<AdaptiveInterval>>,
+    wake_on: Option<Arc<Notify>>,
+    #[cfg(feature = "calendar")]
+    calendar: Option<(Calendar, OutsideWindow)>,
 }
 
 impl Scheduler {
     pub fn new(trigger: Arc<Notify>, interval: Duration) -> Self {
-        Self { interval, trigger }
+        Self {
+            interval,
+            trigger,
+            missed_tick_behavior: MissedTickBehavior::default(),
+            jitter: Duration::ZERO,
+            overlap_policy: OverlapPolicy::default(),
+            stats: <_>::default(),
+            metrics: MetricsHook::default(),
+            clock: Arc::new(MonotonicClock),
+            adaptive: None,
+            wake_on: None,
+            #[cfg(feature = "calendar")]
+            calendar: None,
+        }
     }
-    pub async fn run(&mut self) {
-        let mut t = Instant::now();
+    #[inline]
+    #[must_use]
+    pub fn missed_tick_behavior(mut self, behavior: MissedTickBehavior) -> Self {
+        self.missed_tick_behavior = behavior;
+        self
+    }
+    /// Randomizes each tick within ±jitter
+    #[inline]
+    #[must_use]
+    pub fn jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+    /// Sets the overlap policy. The `QueueOne` policy is designed for a single consumer
+    #[inline]
+    #[must_use]
+    pub fn overlap_policy(mut self, policy: OverlapPolicy) -> Self {
+        self.overlap_policy = policy;
+        self
+    }
+    /// Makes the interval adaptive: the scheduler starts with the base interval as the minimum
+    /// and backs off up to `max` by multiplying the interval by `factor` every time the worker
+    /// reports a stable state with the [`Feedback`] handle. Reported changes reset the interval
+    /// back to the minimum
+    #[inline]
+    #[must_use]
+    pub fn adaptive(mut self, max: Duration, factor: f64) -> Self {
+        self.adaptive.replace(Arc::new(AdaptiveInterval::new(
+            self.interval,
+            max.max(self.interval),
+            factor,
+        )));
+        self
+    }
+    /// Fires immediately when the external trigger is notified and restarts the interval. Use
+    /// `notify_one` for the external trigger, so multiple on-demand requests received while
+    /// the scheduler is busy are coalesced into a single tick
+    #[inline]
+    #[must_use]
+    pub fn wake_on(mut self, trigger: Arc<Notify>) -> Self {
+        self.wake_on.replace(trigger);
+        self
+    }
+    /// Enables self-measurement: tick drift, actual intervals and execution times (see
+    /// `SchedulerTiming`)
+    #[inline]
+    #[must_use]
+    pub fn measure_timing(self) -> Self {
+        self.stats
+            .timing
+            .lock()
+            .unwrap()
+            .get_or_insert_with(SchedulerTiming::default);
+        self
+    }
+    /// Returns the self-measurement results if enabled
+    pub fn timing(&self) -> Option<SchedulerTiming> {
+        self.stats.timing()
+    }
+    /// Returns the feedback handle for adaptive schedulers
+    pub fn feedback(&self) -> Option<Feedback> {
+        self.adaptive.as_ref().map(|a| Feedback(a.clone()))
+    }
+    /// Sets the time source (`MonotonicClock` by default). Calendar windows are always checked
+    /// against the local wall clock
+    #[inline]
+    #[must_use]
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+    /// Restricts ticks to the calendar windows. When deferred ticks are used, it is recommended to
+    /// set the missed tick behavior to `Skip` or `Delay`
+    #[cfg(feature = "calendar")]
+    #[inline]
+    #[must_use]
+    pub fn calendar(mut self, calendar: Calendar, outside: OutsideWindow) -> Self {
+        self.calendar.replace((calendar, outside));
+        self
+    }
+    #[cfg(feature = "calendar")]
+    async fn tick(&self) {
         loop {
-            t += self.interval;
-            sleep_until(t).await;
+            if self.stats.is_paused() {
+                return;
+            }
+            let Some((ref calendar, outside)) = self.calendar else {
+                break;
+            };
+            let now = chrono::Local::now();
+            if calendar.is_allowed(now) {
+                break;
+            }
+            if outside == OutsideWindow::Suppress {
+                return;
+            }
+            let Some(next) = calendar.next_allowed(now) else {
+                return;
+            };
+            self.stats.set_next(Some(next.into()));
+            // the scheduler may be paused and the wall clock may be adjusted while the tick is
+            // deferred, both are checked again after waking up
+            let deadline = self.clock.now() + (next - now).to_std().unwrap_or_default();
+            self.clock.sleep_until(deadline).await;
+        }
+        self.fire();
+    }
+    #[cfg(not(feature = "calendar"))]
+    async fn tick(&self) {
+        if !self.stats.is_paused() {
+            self.fire();
+        }
+    }
+    fn jittered(&self, t: Instant) -> Instant {
+        if self.jitter.is_zero() {
+            return t;
+        }
+        #[allow(clippy::cast_possible_truncation)]
+        let jitter = self.jitter.as_nanos().min(u128::from(u64::MAX / 2)) as u64;
+        #[allow(clippy::cast_possible_truncation)]
+        let offset = (Uuid::new_v4().as_u128() as u64) % (jitter * 2 + 1);
+        if offset >= jitter {
+            t + Duration::from_nanos(offset - jitter)
+        } else {
+            t.checked_sub(Duration::from_nanos(jitter - offset))
+                .unwrap_or(t)
+        }
+    }
+    fn current_interval(&self) -> Duration {
+        self.adaptive
+            .as_ref()
+            .map_or(self.interval, |a| a.interval())
+    }
+    fn next_tick(&self, t: Instant) -> Instant {
+        let interval = self.current_interval();
+        let next = t + interval;
+        let now = self.clock.now();
+        if next >= now {
+            return next;
+        }
+        match self.missed_tick_behavior {
+            MissedTickBehavior::Burst => next,
+            MissedTickBehavior::Delay => now + interval,
+            MissedTickBehavior::Skip => {
+                let interval_ns = interval.as_nanos();
+                if interval_ns == 0 {
+                    return now;
+                }
+                #[allow(clippy::cast_possible_truncation)]
+                let behind = ((now - next).as_nanos() % interval_ns) as u64;
+                now + interval - Duration::from_nanos(behind)
+            }
+        }
+    }
+    fn fire(&self) {
+        if self.overlap_policy == OverlapPolicy::QueueOne {
+            // stores a permit if the consumer is busy
+            self.trigger.notify_one();
+        } else {
             self.trigger.notify_waiters();
         }
+        self.stats.fired();
+        self.metrics.tick();
+        #[cfg(feature = "tracing")]
+        tracing::trace!(worker = self.metrics.worker_id(), interval = ?self.current_interval(), "scheduler tick");
+    }
+    async fn wait_until(&self, t: Instant) {
+        self.stats
+            .set_next(Some(to_system_time(t, self.clock.now())));
+        self.clock.sleep_until(t).await;
+    }
+    /// Waits for the next tick, returns the tick time. For adaptive schedulers the tick is
+    /// rescheduled as soon as the interval is changed by the feedback. External wake-ups fire
+    /// immediately and restart the interval
+    async fn wait_next(&self, t: Instant) -> Instant {
+        loop {
+            let next = self.next_tick(t);
+            let mut changed = self
+                .adaptive
+                .as_ref()
+                .map(|a| Box::pin(a.changed.notified()));
+            if let Some(c) = changed.as_mut() {
+                c.as_mut().enable();
+            }
+            let changed = async {
+                match changed {
+                    Some(c) => c.await,
+                    None => std::future::pending().await,
+                }
+            };
+            let external = async {
+                match self.wake_on {
+                    Some(ref n) => n.notified().await,
+                    None => std::future::pending().await,
+                }
+            };
+            let target = self.jittered(next);
+            tokio::select! {
+                () = self.wait_until(target) => {
+                    self.stats.record_wakeup(target, self.clock.now());
+                    return next;
+                }
+                () = external => return self.clock.now(),
+                () = changed => {}
+            }
+        }
+    }
+    pub async fn run(&mut self) {
+        let mut t = self.clock.now();
+        loop {
+            t = self.wait_next(t).await;
+            self.tick().await;
+        }
     }
     pub async fn run_instant(&mut self) {
-        let mut t = Instant::now();
+        let mut t = self.clock.now();
         loop {
-            self.trigger.notify_waiters();
-            t += self.interval;
-            sleep_until(t).await;
+            self.tick().await;
+            t = self.wait_next(t).await;
+        }
+    }
+}
+
+#[derive(Debug)]
+struct IntervalTaskState<T> {
+    value: Option<(Arc<T>, SystemTime)>,
+    error: Option<(String, SystemTime)>,
+}
+
+/// The latest result of an interval task. Clones share the same state
+#[derive(Debug)]
+pub struct IntervalTaskResult<T> {
+    state: Arc<std::sync::Mutex<IntervalTaskState<T>>>,
+}
+
+impl<T> Clone for IntervalTaskResult<T> {
+    fn clone(&self) -> Self {
+        Self {
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<T> IntervalTaskResult<T> {
+    fn new() -> Self {
+        Self {
+            state: Arc::new(std::sync::Mutex::new(IntervalTaskState {
+                value: None,
+                error: None,
+            })),
+        }
+    }
+    fn set_value(&self, value: T) {
+        self.state.lock().unwrap().value = Some((Arc::new(value), SystemTime::now()));
+    }
+    fn set_error(&self, error: String) {
+        self.state.lock().unwrap().error = Some((error, SystemTime::now()));
+    }
+    /// The latest successful result
+    ///
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn latest(&self) -> Option<Arc<T>> {
+        self.state
+            .lock()
+            .unwrap()
+            .value
+            .as_ref()
+            .map(|v| v.0.clone())
+    }
+    /// The time of the latest successful result
+    ///
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn updated_at(&self) -> Option<SystemTime> {
+        self.state.lock().unwrap().value.as_ref().map(|v| v.1)
+    }
+    /// The last error, kept until the next error even if succeeded after
+    ///
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn last_error(&self) -> Option<(String, SystemTime)> {
+        self.state.lock().unwrap().error.clone()
+    }
+    /// Returns true if the last invocation has failed
+    ///
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn is_failed(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        match (&state.value, &state.error) {
+            (_, None) => false,
+            (None, Some(_)) => true,
+            (Some((_, ok)), Some((_, err))) => err > ok,
         }
     }
 }
 
+struct WorkerEntry {
+    kind: WorkerKind,
+    interval: Option<Duration>,
+    stats: Arc<WorkerStats>,
+    fut: task::JoinHandle<()>,
+    result: Option<Arc<dyn Any + Send + Sync>>,
+}
+
+impl WorkerEntry {
+    fn info(&self) -> WorkerInfo {
+        let (last_fire, next_fire) = *self.stats.fire.lock().unwrap();
+        WorkerInfo {
+            kind: self.kind,
+            interval: self.interval,
+            running: self.stats.running.load(atomic::Ordering::SeqCst),
+            ticks: self.stats.ticks.load(atomic::Ordering::SeqCst),
+            last_fire,
+            next_fire,
+            panicked: self.stats.panicked.load(atomic::Ordering::SeqCst),
+            restarts: self.stats.restarts.load(atomic::Ordering::SeqCst),
+            paused: self.stats.is_paused(),
+        }
+    }
+}
+
+type LifecycleFn = Box<dyn Fn() -> BoxFuture<'static, Result<(), Error>> + Send + Sync>;
+
+struct LifecycleUnit {
+    depends_on: Vec<String>,
+    start: Arc<LifecycleFn>,
+    stop: Arc<LifecycleFn>,
+}
+
 pub struct WorkerFactory {
-    schedulers: BTreeMap<String, task::JoinHandle<()>>,
+    schedulers: BTreeMap<String, WorkerEntry>,
+    groups: BTreeMap<String, WorkerFactory>,
+    units: BTreeMap<String, LifecycleUnit>,
+    metrics: Option<Arc<dyn WorkerMetrics>>,
+    measure_timing: bool,
+    paused: bool,
 }
 
 impl Default for WorkerFactory {
@@ -54,41 +823,355 @@ impl WorkerFactory {
     pub fn new() -> Self {
         Self {
             schedulers: BTreeMap::new(),
+            groups: BTreeMap::new(),
+            units: BTreeMap::new(),
+            metrics: None,
+            measure_timing: false,
+            paused: false,
         }
     }
 
+    /// Returns a worker group (sub-factory), creates a new one if not exists. The group inherits
+    /// metrics hooks, the timing measurement setting and the paused state
+    pub fn group(&mut self, name: &str) -> &mut WorkerFactory {
+        let metrics = self.metrics.clone();
+        let measure_timing = self.measure_timing;
+        let paused = self.paused;
+        self.groups
+            .entry(name.to_owned())
+            .or_insert_with(|| WorkerFactory {
+                metrics,
+                measure_timing,
+                paused,
+                ..WorkerFactory::new()
+            })
+    }
+
+    pub fn groups(&self) -> Vec<&str> {
+        self.groups.keys().map(String::as_str).collect()
+    }
+
+    /// Destroys all workers of the group and its sub-groups
+    ///
     /// # Errors
     ///
-    /// Will return `Err` if the worker already exists
-    pub fn create_scheduler(
-        &mut self,
-        worker_id: &str,
-        trigger: Arc<Notify>,
-        interval: Duration,
-        instant: bool,
-    ) -> Result<(), Error> {
-        self._create_scheduler(worker_id, trigger, interval, false, instant)
+    /// Will return `Err` if the group does not exist
+    pub fn destroy_group(&mut self, name: &str) -> Result<(), Error> {
+        self.groups
+            .remove(name)
+            .map_or(Err(Error::not_found(ERR_GROUP_NOT_FOUND)), |mut group| {
+                group.destroy_all();
+                Ok(())
+            })
+    }
+
+    /// Destroys all workers and groups
+    pub fn destroy_all(&mut self) {
+        for (_, entry) in std::mem::take(&mut self.schedulers) {
+            entry.fut.abort();
+        }
+        for (_, mut group) in std::mem::take(&mut self.groups) {
+            group.destroy_all();
+        }
+    }
+
+    /// Pauses all workers and groups, including the ones created later. Paused workers skip
+    /// ticks
+    pub fn pause(&mut self) {
+        self.set_paused(true);
+    }
+
+    pub fn resume(&mut self) {
+        self.set_paused(false);
+    }
+
+    #[inline]
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+        for entry in self.schedulers.values() {
+            entry.stats.paused.store(paused, atomic::Ordering::SeqCst);
+        }
+        for group in self.groups.values_mut() {
+            group.set_paused(paused);
+        }
     }
 
+    /// Registers a lifecycle unit with start and stop functions. The unit is started after all
+    /// its dependencies and is stopped before them
+    ///
     /// # Errors
     ///
-    /// Will return `Err` if failed to recreate the worker
-    pub fn recreate_scheduler(
+    /// Will return `Err` if the unit already exists
+    pub fn register_unit<S, SFut, T, TFut>(
         &mut self,
-        worker_id: &str,
-        trigger: Arc<Notify>,
-        interval: Duration,
-        instant: bool,
-    ) -> Result<(), Error> {
-        self._create_scheduler(worker_id, trigger, interval, true, instant)
+        unit_id: &str,
+        depends_on: &[&str],
+        start: S,
+        stop: T,
+    ) -> Result<(), Error>
+    where
+        S: Fn() -> SFut + Send + Sync + 'static,
+        SFut: Future<Output = Result<(), Error>> + Send + 'static,
+        T: Fn() -> TFut + Send + Sync + 'static,
+        TFut: Future<Output = Result<(), Error>> + Send + 'static,
+    {
+        if self.units.contains_key(unit_id) {
+            return Err(Error::duplicate(ERR_DUPLICATE_UNIT_ID));
+        }
+        let start: LifecycleFn = Box::new(move || Box::pin(start()));
+        let stop: LifecycleFn = Box::new(move || Box::pin(stop()));
+        self.units.insert(
+            unit_id.to_owned(),
+            LifecycleUnit {
+                depends_on: depends_on.iter().map(|&v| v.to_owned()).collect(),
+                start: Arc::new(start),
+                stop: Arc::new(stop),
+            },
+        );
+        Ok(())
     }
 
-    fn _create_scheduler(
-        &mut self,
-        worker_id: &str,
-        trigger: Arc<Notify>,
-        interval: Duration,
-        recreate: bool,
+    /// Splits the units into stages, each stage depends on the previous ones only
+    fn unit_stages(&self) -> Result<Vec<Vec<String>>, Error> {
+        let mut pending: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for (id, unit) in &self.units {
+            for dep in &unit.depends_on {
+                if !self.units.contains_key(dep) {
+                    return Err(Error::not_found(format!(
+                        "unit {} dependency {} not found",
+                        id, dep
+                    )));
+                }
+            }
+            pending.insert(id, unit.depends_on.iter().map(String::as_str).collect());
+        }
+        let mut stages = Vec::new();
+        while !pending.is_empty() {
+            let stage: Vec<&str> = pending
+                .iter()
+                .filter(|(_, deps)| deps.iter().all(|d| !pending.contains_key(d)))
+                .map(|(&id, _)| id)
+                .collect();
+            if stage.is_empty() {
+                return Err(Error::invalid_data("unit dependency cycle detected"));
+            }
+            for id in &stage {
+                pending.remove(id);
+            }
+            stages.push(stage.into_iter().map(ToOwned::to_owned).collect());
+        }
+        Ok(stages)
+    }
+
+    /// Units completed successfully are put into done
+    async fn run_unit_stage(
+        &self,
+        stage: &[String],
+        start: bool,
+        timeout: Duration,
+        done: &mut Vec<String>,
+    ) -> Result<(), Error> {
+        let mut set = JoinSet::new();
+        for id in stage {
+            let unit = &self.units[id];
+            let f = if start {
+                unit.start.clone()
+            } else {
+                unit.stop.clone()
+            };
+            let id = id.clone();
+            set.spawn(async move {
+                match f().await {
+                    Ok(()) => Ok(id),
+                    Err(e) => Err((id, e)),
+                }
+            });
+        }
+        let mut result = Ok(());
+        let completed = tokio::time::timeout(timeout, async {
+            while let Some(res) = set.join_next().await {
+                let res = match res {
+                    Ok(Ok(id)) => {
+                        done.push(id);
+                        continue;
+                    }
+                    Ok(Err((id, e))) => Err(Error {
+                        kind: e.kind,
+                        message: Some(format!("unit {}: {}", id, e.message.unwrap_or_default())),
+                        source: e.source,
+                    }),
+                    Err(e) => Err(Error::internal(e)),
+                };
+                if result.is_ok() {
+                    result = res;
+                }
+                if start {
+                    // fail fast on startup
+                    break;
+                }
+            }
+        })
+        .await;
+        if completed.is_err() && result.is_ok() {
+            result = Err(Error::timeout());
+        }
+        result
+    }
+
+    /// Starts all lifecycle units in the dependency order. Units of the same stage are started
+    /// concurrently, each stage is limited with the timeout. Stops at the first failed stage, the
+    /// units already started are stopped in the reversed dependency order
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the dependencies are invalid or a unit has failed to start
+    pub async fn start_all(&self, stage_timeout: Duration) -> Result<(), Error> {
+        let mut started: Vec<Vec<String>> = Vec::new();
+        for stage in self.unit_stages()? {
+            let mut done = Vec::new();
+            let res = self
+                .run_unit_stage(&stage, true, stage_timeout, &mut done)
+                .await;
+            started.push(done);
+            if let Err(e) = res {
+                for stage in started.iter().rev() {
+                    if let Err(e) = self
+                        .run_unit_stage(stage, false, stage_timeout, &mut Vec::new())
+                        .await
+                    {
+                        error!("unit rollback failed: {}", e);
+                    }
+                }
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Stops all lifecycle units in the reversed dependency order. Continues on errors, the
+    /// first error is returned
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the dependencies are invalid or a unit has failed to stop
+    pub async fn stop_all(&self, stage_timeout: Duration) -> Result<(), Error> {
+        let mut result = Ok(());
+        for stage in self.unit_stages()?.into_iter().rev() {
+            let res = self
+                .run_unit_stage(&stage, false, stage_timeout, &mut Vec::new())
+                .await;
+            if result.is_ok() {
+                result = res;
+            }
+        }
+        result
+    }
+
+    fn run_guard(&self, stats: &Arc<WorkerStats>) -> RunGuard {
+        stats.paused.store(self.paused, atomic::Ordering::SeqCst);
+        RunGuard::new(stats.clone())
+    }
+
+    /// Sets metrics hooks for the workers created after
+    pub fn set_metrics(&mut self, metrics: Arc<dyn WorkerMetrics>) {
+        self.metrics.replace(metrics);
+    }
+
+    /// Enables scheduler self-measurement for the workers created after (see
+    /// `Scheduler::measure_timing`)
+    pub fn set_measure_timing(&mut self, measure: bool) {
+        self.measure_timing = measure;
+    }
+
+    fn metrics_hook(&self, worker_id: &str) -> MetricsHook {
+        self.metrics
+            .as_ref()
+            .map_or_else(MetricsHook::default, |m| {
+                MetricsHook::new(worker_id, m.clone())
+            })
+    }
+
+    /// # Errors
+    ///
+    /// Will return `Err` if the worker already exists
+    pub fn create_scheduler(
+        &mut self,
+        worker_id: &str,
+        trigger: Arc<Notify>,
+        interval: Duration,
+        instant: bool,
+        missed_tick_behavior: MissedTickBehavior,
+    ) -> Result<(), Error> {
+        self._create_scheduler(
+            worker_id,
+            Scheduler::new(trigger, interval).missed_tick_behavior(missed_tick_behavior),
+            false,
+            instant,
+        )
+    }
+
+    /// Creates a worker for the pre-configured scheduler
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the worker already exists
+    pub fn create_scheduler_from(
+        &mut self,
+        worker_id: &str,
+        scheduler: Scheduler,
+        instant: bool,
+    ) -> Result<(), Error> {
+        self._create_scheduler(worker_id, scheduler, false, instant)
+    }
+
+    /// Creates a scheduler which randomizes each tick within ±jitter
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the worker already exists
+    pub fn create_scheduler_jittered(
+        &mut self,
+        worker_id: &str,
+        trigger: Arc<Notify>,
+        interval: Duration,
+        jitter: Duration,
+    ) -> Result<(), Error> {
+        self._create_scheduler(
+            worker_id,
+            Scheduler::new(trigger, interval).jitter(jitter),
+            false,
+            false,
+        )
+    }
+
+    /// # Errors
+    ///
+    /// Will return `Err` if failed to recreate the worker
+    pub fn recreate_scheduler(
+        &mut self,
+        worker_id: &str,
+        trigger: Arc<Notify>,
+        interval: Duration,
+        instant: bool,
+        missed_tick_behavior: MissedTickBehavior,
+    ) -> Result<(), Error> {
+        self._create_scheduler(
+            worker_id,
+            Scheduler::new(trigger, interval).missed_tick_behavior(missed_tick_behavior),
+            true,
+            instant,
+        )
+    }
+
+    fn _create_scheduler(
+        &mut self,
+        worker_id: &str,
+        mut scheduler: Scheduler,
+        recreate: bool,
         instant: bool,
     ) -> Result<(), Error> {
         if self.schedulers.contains_key(worker_id) {
@@ -98,58 +1181,2082 @@ impl WorkerFactory {
                 return Err(Error::duplicate(ERR_DUPLICATE_WORKER_ID));
             }
         }
-        let mut scheduler = Scheduler::new(trigger, interval);
+        scheduler.metrics = self.metrics_hook(worker_id);
+        if self.measure_timing {
+            scheduler = scheduler.measure_timing();
+        }
+        let interval = scheduler.interval;
+        let stats = scheduler.stats.clone();
+        let guard = self.run_guard(&stats);
         let fut = if instant {
             tokio::spawn(async move {
+                let _g = guard;
                 scheduler.run_instant().await;
             })
         } else {
             tokio::spawn(async move {
+                let _g = guard;
                 scheduler.run().await;
             })
         };
-        self.schedulers.insert(worker_id.to_owned(), fut);
+        self.insert(worker_id, WorkerKind::Periodic, Some(interval), stats, fut);
+        Ok(())
+    }
+
+    /// Spawns a periodic worker which calls the function every interval, no trigger or
+    /// `worker!` consumer is required.
+    ///
+    /// With `OverlapPolicy::Concurrent` every tick spawns a new run, the runs are not aborted
+    /// when the worker is destroyed.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the worker already exists
+    pub fn spawn_periodic<F, Fut>(
+        &mut self,
+        worker_id: &str,
+        interval: Duration,
+        overlap_policy: OverlapPolicy,
+        func: F,
+    ) -> Result<(), Error>
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.spawn_periodic_from(
+            worker_id,
+            Scheduler::new(Arc::new(Notify::new()), interval).overlap_policy(overlap_policy),
+            func,
+        )
+    }
+
+    /// Same as `spawn_periodic` but uses the pre-configured scheduler (clock, missed tick
+    /// behavior, jitter etc.). The scheduler trigger must not be shared with other consumers
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the worker already exists
+    pub fn spawn_periodic_from<F, Fut>(
+        &mut self,
+        worker_id: &str,
+        scheduler: Scheduler,
+        func: F,
+    ) -> Result<(), Error>
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.spawn_scheduled(worker_id, scheduler, false, func)
+    }
+
+    fn spawn_scheduled<F, Fut>(
+        &mut self,
+        worker_id: &str,
+        mut scheduler: Scheduler,
+        instant: bool,
+        func: F,
+    ) -> Result<(), Error>
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        if self.schedulers.contains_key(worker_id) {
+            return Err(Error::duplicate(ERR_DUPLICATE_WORKER_ID));
+        }
+        scheduler.metrics = self.metrics_hook(worker_id);
+        if self.measure_timing {
+            scheduler = scheduler.measure_timing();
+        }
+        let trigger = scheduler.trigger.clone();
+        let overlap_policy = scheduler.overlap_policy;
+        let interval = scheduler.interval;
+        let metrics = scheduler.metrics.clone();
+        let stats = scheduler.stats.clone();
+        let guard = self.run_guard(&stats);
+        let st = stats.clone();
+        let fut = tokio::spawn(async move {
+            let _g = guard;
+            let consumer = async move {
+                loop {
+                    trigger.notified().await;
+                    if overlap_policy == OverlapPolicy::Concurrent {
+                        let fut = func();
+                        let metrics = metrics.clone();
+                        let st = st.clone();
+                        tokio::spawn(async move {
+                            let start = Instant::now();
+                            metrics.measure(fut).await;
+                            st.record_execution(start.elapsed());
+                        });
+                    } else {
+                        let start = Instant::now();
+                        metrics.measure(func()).await;
+                        st.record_execution(start.elapsed());
+                    }
+                }
+            };
+            let producer = async move {
+                if instant {
+                    // lets the consumer subscribe before the first tick is fired
+                    tokio::task::yield_now().await;
+                    scheduler.run_instant().await;
+                } else {
+                    scheduler.run().await;
+                }
+            };
+            tokio::join!(producer, consumer);
+        });
+        self.insert(worker_id, WorkerKind::Periodic, Some(interval), stats, fut);
+        Ok(())
+    }
+
+    /// Same as `spawn_periodic` but each invocation is limited with the timeout
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the worker already exists
+    pub fn spawn_periodic_with_timeout<F, Fut>(
+        &mut self,
+        worker_id: &str,
+        interval: Duration,
+        overlap_policy: OverlapPolicy,
+        timeout: InvocationTimeout,
+        func: F,
+    ) -> Result<(), Error>
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let func = Arc::new(func);
+        let metrics = self.metrics_hook(worker_id);
+        self.spawn_periodic(worker_id, interval, overlap_policy, move || {
+            let func = func.clone();
+            let timeout = timeout.clone();
+            let metrics = metrics.clone();
+            async move {
+                // the execution time is measured by the periodic worker itself
+                if tokio::time::timeout(timeout.timeout, func()).await.is_err() {
+                    metrics.error();
+                    (timeout.handler)(Error::timeout());
+                }
+            }
+        })
+    }
+
+    /// Spawns a periodic worker which calls the function every retry interval until it returns
+    /// `Ok`, then switches to the normal interval. If the function fails again, the worker is
+    /// switched back to the retry interval. The errors are logged
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the worker already exists
+    pub fn spawn_until_success<F, Fut, E>(
+        &mut self,
+        worker_id: &str,
+        interval: Duration,
+        retry_interval: Duration,
+        func: F,
+    ) -> Result<(), Error>
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: fmt::Display + Send,
+    {
+        if self.schedulers.contains_key(worker_id) {
+            return Err(Error::duplicate(ERR_DUPLICATE_WORKER_ID));
+        }
+        let stats: Arc<WorkerStats> = <_>::default();
+        let guard = self.run_guard(&stats);
+        let st = stats.clone();
+        let id = worker_id.to_owned();
+        let metrics = self.metrics_hook(worker_id);
+        let fut = tokio::spawn(async move {
+            let _g = guard;
+            loop {
+                let delay = if st.is_paused() {
+                    interval
+                } else {
+                    st.fired();
+                    metrics.tick();
+                    match metrics.measure(func()).await {
+                        Ok(()) => interval,
+                        Err(e) => {
+                            metrics.error();
+                            error!(
+                                "worker {} failed: {}, retrying in {:?}",
+                                id, e, retry_interval
+                            );
+                            retry_interval
+                        }
+                    }
+                };
+                st.set_next(Some(SystemTime::now() + delay));
+                sleep(delay).await;
+            }
+        });
+        self.insert(worker_id, WorkerKind::Periodic, Some(interval), stats, fut);
+        Ok(())
+    }
+
+    /// Spawns a periodic worker which calls the function immediately and then every interval,
+    /// keeping the latest successful result and the last error. The result cell can be
+    /// obtained later with `latest` / `interval_task_result`. The errors are logged
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the worker already exists
+    pub fn spawn_interval_task<F, Fut, T, E>(
+        &mut self,
+        worker_id: &str,
+        interval: Duration,
+        func: F,
+    ) -> Result<IntervalTaskResult<T>, Error>
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<T, E>> + Send + 'static,
+        T: Send + Sync + 'static,
+        E: fmt::Display + Send,
+    {
+        self.spawn_interval_task_from(
+            worker_id,
+            Scheduler::new(Arc::new(Notify::new()), interval),
+            func,
+        )
+    }
+
+    /// Same as `spawn_interval_task` but uses the pre-configured scheduler (clock, missed tick
+    /// behavior, jitter etc.). The scheduler trigger must not be shared with other consumers
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the worker already exists
+    pub fn spawn_interval_task_from<F, Fut, T, E>(
+        &mut self,
+        worker_id: &str,
+        scheduler: Scheduler,
+        func: F,
+    ) -> Result<IntervalTaskResult<T>, Error>
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<T, E>> + Send + 'static,
+        T: Send + Sync + 'static,
+        E: fmt::Display + Send,
+    {
+        let result = IntervalTaskResult::new();
+        let id = worker_id.to_owned();
+        let metrics = self.metrics_hook(worker_id);
+        let res = result.clone();
+        self.spawn_scheduled(worker_id, scheduler, true, move || {
+            let fut = func();
+            let id = id.clone();
+            let metrics = metrics.clone();
+            let res = res.clone();
+            async move {
+                match fut.await {
+                    Ok(v) => res.set_value(v),
+                    Err(e) => {
+                        metrics.error();
+                        error!("worker {} failed: {}", id, e);
+                        res.set_error(e.to_string());
+                    }
+                }
+            }
+        })?;
+        if let Some(entry) = self.schedulers.get_mut(worker_id) {
+            entry.result.replace(Arc::new(result.clone()));
+        }
+        Ok(result)
+    }
+
+    /// Returns the result cell of the interval task
+    pub fn interval_task_result<T: Send + Sync + 'static>(
+        &self,
+        worker_id: &str,
+    ) -> Option<IntervalTaskResult<T>> {
+        self.schedulers
+            .get(worker_id)?
+            .result
+            .as_ref()?
+            .downcast_ref::<IntervalTaskResult<T>>()
+            .cloned()
+    }
+
+    /// Returns the latest successful result of the interval task, `None` if the worker is not
+    /// found, is not an interval task of the type or has not succeeded yet
+    pub fn latest<T: Send + Sync + 'static>(&self, worker_id: &str) -> Option<Arc<T>> {
+        self.interval_task_result::<T>(worker_id)?.latest()
+    }
+
+    /// Schedules a single-fire trigger notification after the delay. The id of a one-shot
+    /// worker which has already fired can be reused. The notification is stored if the trigger
+    /// consumer is not waiting at the moment
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the worker already exists
+    pub fn schedule_once(
+        &mut self,
+        worker_id: &str,
+        delay: Duration,
+        trigger: Arc<Notify>,
+    ) -> Result<(), Error> {
+        if let Some(entry) = self.schedulers.get(worker_id) {
+            if entry.kind == WorkerKind::OneShot && entry.fut.is_finished() {
+                self.schedulers.remove(worker_id);
+            } else {
+                return Err(Error::duplicate(ERR_DUPLICATE_WORKER_ID));
+            }
+        }
+        let stats: Arc<WorkerStats> = <_>::default();
+        stats.set_next(Some(SystemTime::now() + delay));
+        let guard = self.run_guard(&stats);
+        let st = stats.clone();
+        let metrics = self.metrics_hook(worker_id);
+        let fut = tokio::spawn(async move {
+            let _g = guard;
+            sleep(delay).await;
+            if !st.is_paused() {
+                trigger.notify_one();
+                st.fired();
+                metrics.tick();
+            }
+        });
+        self.insert(worker_id, WorkerKind::OneShot, None, stats, fut);
+        Ok(())
+    }
+
+    /// Schedules a single-fire trigger notification at the specified time. If the time is in
+    /// the past, the trigger is notified immediately
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the worker already exists
+    pub fn schedule_at(
+        &mut self,
+        worker_id: &str,
+        at: SystemTime,
+        trigger: Arc<Notify>,
+    ) -> Result<(), Error> {
+        let delay = at.duration_since(SystemTime::now()).unwrap_or_default();
+        self.schedule_once(worker_id, delay, trigger)
+    }
+
+    /// Spawns a worker which calls the function every time the trigger is notified. If the
+    /// function panics, the panic is logged and the worker is restarted according to the policy.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the worker already exists
+    pub fn create_worker_supervised<F, Fut>(
+        &mut self,
+        worker_id: &str,
+        trigger: Arc<Notify>,
+        policy: RestartPolicy,
+        func: F,
+    ) -> Result<(), Error>
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        if self.schedulers.contains_key(worker_id) {
+            return Err(Error::duplicate(ERR_DUPLICATE_WORKER_ID));
+        }
+        let stats: Arc<WorkerStats> = <_>::default();
+        let guard = self.run_guard(&stats);
+        let st = stats.clone();
+        let id = worker_id.to_owned();
+        let metrics = self.metrics_hook(worker_id);
+        let fut = tokio::spawn(async move {
+            let _g = guard;
+            let mut delay = policy.backoff;
+            loop {
+                let started = Instant::now();
+                let res = CatchUnwind::new(async {
+                    loop {
+                        trigger.notified().await;
+                        if st.is_paused() {
+                            continue;
+                        }
+                        st.fired();
+                        metrics.tick();
+                        metrics.measure(func()).await;
+                    }
+                })
+                .await;
+                let Err(e) = res else {
+                    break;
+                };
+                metrics.error();
+                st.panicked.store(true, atomic::Ordering::SeqCst);
+                // the worker has been running healthy for a while, start the backoff over
+                if started.elapsed() > policy.max_backoff {
+                    delay = policy.backoff;
+                }
+                let restarts = st.restarts.load(atomic::Ordering::SeqCst);
+                if policy.max_restarts.map_or(false, |max| restarts >= max) {
+                    error!(
+                        "worker {} panicked: {}, restart limit reached",
+                        id,
+                        panic_message(e.as_ref())
+                    );
+                    break;
+                }
+                error!(
+                    "worker {} panicked: {}, restarting in {:?}",
+                    id,
+                    panic_message(e.as_ref()),
+                    delay
+                );
+                sleep(delay).await;
+                st.restarts.fetch_add(1, atomic::Ordering::SeqCst);
+                delay = delay.saturating_mul(2).min(policy.max_backoff);
+            }
+        });
+        self.insert(worker_id, WorkerKind::Supervised, None, stats, fut);
         Ok(())
     }
 
+    fn insert(
+        &mut self,
+        worker_id: &str,
+        kind: WorkerKind,
+        interval: Option<Duration>,
+        stats: Arc<WorkerStats>,
+        fut: task::JoinHandle<()>,
+    ) {
+        self.schedulers.insert(
+            worker_id.to_owned(),
+            WorkerEntry {
+                kind,
+                interval,
+                stats,
+                fut,
+                result: None,
+            },
+        );
+    }
+
     /// # Errors
     ///
     /// Will return `Err` if the worker does not exist
     pub fn destroy_scheduler(&mut self, worker_id: &str) -> Result<(), Error> {
         self.schedulers.remove(worker_id).map_or(
             Err(Error::not_found(ERR_WORKER_NOT_FOUND)),
-            |fut| {
-                fut.abort();
+            |entry| {
+                entry.fut.abort();
                 Ok(())
             },
         )
     }
+
+    /// # Errors
+    ///
+    /// Will return `Err` if the worker does not exist
+    pub fn info(&self, worker_id: &str) -> Result<WorkerInfo, Error> {
+        self.schedulers
+            .get(worker_id)
+            .map(WorkerEntry::info)
+            .ok_or_else(|| Error::not_found(ERR_WORKER_NOT_FOUND))
+    }
+
+    /// Returns the scheduler self-measurement results, `None` if not enabled (see
+    /// `Scheduler::measure_timing`)
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the worker does not exist
+    pub fn timing(&self, worker_id: &str) -> Result<Option<SchedulerTiming>, Error> {
+        self.schedulers
+            .get(worker_id)
+            .map(|entry| entry.stats.timing())
+            .ok_or_else(|| Error::not_found(ERR_WORKER_NOT_FOUND))
+    }
+
+    pub fn list(&self) -> Vec<(&str, WorkerInfo)> {
+        self.schedulers
+            .iter()
+            .map(|(id, entry)| (id.as_str(), entry.info()))
+            .collect()
+    }
 }
 
-pub struct TaskWorker<F, Fut, T>
-where
-    F: FnMut(T) -> Fut,
-    Fut: std::future::Future<Output = ()>,
-    T: Sync + fmt::Debug,
-{
-    func: F,
-    rx: mpsc::Receiver<T>,
+/// Per-invocation timeout. If an invocation is timed out, it is aborted and the handler is called
+/// with `ErrorKind::Timeout` error
+#[derive(Clone)]
+pub struct InvocationTimeout {
+    timeout: Duration,
+    handler: Arc<dyn Fn(Error) + Send + Sync>,
 }
 
-impl<F, Fut, T> TaskWorker<F, Fut, T>
+impl InvocationTimeout {
+    pub fn new<H>(timeout: Duration, handler: H) -> Self
+    where
+        H: Fn(Error) + Send + Sync + 'static,
+    {
+        Self {
+            timeout,
+            handler: Arc::new(handler),
+        }
+    }
+}
+
+#[cfg(feature = "tracing")]
+fn traced_item<T: fmt::Debug, Fut: Future>(
+    metrics: &MetricsHook,
+    item: &T,
+) -> impl FnOnce(Fut) -> tracing::instrument::Instrumented<Fut> {
+    use tracing::Instrument;
+    let span = tracing::debug_span!("task_worker_item", worker = metrics.worker_id(), item = ?item);
+    move |fut| fut.instrument(span)
+}
+
+#[cfg(not(feature = "tracing"))]
+fn traced_item<T: fmt::Debug, Fut: Future>(
+    _metrics: &MetricsHook,
+    _item: &T,
+) -> impl FnOnce(Fut) -> Fut {
+    |fut| fut
+}
+
+async fn invoke<Fut>(timeout: Option<&InvocationTimeout>, metrics: &MetricsHook, fut: Fut)
 where
-    F: FnMut(T) -> Fut,
-    Fut: std::future::Future<Output = ()>,
-    T: Sync + fmt::Debug,
+    Fut: Future<Output = ()>,
 {
-    pub fn new(func: F, buf: usize) -> (Self, mpsc::Sender<T>) {
-        let (tx, rx) = mpsc::channel(buf);
-        (Self { func, rx }, tx)
+    if let Some(t) = timeout {
+        if metrics
+            .measure(tokio::time::timeout(t.timeout, fut))
+            .await
+            .is_err()
+        {
+            metrics.error();
+            (t.handler)(Error::timeout());
+        }
+    } else {
+        metrics.measure(fut).await;
     }
+}
 
-    pub async fn run(&mut self) {
-        while let Some(v) = self.rx.recv().await {
-            (self.func)(v).await;
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum DrainMode {
+    /// Abort the current item processing and drop the queued items
+    Immediate,
+    /// Finish processing the queued items
+    Drain,
+    /// Finish processing the queued items, abort if not finished in the given time
+    DrainTimeout(Duration),
+}
+
+#[derive(Debug, Default)]
+struct TaskWorkerShared {
+    mode: std::sync::Mutex<Option<DrainMode>>,
+    stop: Notify,
+    remaining: atomic::AtomicUsize,
+}
+
+/// Task worker controller, allows to stop the worker and to get the number of queued items
+pub struct TaskWorkerController<T> {
+    shared: Arc<TaskWorkerShared>,
+    tx: mpsc::WeakSender<T>,
+}
+
+impl<T> Clone for TaskWorkerController<T> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+            tx: self.tx.clone(),
+        }
+    }
+}
+
+impl<T> TaskWorkerController<T> {
+    /// Asks the worker to stop. The new items are not accepted, the worker `run` method exits
+    /// according to the drain mode
+    pub fn stop(&self, mode: DrainMode) {
+        self.shared.mode.lock().unwrap().replace(mode);
+        self.shared.stop.notify_one();
+    }
+    /// Number of items queued
+    pub fn remaining(&self) -> usize {
+        self.tx.upgrade().map_or_else(
+            || self.shared.remaining.load(atomic::Ordering::SeqCst),
+            |tx| tx.max_capacity() - tx.capacity(),
+        )
+    }
+}
+
+pub struct TaskWorker<F, Fut, T>
+where
+    F: FnMut(T) -> Fut,
+    Fut: std::future::Future<Output = ()>,
+    T: Sync + fmt::Debug,
+{
+    func: F,
+    rx: mpsc::Receiver<T>,
+    tx: mpsc::WeakSender<T>,
+    shared: Arc<TaskWorkerShared>,
+    invocation_timeout: Option<InvocationTimeout>,
+    metrics: MetricsHook,
+}
+
+impl<F, Fut, T> TaskWorker<F, Fut, T>
+where
+    F: FnMut(T) -> Fut,
+    Fut: std::future::Future<Output = ()>,
+    T: Sync + fmt::Debug,
+{
+    pub fn new(func: F, buf: usize) -> (Self, mpsc::Sender<T>) {
+        let (tx, rx) = mpsc::channel(buf);
+        (
+            Self {
+                func,
+                rx,
+                tx: tx.downgrade(),
+                shared: <_>::default(),
+                invocation_timeout: None,
+                metrics: MetricsHook::default(),
+            },
+            tx,
+        )
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn metrics(mut self, worker_id: &str, metrics: Arc<dyn WorkerMetrics>) -> Self {
+        self.metrics = MetricsHook::new(worker_id, metrics);
+        self
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn invocation_timeout(mut self, timeout: InvocationTimeout) -> Self {
+        self.invocation_timeout.replace(timeout);
+        self
+    }
+
+    pub fn controller(&self) -> TaskWorkerController<T> {
+        TaskWorkerController {
+            shared: self.shared.clone(),
+            tx: self.tx.clone(),
+        }
+    }
+
+    /// Processes messages until all senders are dropped or the worker is stopped with the
+    /// controller
+    pub async fn run(&mut self) {
+        let shared = self.shared.clone();
+        // drain deadline, shared by the in-flight item and the queued ones
+        let mut deadline = None;
+        loop {
+            let v = tokio::select! {
+                v = self.rx.recv() => v,
+                () = shared.stop.notified() => break,
+            };
+            let Some(v) = v else {
+                break;
+            };
+            shared
+                .remaining
+                .store(self.rx.len(), atomic::Ordering::SeqCst);
+            self.metrics.queue_depth(self.rx.len());
+            let traced = traced_item(&self.metrics, &v);
+            let fut = invoke(
+                self.invocation_timeout.as_ref(),
+                &self.metrics,
+                traced((self.func)(v)),
+            );
+            tokio::pin!(fut);
+            tokio::select! {
+                () = &mut fut => continue,
+                () = shared.stop.notified() => {}
+            }
+            // stop requested while processing
+            let mode = *shared.mode.lock().unwrap();
+            match mode {
+                Some(DrainMode::Immediate) => {}
+                Some(DrainMode::DrainTimeout(timeout)) => {
+                    let d = Instant::now() + timeout;
+                    deadline.replace(d);
+                    let _r = tokio::time::timeout_at(d, fut).await;
+                }
+                Some(DrainMode::Drain) | None => fut.await,
+            }
+            break;
+        }
+        self.rx.close();
+        let mode = *shared.mode.lock().unwrap();
+        match mode {
+            Some(DrainMode::Drain) => self.drain().await,
+            Some(DrainMode::DrainTimeout(timeout)) => {
+                let d = deadline.unwrap_or_else(|| Instant::now() + timeout);
+                let _r = tokio::time::timeout_at(d, self.drain()).await;
+            }
+            Some(DrainMode::Immediate) | None => {}
+        }
+        shared
+            .remaining
+            .store(self.rx.len(), atomic::Ordering::SeqCst);
+    }
+
+    async fn drain(&mut self) {
+        while let Some(v) = self.rx.recv().await {
+            self.shared
+                .remaining
+                .store(self.rx.len(), atomic::Ordering::SeqCst);
+            self.metrics.queue_depth(self.rx.len());
+            let traced = traced_item(&self.metrics, &v);
+            invoke(
+                self.invocation_timeout.as_ref(),
+                &self.metrics,
+                traced((self.func)(v)),
+            )
+            .await;
+        }
+        self.shared
+            .remaining
+            .store(self.rx.len(), atomic::Ordering::SeqCst);
+    }
+}
+
+impl<F, Fut, T> TaskWorker<F, Fut, T>
+where
+    F: FnMut(T) -> Fut,
+    Fut: std::future::Future<Output = ()> + Send + 'static,
+    T: Sync + fmt::Debug,
+{
+    /// Processes up to max_in_flight messages in parallel. If ordered is true, the tasks are
+    /// joined in the order the messages have been received, otherwise as soon as completed.
+    /// Processes messages until all senders are dropped or the worker is stopped with the
+    /// controller
+    pub async fn run_concurrent(&mut self, max_in_flight: usize, ordered: bool) {
+        let max_in_flight = max_in_flight.max(1);
+        let shared = self.shared.clone();
+        let mut tasks = if ordered {
+            ConcurrentTasks::Ordered(OrderedTasks(VecDeque::with_capacity(max_in_flight)))
+        } else {
+            ConcurrentTasks::Unordered(JoinSet::new())
+        };
+        loop {
+            if tasks.len() >= max_in_flight {
+                tokio::select! {
+                    () = tasks.join_next() => {}
+                    () = shared.stop.notified() => break,
+                }
+            }
+            let v = tokio::select! {
+                v = self.rx.recv() => v,
+                () = shared.stop.notified() => break,
+            };
+            let Some(v) = v else {
+                break;
+            };
+            self.spawn_concurrent(&mut tasks, v);
+        }
+        self.rx.close();
+        let mode = *shared.mode.lock().unwrap();
+        match mode {
+            Some(DrainMode::Immediate) => tasks.abort_all(),
+            Some(DrainMode::Drain) | None => self.drain_concurrent(&mut tasks, max_in_flight).await,
+            Some(DrainMode::DrainTimeout(timeout)) => {
+                let d = Instant::now() + timeout;
+                if tokio::time::timeout_at(d, self.drain_concurrent(&mut tasks, max_in_flight))
+                    .await
+                    .is_err()
+                {
+                    tasks.abort_all();
+                }
+            }
+        }
+        shared
+            .remaining
+            .store(self.rx.len(), atomic::Ordering::SeqCst);
+    }
+
+    fn spawn_concurrent(&mut self, tasks: &mut ConcurrentTasks, v: T) {
+        self.shared
+            .remaining
+            .store(self.rx.len(), atomic::Ordering::SeqCst);
+        self.metrics.queue_depth(self.rx.len());
+        let traced = traced_item(&self.metrics, &v);
+        let fut = traced((self.func)(v));
+        let timeout = self.invocation_timeout.clone();
+        let metrics = self.metrics.clone();
+        tasks.spawn(async move {
+            invoke(timeout.as_ref(), &metrics, fut).await;
+        });
+    }
+
+    async fn drain_concurrent(&mut self, tasks: &mut ConcurrentTasks, max_in_flight: usize) {
+        loop {
+            if tasks.len() >= max_in_flight {
+                tasks.join_next().await;
+            }
+            let Some(v) = self.rx.recv().await else {
+                break;
+            };
+            self.spawn_concurrent(tasks, v);
+        }
+        while tasks.len() > 0 {
+            tasks.join_next().await;
+        }
+    }
+}
+
+/// Tasks joined in the spawn order, aborted if the owner is dropped
+struct OrderedTasks(VecDeque<task::JoinHandle<()>>);
+
+impl OrderedTasks {
+    async fn join_next(&mut self) {
+        if let Some(fut) = self.0.front_mut() {
+            report_task_result(fut.await);
+            self.0.pop_front();
+        }
+    }
+}
+
+impl Drop for OrderedTasks {
+    fn drop(&mut self) {
+        for fut in &self.0 {
+            fut.abort();
+        }
+    }
+}
+
+/// In-flight tasks of a concurrent task worker
+enum ConcurrentTasks {
+    Ordered(OrderedTasks),
+    Unordered(JoinSet<()>),
+}
+
+impl ConcurrentTasks {
+    fn len(&self) -> usize {
+        match self {
+            ConcurrentTasks::Ordered(queue) => queue.0.len(),
+            ConcurrentTasks::Unordered(set) => set.len(),
+        }
+    }
+    fn spawn<F>(&mut self, fut: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        match self {
+            ConcurrentTasks::Ordered(queue) => queue.0.push_back(tokio::spawn(fut)),
+            ConcurrentTasks::Unordered(set) => {
+                set.spawn(fut);
+            }
+        }
+    }
+    /// Cancel-safe, the task stays in the set if the future is dropped
+    async fn join_next(&mut self) {
+        match self {
+            ConcurrentTasks::Ordered(queue) => queue.join_next().await,
+            ConcurrentTasks::Unordered(set) => {
+                if let Some(res) = set.join_next().await {
+                    report_task_result(res);
+                }
+            }
+        }
+    }
+    fn abort_all(&mut self) {
+        match self {
+            ConcurrentTasks::Ordered(queue) => {
+                for fut in queue.0.drain(..) {
+                    fut.abort();
+                }
+            }
+            ConcurrentTasks::Unordered(set) => {
+                set.abort_all();
+                set.detach_all();
+            }
+        }
+    }
+}
+
+fn report_task_result(res: Result<(), task::JoinError>) {
+    if let Err(e) = res {
+        error!("task worker task failed: {}", e);
+    }
+}
+
+/// Retry policy for failed task worker items. The retry delay starts with the initial backoff
+/// and is doubled after each attempt, up to the max backoff
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    max_retries: u32,
+    backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    #[inline]
+    pub fn new(max_retries: u32, backoff: Duration, max_backoff: Duration) -> Self {
+        Self {
+            max_retries,
+            backoff,
+            max_backoff,
+        }
+    }
+    fn delay(&self, attempt: u32) -> Duration {
+        self.backoff
+            .checked_mul(2_u32.saturating_pow(attempt))
+            .map_or(self.max_backoff, |d| d.min(self.max_backoff))
+    }
+}
+
+/// Task worker with handlers returning `Result`. Results and errors are delivered to the result
+/// channel, failed items are optionally retried
+pub struct TryTaskWorker<F, Fut, T, R, E>
+where
+    F: FnMut(T) -> Fut,
+    Fut: std::future::Future<Output = Result<R, E>>,
+    T: Sync + Clone + fmt::Debug,
+{
+    func: F,
+    rx: mpsc::Receiver<T>,
+    tx_result: mpsc::Sender<Result<R, E>>,
+    retry_policy: Option<RetryPolicy>,
+    metrics: MetricsHook,
+}
+
+impl<F, Fut, T, R, E> TryTaskWorker<F, Fut, T, R, E>
+where
+    F: FnMut(T) -> Fut,
+    Fut: std::future::Future<Output = Result<R, E>>,
+    T: Sync + Clone + fmt::Debug,
+{
+    pub fn new(
+        func: F,
+        buf: usize,
+        result_buf: usize,
+    ) -> (Self, mpsc::Sender<T>, mpsc::Receiver<Result<R, E>>) {
+        let (tx, rx) = mpsc::channel(buf);
+        let (tx_result, rx_result) = mpsc::channel(result_buf);
+        (
+            Self {
+                func,
+                rx,
+                tx_result,
+                retry_policy: None,
+                metrics: MetricsHook::default(),
+            },
+            tx,
+            rx_result,
+        )
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy.replace(policy);
+        self
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn metrics(mut self, worker_id: &str, metrics: Arc<dyn WorkerMetrics>) -> Self {
+        self.metrics = MetricsHook::new(worker_id, metrics);
+        self
+    }
+
+    /// Processes messages sequentially. Results are dropped if the result channel is closed
+    pub async fn run(&mut self) {
+        while let Some(v) = self.rx.recv().await {
+            self.metrics.queue_depth(self.rx.len());
+            let mut attempt = 0;
+            let res = loop {
+                let res = self.metrics.measure((self.func)(v.clone())).await;
+                if res.is_err() {
+                    self.metrics.error();
+                    if let Some(ref policy) = self.retry_policy {
+                        if attempt < policy.max_retries {
+                            sleep(policy.delay(attempt)).await;
+                            attempt += 1;
+                            continue;
+                        }
+                    }
+                }
+                break res;
+            };
+            let _r = self.tx_result.send(res).await;
+        }
+    }
+}
+
+/// Collects incoming items and calls the handler with a batch when either the max batch size is
+/// reached or the linger time since the first item of the batch is passed
+pub struct BatchWorker<F, Fut, T>
+where
+    F: FnMut(Vec<T>) -> Fut,
+    Fut: std::future::Future<Output = ()>,
+    T: Sync + fmt::Debug,
+{
+    func: F,
+    rx: mpsc::Receiver<T>,
+    max_size: usize,
+    linger: Duration,
+}
+
+impl<F, Fut, T> BatchWorker<F, Fut, T>
+where
+    F: FnMut(Vec<T>) -> Fut,
+    Fut: std::future::Future<Output = ()>,
+    T: Sync + fmt::Debug,
+{
+    pub fn new(func: F, buf: usize, max_size: usize, linger: Duration) -> (Self, mpsc::Sender<T>) {
+        let (tx, rx) = mpsc::channel(buf);
+        (
+            Self {
+                func,
+                rx,
+                max_size: max_size.max(1),
+                linger,
+            },
+            tx,
+        )
+    }
+
+    /// Processes batches until all senders are dropped, the remaining items are processed as
+    /// the last batch
+    pub async fn run(&mut self) {
+        let mut closed = false;
+        while !closed {
+            let Some(v) = self.rx.recv().await else {
+                break;
+            };
+            let mut batch = Vec::with_capacity(self.max_size);
+            batch.push(v);
+            let deadline = Instant::now() + self.linger;
+            while batch.len() < self.max_size {
+                match tokio::time::timeout_at(deadline, self.rx.recv()).await {
+                    Ok(Some(v)) => batch.push(v),
+                    Ok(None) => {
+                        closed = true;
+                        break;
+                    }
+                    Err(_) => break,
+                }
+            }
+            (self.func)(batch).await;
+        }
+    }
+}
+
+/// Processes items with per-key ordering: items with the same key are processed sequentially
+/// in the order received, items with different keys are processed concurrently, up to
+/// `max_in_flight` keys at once
+pub struct KeyedTaskWorker<F, Fut, K, T>
+where
+    F: FnMut(K, T) -> Fut,
+    Fut: std::future::Future<Output = ()> + Send + 'static,
+    K: Eq + std::hash::Hash + Clone + Send + 'static,
+{
+    func: F,
+    rx: mpsc::Receiver<(K, T)>,
+    buf: usize,
+    max_in_flight: usize,
+    metrics: MetricsHook,
+}
+
+impl<F, Fut, K, T> KeyedTaskWorker<F, Fut, K, T>
+where
+    F: FnMut(K, T) -> Fut,
+    Fut: std::future::Future<Output = ()> + Send + 'static,
+    K: Eq + std::hash::Hash + Clone + Send + 'static,
+{
+    /// The worker keeps up to `buf` items pending, senders are blocked when the buffer is full
+    pub fn new(func: F, buf: usize, max_in_flight: usize) -> (Self, mpsc::Sender<(K, T)>) {
+        let (tx, rx) = mpsc::channel(buf);
+        (
+            Self {
+                func,
+                rx,
+                buf: buf.max(1),
+                max_in_flight: max_in_flight.max(1),
+                metrics: MetricsHook::default(),
+            },
+            tx,
+        )
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn metrics(mut self, worker_id: &str, metrics: Arc<dyn WorkerMetrics>) -> Self {
+        self.metrics = MetricsHook::new(worker_id, metrics);
+        self
+    }
+
+    /// Processes items until all senders are dropped and all pending items are processed
+    pub async fn run(&mut self) {
+        let mut pending: HashMap<K, VecDeque<T>> = HashMap::new();
+        // keys with pending items, which are not being processed
+        let mut ready: VecDeque<K> = VecDeque::new();
+        let mut active: std::collections::HashSet<K> = <_>::default();
+        let mut set: JoinSet<K> = JoinSet::new();
+        let mut queued = 0;
+        let mut closed = false;
+        loop {
+            while set.len() < self.max_in_flight {
+                let Some(key) = ready.pop_front() else {
+                    break;
+                };
+                let Some(v) = pending.get_mut(&key).and_then(VecDeque::pop_front) else {
+                    continue;
+                };
+                queued -= 1;
+                active.insert(key.clone());
+                let fut = (self.func)(key.clone(), v);
+                let metrics = self.metrics.clone();
+                set.spawn(async move {
+                    if let Err(e) = CatchUnwind::new(metrics.measure(fut)).await {
+                        metrics.error();
+                        error!("keyed task worker task panicked: {}", panic_message(&*e));
+                    }
+                    key
+                });
+            }
+            self.metrics.queue_depth(queued);
+            if closed && set.is_empty() {
+                break;
+            }
+            tokio::select! {
+                v = self.rx.recv(), if !closed && queued < self.buf => {
+                    let Some((key, v)) = v else {
+                        closed = true;
+                        continue;
+                    };
+                    queued += 1;
+                    let queue = pending.entry(key.clone()).or_default();
+                    queue.push_back(v);
+                    // the first pending item of an idle key
+                    if queue.len() == 1 && !active.contains(&key) {
+                        ready.push_back(key);
+                    }
+                }
+                Some(res) = set.join_next(), if !set.is_empty() => {
+                    // panics are caught in tasks, the set is never aborted
+                    let Ok(key) = res else {
+                        continue;
+                    };
+                    active.remove(&key);
+                    if pending.get(&key).map_or(false, |q| !q.is_empty()) {
+                        ready.push_back(key);
+                    } else {
+                        pending.remove(&key);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Registry of named tasks
+#[derive(Default)]
+pub struct TaskRegistry {
+    tasks: BTreeMap<String, task::JoinHandle<()>>,
+}
+
+impl TaskRegistry {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// # Errors
+    ///
+    /// Will return `Err` if the task already exists, the passed task is aborted in this case
+    pub fn register(&mut self, name: &str, fut: task::JoinHandle<()>) -> Result<(), Error> {
+        if self.tasks.contains_key(name) {
+            fut.abort();
+            return Err(Error::duplicate(ERR_DUPLICATE_TASK_NAME));
+        }
+        self.tasks.insert(name.to_owned(), fut);
+        Ok(())
+    }
+
+    /// Spawns the future and registers its task
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the task already exists
+    pub fn spawn<Fut>(&mut self, name: &str, fut: Fut) -> Result<(), Error>
+    where
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        if self.tasks.contains_key(name) {
+            return Err(Error::duplicate(ERR_DUPLICATE_TASK_NAME));
+        }
+        self.tasks.insert(name.to_owned(), tokio::spawn(fut));
+        Ok(())
+    }
+
+    /// Returns task names and finished flags
+    pub fn list(&self) -> Vec<(&str, bool)> {
+        self.tasks
+            .iter()
+            .map(|(name, fut)| (name.as_str(), fut.is_finished()))
+            .collect()
+    }
+
+    /// Aborts the task and removes it from the registry
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the task does not exist
+    pub fn abort(&mut self, name: &str) -> Result<(), Error> {
+        self.tasks
+            .remove(name)
+            .map_or(Err(Error::not_found(ERR_TASK_NOT_FOUND)), |fut| {
+                fut.abort();
+                Ok(())
+            })
+    }
+
+    pub fn abort_all(&mut self) {
+        for (_, fut) in std::mem::take(&mut self.tasks) {
+            fut.abort();
+        }
+    }
+
+    /// Waits for all tasks to finish. The tasks, not finished in time, are aborted
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` with `ErrorKind::Timeout` if not all tasks have been finished in time
+    pub async fn join_all(&mut self, timeout: Duration) -> Result<(), Error> {
+        let deadline = Instant::now() + timeout;
+        let mut result = Ok(());
+        for (name, mut fut) in std::mem::take(&mut self.tasks) {
+            match tokio::time::timeout_at(deadline, &mut fut).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => {
+                    if e.is_panic() {
+                        error!("task {} panicked", name);
+                    }
+                }
+                Err(_) => {
+                    fut.abort();
+                    result = Err(Error::timeout());
+                }
+            }
+        }
+        result
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct TimerId(u64);
+
+struct TimerWheelInner<T> {
+    // timer id -> (rounds left, value)
+    slots: Vec<HashMap<u64, (u64, T)>>,
+    // timer id -> slot
+    index: HashMap<u64, usize>,
+    cursor: usize,
+    next_id: u64,
+}
+
+impl<T> TimerWheelInner<T> {
+    fn advance(&mut self) -> Vec<T> {
+        self.cursor = (self.cursor + 1) % self.slots.len();
+        let slot = &mut self.slots[self.cursor];
+        let expired_ids: Vec<u64> = slot
+            .iter_mut()
+            .filter_map(|(id, (rounds, _))| {
+                if *rounds == 0 {
+                    Some(*id)
+                } else {
+                    *rounds -= 1;
+                    None
+                }
+            })
+            .collect();
+        let mut expired = Vec::with_capacity(expired_ids.len());
+        for id in expired_ids {
+            if let Some((_, value)) = slot.remove(&id) {
+                expired.push(value);
+            }
+            self.index.remove(&id);
+        }
+        expired
+    }
+}
+
+/// Hashed timer wheel for large numbers of one-shot timers with O(1) insertion and
+/// cancellation. Expired values are delivered to the channel. The timer precision equals to the
+/// wheel resolution
+pub struct TimerWheel<T> {
+    inner: Arc<std::sync::Mutex<TimerWheelInner<T>>>,
+    resolution: Duration,
+    fut: task::JoinHandle<()>,
+}
+
+impl<T: Send + 'static> TimerWheel<T> {
+    /// Creates a new timer wheel and spawns its task. Timeouts longer than resolution * slots
+    /// are processed in multiple wheel rounds
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the resolution is zero
+    pub fn new(resolution: Duration, slots: usize, buf: usize) -> (Self, mpsc::Receiver<T>) {
+        assert!(
+            !resolution.is_zero(),
+            "timer wheel resolution can not be zero"
+        );
+        let (tx, rx) = mpsc::channel(buf);
+        let inner = Arc::new(std::sync::Mutex::new(TimerWheelInner {
+            slots: (0..slots.max(1)).map(|_| HashMap::new()).collect(),
+            index: HashMap::new(),
+            cursor: 0,
+            next_id: 0,
+        }));
+        let wheel = inner.clone();
+        let fut = tokio::spawn(async move {
+            let mut interval = tokio::time::interval_at(Instant::now() + resolution, resolution);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Burst);
+            loop {
+                interval.tick().await;
+                let expired = wheel.lock().unwrap().advance();
+                for value in expired {
+                    if tx.send(value).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+        (
+            Self {
+                inner,
+                resolution,
+                fut,
+            },
+            rx,
+        )
+    }
+
+    /// Inserts a new timer
+    ///
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn insert(&self, timeout: Duration, value: T) -> TimerId {
+        #[allow(clippy::cast_possible_truncation)]
+        let ticks = (timeout.as_nanos().div_ceil(self.resolution.as_nanos()) as u64).max(1);
+        let mut inner = self.inner.lock().unwrap();
+        let len = inner.slots.len() as u64;
+        #[allow(clippy::cast_possible_truncation)]
+        let slot = ((inner.cursor as u64 + ticks) % len) as usize;
+        let id = inner.next_id;
+        inner.next_id += 1;
+        inner.slots[slot].insert(id, ((ticks - 1) / len, value));
+        inner.index.insert(id, slot);
+        TimerId(id)
+    }
+
+    /// Cancels the timer, returns its value if the timer has not been expired yet
+    ///
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn cancel(&self, id: TimerId) -> Option<T> {
+        let mut inner = self.inner.lock().unwrap();
+        let slot = inner.index.remove(&id.0)?;
+        inner.slots[slot].remove(&id.0).map(|(_, value)| value)
+    }
+
+    /// Number of active timers
+    ///
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().index.len()
+    }
+
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> Drop for TimerWheel<T> {
+    fn drop(&mut self) {
+        self.fut.abort();
+    }
+}
+
+struct DelayQueueInner<K, T> {
+    by_time: BTreeMap<(Instant, u64), K>,
+    items: HashMap<K, (Instant, u64, T)>,
+    seq: u64,
+}
+
+impl<K: Eq + std::hash::Hash + Clone, T> DelayQueueInner<K, T> {
+    fn insert(&mut self, key: K, value: T, fire_at: Instant) -> Option<T> {
+        let seq = self.seq;
+        self.seq += 1;
+        self.by_time.insert((fire_at, seq), key.clone());
+        let prev = self.items.insert(key, (fire_at, seq, value));
+        prev.map(|(t, s, v)| {
+            self.by_time.remove(&(t, s));
+            v
+        })
+    }
+    fn next(&self) -> Option<Instant> {
+        self.by_time.keys().next().map(|(t, _)| *t)
+    }
+    fn pop_due(&mut self, now: Instant) -> Vec<T> {
+        let mut due = Vec::new();
+        while let Some(entry) = self.by_time.first_entry() {
+            if entry.key().0 > now {
+                break;
+            }
+            let key = entry.remove();
+            if let Some((_, _, v)) = self.items.remove(&key) {
+                due.push(v);
+            }
+        }
+        due
+    }
+}
+
+/// Delay queue with per-key cancellation and rescheduling. Items are delivered to the channel
+/// at their due time, a single task is used for all items. An item pushed with an existing key
+/// replaces the previous one
+pub struct DelayQueue<K, T> {
+    inner: Arc<std::sync::Mutex<DelayQueueInner<K, T>>>,
+    changed: Arc<Notify>,
+    fut: task::JoinHandle<()>,
+}
+
+impl<K, T> DelayQueue<K, T>
+where
+    K: Eq + std::hash::Hash + Clone + Send + 'static,
+    T: Send + 'static,
+{
+    /// Creates a new delay queue and spawns its task
+    pub fn new(buf: usize) -> (Self, mpsc::Receiver<T>) {
+        let (tx, rx) = mpsc::channel(buf);
+        let inner = Arc::new(std::sync::Mutex::new(DelayQueueInner {
+            by_time: BTreeMap::new(),
+            items: HashMap::new(),
+            seq: 0,
+        }));
+        let changed: Arc<Notify> = <_>::default();
+        let queue = inner.clone();
+        let ch = changed.clone();
+        let fut = tokio::spawn(async move {
+            loop {
+                let notified = ch.notified();
+                tokio::pin!(notified);
+                notified.as_mut().enable();
+                let next = queue.lock().unwrap().next();
+                match next {
+                    None => notified.await,
+                    Some(t) if t <= Instant::now() => {
+                        let due = queue.lock().unwrap().pop_due(t.max(Instant::now()));
+                        for value in due {
+                            if tx.send(value).await.is_err() {
+                                return;
+                            }
+                        }
+                    }
+                    Some(t) => {
+                        tokio::select! {
+                            () = sleep_until(t) => {}
+                            () = notified => {}
+                        }
+                    }
+                }
+            }
+        });
+        (
+            Self {
+                inner,
+                changed,
+                fut,
+            },
+            rx,
+        )
+    }
+
+    /// Schedules the item, returns the previous item with the same key if replaced
+    ///
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn push(&self, key: K, value: T, fire_at: Instant) -> Option<T> {
+        let prev = self.inner.lock().unwrap().insert(key, value, fire_at);
+        self.changed.notify_one();
+        prev
+    }
+
+    /// Schedules the item after the delay
+    #[inline]
+    pub fn push_after(&self, key: K, value: T, delay: Duration) -> Option<T> {
+        self.push(key, value, Instant::now() + delay)
+    }
+
+    /// Cancels the item, returns it if not delivered yet
+    ///
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn cancel(&self, key: &K) -> Option<T> {
+        let mut inner = self.inner.lock().unwrap();
+        let (t, s, v) = inner.items.remove(key)?;
+        inner.by_time.remove(&(t, s));
+        Some(v)
+    }
+
+    /// Changes the due time of the item, returns false if the item is not found
+    ///
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn reschedule(&self, key: &K, fire_at: Instant) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        let Some((t, s, v)) = inner.items.remove(key) else {
+            return false;
+        };
+        inner.by_time.remove(&(t, s));
+        inner.insert(key.clone(), v, fire_at);
+        drop(inner);
+        self.changed.notify_one();
+        true
+    }
+
+    /// The due time of the item
+    ///
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn due_at(&self, key: &K) -> Option<Instant> {
+        self.inner.lock().unwrap().items.get(key).map(|v| v.0)
+    }
+
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn contains(&self, key: &K) -> bool {
+        self.inner.lock().unwrap().items.contains_key(key)
+    }
+
+    /// Number of scheduled items
+    ///
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().items.len()
+    }
+
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn is_empty(&self) -> bool {
+        self.inner.lock().unwrap().items.is_empty()
+    }
+}
+
+impl<K, T> Drop for DelayQueue<K, T> {
+    fn drop(&mut self) {
+        self.fut.abort();
+    }
+}
+
+/// Behavior of `QueuedTrigger::trigger` when the queue is full
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum TriggerOverflow {
+    /// Silently drop the trigger
+    #[default]
+    Drop,
+    /// Return an error
+    Reject,
+}
+
+/// Bounded trigger, each trigger corresponds to exactly one worker run (up to the queue
+/// capacity). Used by `worker_queued!`
+#[derive(Debug, Clone)]
+pub struct QueuedTrigger {
+    tx: mpsc::Sender<()>,
+    overflow: TriggerOverflow,
+}
+
+impl QueuedTrigger {
+    pub fn new(capacity: usize, overflow: TriggerOverflow) -> (Self, mpsc::Receiver<()>) {
+        let (tx, rx) = mpsc::channel(capacity.max(1));
+        (Self { tx, overflow }, rx)
+    }
+
+    /// # Errors
+    ///
+    /// Will return `Err` if the queue is full and the overflow policy is `Reject` or if the
+    /// worker is not running
+    pub fn trigger(&self) -> Result<(), Error> {
+        match self.tx.try_send(()) {
+            Ok(()) => Ok(()),
+            Err(mpsc::error::TrySendError::Full(())) => match self.overflow {
+                TriggerOverflow::Drop => Ok(()),
+                TriggerOverflow::Reject => Err(Error::internal(ERR_TRIGGER_QUEUE_FULL)),
+            },
+            Err(mpsc::error::TrySendError::Closed(())) => {
+                Err(Error::internal(ERR_WORKER_NOT_RUNNING))
+            }
+        }
+    }
+
+    /// Waits for a free queue slot
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the worker is not running
+    pub async fn trigger_wait(&self) -> Result<(), Error> {
+        self.tx
+            .send(())
+            .await
+            .map_err(|_| Error::internal(ERR_WORKER_NOT_RUNNING))
+    }
+}
+
+pub type WatchdogCallback = Arc<dyn Fn(&str, Duration) + Send + Sync>;
+
+/// Watchdog action, performed once for each stall (until the worker feeds the watchdog again)
+#[derive(Clone)]
+pub enum WatchdogAction {
+    /// Log an error
+    Log,
+    /// Call the function with the worker ID and the time passed since the last feed
+    Callback(WatchdogCallback),
+    /// Send the worker ID to the channel (e.g. to restart the worker with the factory). The ID is
+    /// dropped if the channel is full
+    Report(async_channel::Sender<String>),
+    /// Log an error and kill the process after the delay (see `process::suicide`)
+    Suicide(Duration),
+}
+
+impl fmt::Debug for WatchdogAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WatchdogAction::Log => write!(f, "Log"),
+            WatchdogAction::Callback(_) => write!(f, "Callback"),
+            WatchdogAction::Report(_) => write!(f, "Report"),
+            WatchdogAction::Suicide(d) => f.debug_tuple("Suicide").field(d).finish(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct WatchdogEntry {
+    timeout: Duration,
+    last_feed: Instant,
+    stalled: bool,
+}
+
+type WatchdogEntries = Arc<std::sync::Mutex<BTreeMap<String, WatchdogEntry>>>;
+
+/// Detects stalled worker loops. Workers feed the watchdog with their handles, if a worker
+/// misses its deadline, the watchdog action is performed
+#[derive(Debug)]
+pub struct Watchdog {
+    entries: WatchdogEntries,
+    fut: task::JoinHandle<()>,
+}
+
+impl Watchdog {
+    /// Creates a watchdog which checks the workers every `check_interval`
+    pub fn new(check_interval: Duration, action: WatchdogAction) -> Self {
+        let entries: WatchdogEntries = <_>::default();
+        let ent = entries.clone();
+        let fut = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(check_interval);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                let now = Instant::now();
+                let stalled: Vec<(String, Duration)> = ent
+                    .lock()
+                    .unwrap()
+                    .iter_mut()
+                    .filter_map(|(id, entry)| {
+                        let passed = now.saturating_duration_since(entry.last_feed);
+                        if passed > entry.timeout && !entry.stalled {
+                            entry.stalled = true;
+                            Some((id.clone(), passed))
+                        } else {
+                            None
+                        }
+                    })
+                    .collect();
+                for (id, passed) in stalled {
+                    match action {
+                        WatchdogAction::Log => {
+                            error!("worker {} stalled, not fed for {:?}", id, passed);
+                        }
+                        WatchdogAction::Callback(ref f) => f(&id, passed),
+                        WatchdogAction::Report(ref tx) => {
+                            let _r = tx.try_send(id);
+                        }
+                        WatchdogAction::Suicide(delay) => {
+                            error!("worker {} stalled, not fed for {:?}", id, passed);
+                            crate::process::suicide(delay, true);
+                        }
+                    }
+                }
+            }
+        });
+        Self { entries, fut }
+    }
+    /// Registers a worker, the worker must feed the watchdog at least once per `timeout`
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the worker is already registered
+    ///
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn register(&self, worker_id: &str, timeout: Duration) -> Result<WatchdogHandle, Error> {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.contains_key(worker_id) {
+            return Err(Error::duplicate(ERR_DUPLICATE_WORKER_ID));
+        }
+        entries.insert(
+            worker_id.to_owned(),
+            WatchdogEntry {
+                timeout,
+                last_feed: Instant::now(),
+                stalled: false,
+            },
+        );
+        Ok(WatchdogHandle {
+            worker_id: worker_id.into(),
+            entries: self.entries.clone(),
+        })
+    }
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn unregister(&self, worker_id: &str) -> bool {
+        self.entries.lock().unwrap().remove(worker_id).is_some()
+    }
+    /// Returns IDs of the workers which have missed their deadlines
+    ///
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn stalled(&self) -> Vec<String> {
+        let now = Instant::now();
+        self.entries
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, entry)| now.saturating_duration_since(entry.last_feed) > entry.timeout)
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.fut.abort();
+    }
+}
+
+/// Watchdog handle, used by workers to feed the watchdog
+#[derive(Debug, Clone)]
+pub struct WatchdogHandle {
+    worker_id: Arc<str>,
+    entries: WatchdogEntries,
+}
+
+impl WatchdogHandle {
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn feed(&self) {
+        if let Some(entry) = self.entries.lock().unwrap().get_mut(&*self.worker_id) {
+            entry.last_feed = Instant::now();
+            entry.stalled = false;
+        }
+    }
+    #[inline]
+    pub fn worker_id(&self) -> &str {
+        &self.worker_id
+    }
+}
+
+#[derive(Debug, Default)]
+struct ReadinessInner {
+    workers: std::sync::Mutex<BTreeMap<String, bool>>,
+    changed: Notify,
+}
+
+/// Startup readiness gate. Workers register and mark themselves ready, the main loop waits
+/// until all registered workers are ready. Clones share the same state
+#[derive(Debug, Default, Clone)]
+pub struct Readiness {
+    inner: Arc<ReadinessInner>,
+}
+
+impl Readiness {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Registers a worker as not ready
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the worker is already registered
+    ///
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn register(&self, worker_id: &str) -> Result<ReadinessHandle, Error> {
+        let mut workers = self.inner.workers.lock().unwrap();
+        if workers.contains_key(worker_id) {
+            return Err(Error::duplicate(ERR_DUPLICATE_WORKER_ID));
+        }
+        workers.insert(worker_id.to_owned(), false);
+        Ok(ReadinessHandle {
+            worker_id: worker_id.into(),
+            inner: self.inner.clone(),
+        })
+    }
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn unregister(&self, worker_id: &str) -> bool {
+        let removed = self
+            .inner
+            .workers
+            .lock()
+            .unwrap()
+            .remove(worker_id)
+            .is_some();
+        if removed {
+            self.inner.changed.notify_waiters();
+        }
+        removed
+    }
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn is_all_ready(&self) -> bool {
+        self.inner.workers.lock().unwrap().values().all(|v| *v)
+    }
+    /// Returns IDs of the workers which are not ready
+    ///
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn not_ready(&self) -> Vec<String> {
+        self.inner
+            .workers
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, ready)| !**ready)
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+    /// Returns the readiness state of all registered workers
+    ///
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn snapshot(&self) -> BTreeMap<String, bool> {
+        self.inner.workers.lock().unwrap().clone()
+    }
+    /// Waits until all registered workers are ready
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` with `ErrorKind::Timeout` on timeout, the message contains the list of
+    /// not ready workers
+    pub async fn wait_all_ready(&self, timeout: Duration) -> Result<(), Error> {
+        let wait = async {
+            loop {
+                let changed = self.inner.changed.notified();
+                tokio::pin!(changed);
+                changed.as_mut().enable();
+                if self.is_all_ready() {
+                    break;
+                }
+                changed.await;
+            }
+        };
+        tokio::time::timeout(timeout, wait).await.map_err(|_| {
+            Error::new(
+                ErrorKind::Timeout,
+                format!("workers not ready: {}", self.not_ready().join(", ")),
+            )
+        })
+    }
+}
+
+/// Readiness handle of a worker
+#[derive(Debug, Clone)]
+pub struct ReadinessHandle {
+    worker_id: Arc<str>,
+    inner: Arc<ReadinessInner>,
+}
+
+impl ReadinessHandle {
+    #[inline]
+    pub fn ready(&self) {
+        self.set(true);
+    }
+    #[inline]
+    pub fn not_ready(&self) {
+        self.set(false);
+    }
+    /// # Panics
+    ///
+    /// Should not panic
+    pub fn set(&self, ready: bool) {
+        if let Some(v) = self.inner.workers.lock().unwrap().get_mut(&*self.worker_id) {
+            *v = ready;
+        }
+        self.inner.changed.notify_waiters();
+    }
+    #[inline]
+    pub fn worker_id(&self) -> &str {
+        &self.worker_id
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DrainMode, KeyedTaskWorker, TaskWorker};
+    use std::collections::BTreeMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_keyed_task_worker_ordering() {
+        let processed: Arc<Mutex<BTreeMap<u32, Vec<u32>>>> = <_>::default();
+        let active = Arc::new(AtomicUsize::new(0));
+        let max_active = Arc::new(AtomicUsize::new(0));
+        let p = processed.clone();
+        let max = max_active.clone();
+        let (mut worker, tx) = KeyedTaskWorker::new(
+            move |key: u32, v: u32| {
+                let p = p.clone();
+                let active = active.clone();
+                let max_active = max.clone();
+                async move {
+                    let n = active.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_active.fetch_max(n, Ordering::SeqCst);
+                    // later items of a key complete faster
+                    tokio::time::sleep(Duration::from_millis(u64::from(10 - v % 10))).await;
+                    p.lock().unwrap().entry(key).or_default().push(v);
+                    active.fetch_sub(1, Ordering::SeqCst);
+                }
+            },
+            8,
+            3,
+        );
+        let fut = tokio::spawn(async move { worker.run().await });
+        for v in 0..10 {
+            for key in 0..5 {
+                tx.send((key, v)).await.unwrap();
+            }
+        }
+        drop(tx);
+        fut.await.unwrap();
+        let processed = processed.lock().unwrap();
+        assert_eq!(processed.len(), 5);
+        for items in processed.values() {
+            assert_eq!(*items, (0..10).collect::<Vec<u32>>());
+        }
+        assert_eq!(max_active.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_keyed_task_worker_panic() {
+        let processed: Arc<Mutex<Vec<u32>>> = <_>::default();
+        let p = processed.clone();
+        let (mut worker, tx) = KeyedTaskWorker::new(
+            move |_key: u32, v: u32| {
+                let p = p.clone();
+                async move {
+                    assert!(v != 1, "task failed");
+                    p.lock().unwrap().push(v);
+                }
+            },
+            8,
+            2,
+        );
+        for v in 0..4 {
+            tx.send((0, v)).await.unwrap();
+        }
+        drop(tx);
+        worker.run().await;
+        assert_eq!(*processed.lock().unwrap(), vec![0, 2, 3]);
+    }
+
+    async fn run_concurrent_stopped(mode: DrainMode, item_delay: Duration, ordered: bool) -> usize {
+        let processed = Arc::new(AtomicUsize::new(0));
+        let p = processed.clone();
+        let (mut worker, tx) = TaskWorker::new(
+            move |_v: u32| {
+                let p = p.clone();
+                async move {
+                    tokio::time::sleep(item_delay).await;
+                    p.fetch_add(1, Ordering::SeqCst);
+                }
+            },
+            16,
+        );
+        let controller = worker.controller();
+        for v in 0..10 {
+            tx.send(v).await.unwrap();
+        }
+        let fut = tokio::spawn(async move { worker.run_concurrent(3, ordered).await });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        controller.stop(mode);
+        // the sender is still alive, the worker must exit on the stop request
+        tokio::time::timeout(Duration::from_secs(5), fut)
+            .await
+            .unwrap()
+            .unwrap();
+        drop(tx);
+        processed.load(Ordering::SeqCst)
+    }
+
+    #[tokio::test]
+    async fn test_task_worker_concurrent_stop() {
+        for ordered in [false, true] {
+            let processed =
+                run_concurrent_stopped(DrainMode::Immediate, Duration::from_secs(60), ordered)
+                    .await;
+            assert_eq!(processed, 0);
+            let processed = run_concurrent_stopped(
+                DrainMode::DrainTimeout(Duration::from_millis(50)),
+                Duration::from_secs(60),
+                ordered,
+            )
+            .await;
+            assert_eq!(processed, 0);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_task_worker_concurrent_drain() {
+        for ordered in [false, true] {
+            let processed =
+                run_concurrent_stopped(DrainMode::Drain, Duration::from_millis(20), ordered).await;
+            assert_eq!(processed, 10);
+            let processed = run_concurrent_stopped(
+                DrainMode::DrainTimeout(Duration::from_secs(5)),
+                Duration::from_millis(20),
+                ordered,
+            )
+            .await;
+            assert_eq!(processed, 10);
         }
     }
 }